@@ -0,0 +1,321 @@
+extern crate sdl2;
+
+use std::collections::HashMap;
+use sdl2::keyboard::{Keycode, Mod};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum CommandId {
+    Delete,
+    SelectAll,
+    Deselect,
+    ToggleSelectMode,
+    Copy,
+    Cut,
+    Paste,
+    Undo,
+    Redo,
+    NudgeLeft,
+    NudgeRight,
+    NudgeUp,
+    NudgeDown,
+    NudgeLeftFar,
+    NudgeRightFar,
+    NudgeUpFar,
+    NudgeDownFar,
+    BringForward,
+    SendBackward,
+    BringToFront,
+    SendToBack,
+    ToolCircle,
+    ToolTriangle,
+    ToolRect,
+    ToolTextBox,
+    ToolLine,
+    ToolColorPicker,
+    SaveStyles,
+    UpdateStyle,
+    TextBold,
+    TextItalic,
+    TextUnderline,
+    TextStrikethrough,
+    TextGrow,
+    TextShrink,
+    ToggleRoute,
+    PinWaypoint,
+    ClearWaypoints,
+    ToggleLayers,
+    NewLayer,
+    DeleteLayer,
+    MoveToLayer,
+    SaveDocument,
+    OpenDocument,
+    ExportPdf,
+    ExportSvg,
+    NewPage,
+    DuplicatePage,
+    DeletePage,
+    RenamePage,
+    NextPage,
+    PrevPage,
+    MovePageLeft,
+    MovePageRight,
+    FollowLink,
+    ResetView,
+    ToggleMinimap,
+    CommandPalette,
+    Quit,
+}
+
+impl CommandId {
+    pub const ALL: [CommandId; 59] = [
+        CommandId::Delete, CommandId::SelectAll, CommandId::Deselect, CommandId::ToggleSelectMode,
+        CommandId::Copy, CommandId::Cut, CommandId::Paste, CommandId::Undo, CommandId::Redo,
+        CommandId::NudgeLeft, CommandId::NudgeRight, CommandId::NudgeUp, CommandId::NudgeDown,
+        CommandId::NudgeLeftFar, CommandId::NudgeRightFar, CommandId::NudgeUpFar, CommandId::NudgeDownFar,
+        CommandId::BringForward, CommandId::SendBackward, CommandId::BringToFront, CommandId::SendToBack,
+        CommandId::ToolCircle, CommandId::ToolTriangle, CommandId::ToolRect, CommandId::ToolTextBox,
+        CommandId::ToolLine, CommandId::ToolColorPicker,
+        CommandId::SaveStyles, CommandId::UpdateStyle,
+        CommandId::TextBold, CommandId::TextItalic, CommandId::TextUnderline, CommandId::TextStrikethrough,
+        CommandId::TextGrow, CommandId::TextShrink,
+        CommandId::ToggleRoute, CommandId::PinWaypoint, CommandId::ClearWaypoints,
+        CommandId::ToggleLayers, CommandId::NewLayer, CommandId::DeleteLayer, CommandId::MoveToLayer,
+        CommandId::SaveDocument, CommandId::OpenDocument, CommandId::ExportPdf, CommandId::ExportSvg,
+        CommandId::NewPage, CommandId::DuplicatePage, CommandId::DeletePage, CommandId::RenamePage,
+        CommandId::NextPage, CommandId::PrevPage, CommandId::MovePageLeft, CommandId::MovePageRight,
+        CommandId::FollowLink, CommandId::ResetView, CommandId::ToggleMinimap,
+        CommandId::CommandPalette, CommandId::Quit,
+    ];
+    //stable identifier used in keymap files
+    pub fn id(&self) -> &'static str {
+        match self {
+            CommandId::Delete => "edit.delete",
+            CommandId::SelectAll => "edit.select_all",
+            CommandId::ToggleSelectMode => "edit.toggle_select_mode",
+            CommandId::Deselect => "edit.deselect",
+            CommandId::Copy => "edit.copy",
+            CommandId::Cut => "edit.cut",
+            CommandId::Paste => "edit.paste",
+            CommandId::Undo => "edit.undo",
+            CommandId::Redo => "edit.redo",
+            CommandId::NudgeLeft => "edit.nudge_left",
+            CommandId::NudgeRight => "edit.nudge_right",
+            CommandId::NudgeUp => "edit.nudge_up",
+            CommandId::NudgeDown => "edit.nudge_down",
+            CommandId::NudgeLeftFar => "edit.nudge_left_far",
+            CommandId::NudgeRightFar => "edit.nudge_right_far",
+            CommandId::NudgeUpFar => "edit.nudge_up_far",
+            CommandId::NudgeDownFar => "edit.nudge_down_far",
+            CommandId::BringForward => "arrange.bring_forward",
+            CommandId::SendBackward => "arrange.send_backward",
+            CommandId::BringToFront => "arrange.bring_to_front",
+            CommandId::SendToBack => "arrange.send_to_back",
+            CommandId::ToolCircle => "tool.circle",
+            CommandId::ToolTriangle => "tool.triangle",
+            CommandId::ToolRect => "tool.rect",
+            CommandId::ToolTextBox => "tool.text_box",
+            CommandId::ToolLine => "tool.line",
+            CommandId::ToolColorPicker => "tool.color_picker",
+            CommandId::SaveStyles => "style.save",
+            CommandId::UpdateStyle => "style.update_from_selection",
+            CommandId::TextBold => "text.bold",
+            CommandId::TextItalic => "text.italic",
+            CommandId::TextUnderline => "text.underline",
+            CommandId::TextStrikethrough => "text.strikethrough",
+            CommandId::TextGrow => "text.grow",
+            CommandId::TextShrink => "text.shrink",
+            CommandId::ToggleRoute => "line.toggle_route",
+            CommandId::PinWaypoint => "line.pin_waypoint",
+            CommandId::ClearWaypoints => "line.clear_waypoints",
+            CommandId::ToggleLayers => "layer.toggle_panel",
+            CommandId::NewLayer => "layer.new",
+            CommandId::DeleteLayer => "layer.delete",
+            CommandId::MoveToLayer => "layer.move_selection",
+            CommandId::SaveDocument => "file.save",
+            CommandId::OpenDocument => "file.open",
+            CommandId::ExportPdf => "file.export_pdf",
+            CommandId::ExportSvg => "file.export_svg",
+            CommandId::NewPage => "page.new",
+            CommandId::DuplicatePage => "page.duplicate",
+            CommandId::DeletePage => "page.delete",
+            CommandId::RenamePage => "page.rename",
+            CommandId::NextPage => "page.next",
+            CommandId::PrevPage => "page.previous",
+            CommandId::MovePageLeft => "page.move_left",
+            CommandId::MovePageRight => "page.move_right",
+            CommandId::FollowLink => "page.follow_link",
+            CommandId::ResetView => "view.reset",
+            CommandId::ToggleMinimap => "view.toggle_minimap",
+            CommandId::CommandPalette => "app.command_palette",
+            CommandId::Quit => "app.quit",
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandId::Delete => "Delete Selection",
+            CommandId::SelectAll => "Select All",
+            CommandId::ToggleSelectMode => "Toggle Selecting Touched or Enclosed Shapes",
+            CommandId::Deselect => "Deselect",
+            CommandId::Copy => "Copy Text",
+            CommandId::Cut => "Cut Text",
+            CommandId::Paste => "Paste Text",
+            CommandId::Undo => "Undo Text Edit",
+            CommandId::Redo => "Redo Text Edit",
+            CommandId::NudgeLeft => "Nudge Left",
+            CommandId::NudgeRight => "Nudge Right",
+            CommandId::NudgeUp => "Nudge Up",
+            CommandId::NudgeDown => "Nudge Down",
+            CommandId::NudgeLeftFar => "Nudge Left 10px",
+            CommandId::NudgeRightFar => "Nudge Right 10px",
+            CommandId::NudgeUpFar => "Nudge Up 10px",
+            CommandId::NudgeDownFar => "Nudge Down 10px",
+            CommandId::BringForward => "Bring Forward",
+            CommandId::SendBackward => "Send Backward",
+            CommandId::BringToFront => "Bring to Front",
+            CommandId::SendToBack => "Send to Back",
+            CommandId::ToolCircle => "Circle Tool",
+            CommandId::ToolTriangle => "Triangle Tool",
+            CommandId::ToolRect => "Rectangle Tool",
+            CommandId::ToolTextBox => "Text Box Tool",
+            CommandId::ToolLine => "Line Tool",
+            CommandId::ToolColorPicker => "Toggle Color Picker",
+            CommandId::SaveStyles => "Save Stylesheet",
+            CommandId::UpdateStyle => "Update Style from Selection",
+            CommandId::TextBold => "Toggle Bold",
+            CommandId::TextItalic => "Toggle Italic",
+            CommandId::TextUnderline => "Toggle Underline",
+            CommandId::TextStrikethrough => "Toggle Strikethrough",
+            CommandId::TextGrow => "Increase Text Size",
+            CommandId::TextShrink => "Decrease Text Size",
+            CommandId::ToggleRoute => "Toggle Orthogonal Routing",
+            CommandId::PinWaypoint => "Pin Waypoint at Cursor",
+            CommandId::ClearWaypoints => "Clear Waypoints",
+            CommandId::ToggleLayers => "Toggle Layers Panel",
+            CommandId::NewLayer => "New Layer",
+            CommandId::DeleteLayer => "Delete Layer and Its Shapes",
+            CommandId::MoveToLayer => "Move Selection to Current Layer",
+            CommandId::SaveDocument => "Save Diagram",
+            CommandId::OpenDocument => "Reopen Saved Diagram",
+            CommandId::ExportPdf => "Export PDF",
+            CommandId::ExportSvg => "Export SVG per Page",
+            CommandId::NewPage => "New Page",
+            CommandId::DuplicatePage => "Duplicate Page",
+            CommandId::DeletePage => "Delete Page",
+            CommandId::RenamePage => "Rename Page",
+            CommandId::NextPage => "Next Page",
+            CommandId::PrevPage => "Previous Page",
+            CommandId::MovePageLeft => "Move Page Left",
+            CommandId::MovePageRight => "Move Page Right",
+            CommandId::FollowLink => "Follow Link to Page",
+            CommandId::ResetView => "Reset View",
+            CommandId::ToggleMinimap => "Toggle Minimap",
+            CommandId::CommandPalette => "Command Palette",
+            CommandId::Quit => "Quit",
+        }
+    }
+    pub fn from_id(id: &str) -> Option<CommandId> {
+        CommandId::ALL.iter().find(|c| c.id() == id).cloned()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct KeyChord {
+    pub keycode: Keycode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyChord {
+    pub fn new(keycode: Keycode, keymod: Mod) -> Self {
+        KeyChord {
+            keycode,
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        }
+    }
+    //chords that reach the keymap even while text is being edited
+    pub fn is_command_chord(&self) -> bool {
+        self.ctrl || self.alt
+    }
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut chord = KeyChord { keycode: Keycode::Escape, ctrl: false, shift: false, alt: false };
+        let mut parts: Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+        let key = parts.pop().filter(|k| !k.is_empty()).ok_or(format!("Missing key in chord {:?}", s))?;
+        for part in parts {
+            match part.to_lowercase().as_str() {
+                "ctrl" => chord.ctrl = true,
+                "shift" => chord.shift = true,
+                "alt" => chord.alt = true,
+                _ => return Err(format!("Unknown modifier {:?}", part))
+            }
+        }
+        chord.keycode = Keycode::from_name(key).ok_or(format!("Unknown key {:?}", key))?;
+        Ok(chord)
+    }
+    pub fn display(&self) -> String {
+        let mut s = String::new();
+        if self.ctrl { s.push_str("Ctrl+"); }
+        if self.shift { s.push_str("Shift+"); }
+        if self.alt { s.push_str("Alt+"); }
+        s.push_str(&self.keycode.name());
+        s
+    }
+}
+
+pub struct Keymap {
+    bindings: HashMap<KeyChord, CommandId>,
+}
+
+impl Keymap {
+    pub const PATH: &'static str = "diagram.keymap";
+
+    pub fn default_keymap() -> Self {
+        let mut keymap = Keymap { bindings: HashMap::new() };
+        keymap.add_bindings(include_str!("keymaps/default.keymap")).unwrap();
+        keymap
+    }
+    //the user keymap, if any, is layered over the default bindings
+    pub fn load_or_default(path: &str) -> Self {
+        let mut keymap = Keymap::default_keymap();
+        if let Ok(src) = std::fs::read_to_string(path) {
+            if let Err(e) = keymap.add_bindings(&src) {
+                eprintln!("Error loading keymap {}: {}", path, e);
+            }
+        }
+        keymap
+    }
+    pub fn add_bindings(&mut self, src: &str) -> Result<(), String> {
+        for (n, line) in src.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let mut kv = line.rsplitn(2, '=');
+            let (cmd, chord) = match (kv.next(), kv.next()) {
+                (Some(cmd), Some(chord)) => (cmd.trim(), chord.trim()),
+                _ => return Err(format!("Line {}: expected `chord = command`", n + 1))
+            };
+            let chord = KeyChord::parse(chord).map_err(|e| format!("Line {}: {}", n + 1, e))?;
+            if cmd == "none" {
+                self.bindings.remove(&chord);
+                continue;
+            }
+            let cmd = CommandId::from_id(cmd).ok_or(format!("Line {}: unknown command {:?}", n + 1, cmd))?;
+            self.bind(chord, cmd);
+        }
+        Ok(())
+    }
+    pub fn bind(&mut self, chord: KeyChord, cmd: CommandId) {
+        self.bindings.insert(chord, cmd);
+    }
+    pub fn lookup(&self, chord: &KeyChord) -> Option<CommandId> {
+        self.bindings.get(chord).cloned()
+    }
+    pub fn bindings_for(&self, cmd: CommandId) -> Vec<KeyChord> {
+        let mut chords: Vec<KeyChord> = self.bindings.iter().filter(|(_, c)| **c == cmd).map(|(k, _)| *k).collect();
+        chords.sort_by_key(|k| k.display());
+        chords
+    }
+}
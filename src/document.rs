@@ -0,0 +1,246 @@
+use crate::primitives::*;
+use crate::layers::Layer;
+use crate::textedit::TextBox;
+use crate::hexcolor::{color_to_hex, hex_to_color};
+
+//a shape as read from a document, with the style it was made with and its text
+pub struct DocShape {
+    pub shape: Shape,
+    pub style: Option<String>,
+    pub text: Option<TextBox>,
+    pub link: Option<usize>,        // Index of the page it links to
+}
+
+pub struct DocLayer {
+    pub layer: Layer,
+    pub shapes: Vec<DocShape>,      // In drawing order
+}
+
+pub struct DocPage {
+    pub name: String,
+    pub camera: Point,
+    pub layers: Vec<DocLayer>,
+}
+
+impl DocPage {
+    fn new(name: &str) -> Self {
+        DocPage { name: name.to_string(), camera: Point::origin(), layers: Vec::new() }
+    }
+}
+
+//a saved diagram. it's ini-like, each [page] section is followed by its [layer] sections, and each
+//[layer] by a [shape] or [line] section for each of its shapes, bottom first. a [text] section holds
+//the text of the shape before it. layers before the first [page] go on a page of their own
+pub struct Document {
+    pub pages: Vec<DocPage>,
+}
+
+enum Section {
+    Page,
+    Layer,
+    Shape,
+    Text(String),
+}
+
+impl Document {
+    pub const PATH: &'static str = "diagram.graph";
+
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut pages: Vec<DocPage> = Vec::new();
+        let mut section: Option<Section> = None;
+        for (n, line) in src.lines().enumerate() {
+            let trimmed = line.trim();
+            let err = |e: String| format!("Line {}: {}", n + 1, e);
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                finish_text(&mut pages, &mut section).map_err(err)?;
+                let name = trimmed[1..trimmed.len()-1].trim();
+                if name == "layer" && pages.is_empty() {
+                    pages.push(DocPage::new("Page 1"));
+                }
+                if name != "page" && name != "layer" && pages.last().is_none_or(|p| p.layers.is_empty()) {
+                    return Err(err(format!("[{}] before the first [layer]", name)));
+                }
+                let shapes = pages.last_mut().and_then(|p| p.layers.last_mut()).map(|l| &mut l.shapes);
+                let new_shape = |shape| DocShape { shape, style: None, text: None, link: None };
+                section = Some(match (name, shapes) {
+                    ("page", _) => {
+                        pages.push(DocPage::new(""));
+                        Section::Page
+                    }
+                    ("layer", _) => {
+                        pages.last_mut().unwrap().layers.push(DocLayer { layer: Layer::new(""), shapes: Vec::new() });
+                        Section::Layer
+                    }
+                    ("shape", Some(shapes)) => {
+                        shapes.push(new_shape(Shape::Polygon(DrawPolygon::default())));
+                        Section::Shape
+                    }
+                    ("line", Some(shapes)) => {
+                        shapes.push(new_shape(Shape::Line(DrawLine::default())));
+                        Section::Shape
+                    }
+                    ("text", Some(shapes)) if !shapes.is_empty() => Section::Text(String::new()),
+                    _ => return Err(err(format!("Unexpected section [{}]", name)))
+                });
+                continue;
+            }
+            //text is kept as is for the text box to parse, spaces and all
+            if let Some(Section::Text(ref mut text)) = section {
+                text.push_str(line);
+                text.push('\n');
+                continue;
+            }
+            if trimmed.is_empty() || trimmed.starts_with(';') {
+                continue;
+            }
+            let mut kv = trimmed.splitn(2, '=');
+            let (key, value) = match (kv.next(), kv.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => return Err(err("expected `property = value`".to_string()))
+            };
+            let page = pages.last_mut();
+            match (&section, page) {
+                (Some(Section::Page), Some(page)) => match key {
+                    "name" => page.name = value.to_string(),
+                    "camera" => page.camera = parse_point(value).map_err(err)?,
+                    _ => return Err(err(format!("Unknown page property {:?}", key)))
+                },
+                (Some(Section::Layer), Some(page)) => page.layers.last_mut().unwrap().layer.set_field(key, value).map_err(err)?,
+                (Some(Section::Shape), Some(page)) => {
+                    let doc_shape = page.layers.last_mut().unwrap().shapes.last_mut().unwrap();
+                    match key {
+                        "style" => doc_shape.style = Some(value.to_string()),
+                        "link" => doc_shape.link = Some(value.parse::<usize>().map_err(|_| err(format!("Invalid page {:?}", value)))?),
+                        _ => set_shape_field(&mut doc_shape.shape, key, value).map_err(err)?
+                    }
+                }
+                _ => return Err(err("property outside of a section".to_string()))
+            }
+        }
+        finish_text(&mut pages, &mut section)?;
+        if pages.is_empty() || pages.iter().any(|p| p.layers.is_empty()) {
+            return Err("Every page needs at least one layer".to_string());
+        }
+        let npages = pages.len();
+        let mut links = pages.iter().flat_map(|p| p.layers.iter()).flat_map(|l| l.shapes.iter()).filter_map(|s| s.link);
+        if let Some(link) = links.find(|link| *link >= npages) {
+            return Err(format!("Link to page {} of {}", link, npages));
+        }
+        Ok(Document { pages })
+    }
+    pub fn load(path: &str) -> Result<Self, String> {
+        let src = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        Document::parse(&src)
+    }
+}
+
+fn finish_text(pages: &mut [DocPage], section: &mut Option<Section>) -> Result<(), String> {
+    if let Some(Section::Text(text)) = section.take() {
+        let doc_shape = pages.last_mut().and_then(|p| p.layers.last_mut()).and_then(|l| l.shapes.last_mut()).unwrap();
+        doc_shape.text = Some(TextBox::parse(&text)?);
+    }
+    Ok(())
+}
+
+fn parse_point(s: &str) -> Result<Point, String> {
+    let xy: Vec<f32> = s.split_whitespace().filter_map(|n| n.parse::<f32>().ok()).collect();
+    match xy[..] {
+        [x, y] => Ok(Point::new(x, y)),
+        _ => Err(format!("Invalid point {:?}", s))
+    }
+}
+
+//documents are written a section at a time, straight from the shapes on the canvas
+pub fn write_page(out: &mut String, name: &str, camera: &Point) {
+    out.push_str("[page]\n");
+    out.push_str(&format!("name = {}\n", name));
+    out.push_str(&format!("camera = {} {}\n", camera.x, camera.y));
+    out.push('\n');
+}
+
+pub fn write_layer(out: &mut String, layer: &Layer) {
+    out.push_str("[layer]\n");
+    layer.write(out);
+    out.push('\n');
+}
+
+pub fn write_shape(out: &mut String, shape: &Shape, style: Option<&str>, text: Option<&TextBox>, link: Option<usize>) {
+    let point = |p: &Point| format!("{} {}", p.x, p.y);
+    match shape {
+        Shape::Polygon(poly) => {
+            out.push_str("[shape]\n");
+            out.push_str(&format!("prim = {}\n", poly.prim.name()));
+            out.push_str(&format!("offset = {}\n", point(&poly.rect.offset)));
+            out.push_str(&format!("size = {}\n", point(&poly.rect.size)));
+            out.push_str(&format!("radians = {}\n", poly.rect.rot.0));
+            out.push_str(&format!("fill = {}\n", poly.fill));
+            out.push_str(&format!("fill_color = {}\n", color_to_hex(&poly.color)));
+            out.push_str(&format!("stroke_color = {}\n", color_to_hex(&poly.stroke_color)));
+            out.push_str(&format!("stroke_width = {}\n", poly.line_width));
+        }
+        Shape::Line(line) => {
+            out.push_str("[line]\n");
+            out.push_str(&format!("p1 = {}\n", point(&line.p1)));
+            out.push_str(&format!("p2 = {}\n", point(&line.p2)));
+            if !line.waypoints.is_empty() {
+                let waypoints: Vec<String> = line.waypoints.iter().map(point).collect();
+                out.push_str(&format!("waypoints = {}\n", waypoints.join(", ")));
+            }
+            out.push_str(&format!("color = {}\n", color_to_hex(&line.color)));
+            out.push_str(&format!("width = {}\n", line.line_width));
+            out.push_str(&format!("start_marker = {}\n", line.start_marker.name()));
+            out.push_str(&format!("end_marker = {}\n", line.end_marker.name()));
+            out.push_str(&format!("route = {}\n", line.route.name()));
+            out.push_str(&format!("label_position = {}\n", line.label.t));
+            out.push_str(&format!("label_offset = {}\n", line.label.offset));
+            out.push_str(&format!("label_follow = {}\n", line.label.follow_line));
+        }
+    }
+    if let Some(style) = style {
+        out.push_str(&format!("style = {}\n", style));
+    }
+    if let Some(link) = link {
+        out.push_str(&format!("link = {}\n", link));
+    }
+    if let Some(tb) = text {
+        out.push_str("[text]\n");
+        out.push_str(&tb.write());
+    }
+    out.push('\n');
+}
+
+fn set_shape_field(shape: &mut Shape, key: &str, value: &str) -> Result<(), String> {
+    let num = || value.parse::<f32>().map_err(|_| format!("Invalid number {:?}", value));
+    let color = || hex_to_color(value).ok_or(format!("Invalid color {:?}", value));
+    let bool_val = || value.parse::<bool>().map_err(|_| format!("Invalid bool {:?}", value));
+    let point = parse_point;
+    let marker = || LineMarker::from_name(value).ok_or(format!("Invalid line marker {:?}", value));
+    match shape {
+        Shape::Polygon(ref mut poly) => match key {
+            "prim" => poly.prim = PrimType::from_name(value).ok_or(format!("Unknown shape {:?}", value))?,
+            "offset" => poly.rect.offset = point(value)?,
+            "size" => poly.rect.size = point(value)?,
+            "radians" => poly.rect.set_radians(Radians(num()?)),
+            "fill" => poly.fill = bool_val()?,
+            "fill_color" => poly.color = color()?,
+            "stroke_color" => poly.stroke_color = color()?,
+            "stroke_width" => poly.line_width = num()?,
+            _ => return Err(format!("Unknown shape property {:?}", key))
+        },
+        Shape::Line(ref mut line) => match key {
+            "p1" => line.p1 = point(value)?,
+            "p2" => line.p2 = point(value)?,
+            "waypoints" => line.waypoints = value.split(',').map(point).collect::<Result<Vec<Point>, String>>()?,
+            "color" => line.color = color()?,
+            "width" => line.line_width = num()?,
+            "start_marker" => line.start_marker = marker()?,
+            "end_marker" => line.end_marker = marker()?,
+            "route" => line.route = LineRoute::from_name(value).ok_or(format!("Invalid line route {:?}", value))?,
+            "label_position" => line.label.t = num()?,
+            "label_offset" => line.label.offset = num()?,
+            "label_follow" => line.label.follow_line = bool_val()?,
+            _ => return Err(format!("Unknown line property {:?}", key))
+        }
+    }
+    Ok(())
+}
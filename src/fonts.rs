@@ -0,0 +1,265 @@
+extern crate freetype;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use freetype::library::Library;
+use freetype::face::{Face, StyleFlag};
+use freetype::{Matrix, Vector};
+use crate::shaping::Shaper;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl FontStyle {
+    pub const ALL: [FontStyle; 4] = [FontStyle::Regular, FontStyle::Bold, FontStyle::Italic, FontStyle::BoldItalic];
+
+    pub fn new(bold: bool, italic: bool) -> Self {
+        match (bold, italic) {
+            (false, false) => FontStyle::Regular,
+            (true, false) => FontStyle::Bold,
+            (false, true) => FontStyle::Italic,
+            (true, true) => FontStyle::BoldItalic,
+        }
+    }
+    pub fn is_bold(&self) -> bool {
+        *self == FontStyle::Bold || *self == FontStyle::BoldItalic
+    }
+    pub fn is_italic(&self) -> bool {
+        *self == FontStyle::Italic || *self == FontStyle::BoldItalic
+    }
+    fn from_flags(flags: StyleFlag) -> Self {
+        FontStyle::new(flags.contains(StyleFlag::BOLD), flags.contains(StyleFlag::ITALIC))
+    }
+}
+
+pub struct FontConfig {
+    pub dirs: Vec<PathBuf>,
+    pub families: Vec<String>,
+    pub pixel_size: u32,
+}
+
+impl FontConfig {
+    pub const PATH: &'static str = "diagram.fonts";
+
+    pub fn default_config() -> Self {
+        let mut config = FontConfig { dirs: Vec::new(), families: Vec::new(), pixel_size: 24 };
+        config.add_settings(include_str!("fonts/default.fonts")).unwrap();
+        config
+    }
+    //settings in the user config replace the defaults
+    pub fn load_or_default(path: &str) -> Self {
+        let mut config = FontConfig::default_config();
+        if let Ok(src) = std::fs::read_to_string(path) {
+            if let Err(e) = config.add_settings(&src) {
+                eprintln!("Error loading font config {}: {}", path, e);
+            }
+        }
+        config
+    }
+    pub fn add_settings(&mut self, src: &str) -> Result<(), String> {
+        for (n, line) in src.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let mut kv = line.splitn(2, '=');
+            let (key, value) = match (kv.next(), kv.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => return Err(format!("Line {}: expected `key = value`", n + 1))
+            };
+            let list = || value.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+            match key {
+                "dirs" => self.dirs = list().map(|d| expand_home(&d)).collect(),
+                "families" => self.families = list().collect(),
+                "size" => self.pixel_size = value.parse::<u32>().ok().filter(|s| *s > 0)
+                    .ok_or(format!("Line {}: invalid size {:?}", n + 1, value))?,
+                _ => return Err(format!("Line {}: unknown font setting {:?}", n + 1, key))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn expand_home(dir: &str) -> PathBuf {
+    match (dir.starts_with("~/"), std::env::var("HOME")) {
+        (true, Ok(home)) => Path::new(&home).join(&dir[2..]),
+        _ => PathBuf::from(dir)
+    }
+}
+
+struct FontFile {
+    path: PathBuf,
+    index: isize,
+    family: String,
+    style: FontStyle,
+    style_name: String,
+}
+
+pub struct FontFace {
+    pub ft: Face,
+    pub shaper: Shaper,
+    pub family: String,
+    pub style: FontStyle,
+    pub embolden: bool,             // Bold is synthesized when glyphs are rasterized
+    pub slant: f32,                 // Horizontal shear of a synthesized italic
+    pub data: &'static [u8],        // The font file, for writers that embed it
+}
+
+impl FontFace {
+    pub fn has_char(&self, ch: char) -> bool {
+        self.ft.get_char_index(ch as usize) != 0
+    }
+}
+
+//loaded faces are referred to by their index, fallback chains list them in the order they're tried
+pub struct FontManager {
+    lib: Library,
+    files: Vec<FontFile>,
+    faces: Vec<FontFace>,
+    loaded: HashMap<(String, FontStyle), usize>,
+    chains: HashMap<FontStyle, Vec<usize>>,
+    pixel_size: u32,
+    bundled: Rc<Vec<u8>>,               // The bundled font for FreeType, every style's face shares it
+}
+
+impl FontManager {
+    //glyphs are rasterized at this multiple of the pixel size so their distance fields stay sharp
+    pub const RASTER_SCALE: u32 = 2;
+    //synthesized italics are slanted by about 12 degrees, FreeType matrices are 16.16 fixed point
+    const OBLIQUE_SHEAR: i32 = 0x0366A;
+    const BUNDLED_FAMILY: &'static str = "DejaVu Sans";
+    const BUNDLED_FONT: &'static [u8] = include_bytes!("fonts/DejaVuSans.ttf");
+
+    pub fn new(config: &FontConfig) -> Result<Self, String> {
+        let lib = Library::init().map_err(|e| format!("Could not init freetype: {:?}", e))?;
+        let files = FontManager::discover(&lib, &config.dirs);
+        let mut fonts = FontManager {
+            lib, files,
+            faces: Vec::new(),
+            loaded: HashMap::new(),
+            chains: HashMap::new(),
+            pixel_size: config.pixel_size,
+            bundled: Rc::new(FontManager::BUNDLED_FONT.to_vec()),
+        };
+        for style in FontStyle::ALL.iter() {
+            let mut chain: Vec<usize> = config.families.iter()
+                .filter_map(|family| fonts.load_family(family, *style)).collect();
+            chain.push(fonts.load_bundled(*style)?);
+            chain.dedup();
+            fonts.chains.insert(*style, chain);
+        }
+        if fonts.faces[fonts.chains[&FontStyle::Regular][0]].family == FontManager::BUNDLED_FAMILY {
+            eprintln!("None of the configured font families were found, using bundled {}", FontManager::BUNDLED_FAMILY);
+        }
+        Ok(fonts)
+    }
+    fn discover(lib: &Library, dirs: &[PathBuf]) -> Vec<FontFile> {
+        let mut paths = Vec::new();
+        for dir in dirs {
+            FontManager::find_font_paths(dir, &mut paths);
+        }
+        let mut files = Vec::new();
+        for path in paths {
+            let mut index = 0;
+            //collections hold several faces in one file
+            while let Ok(face) = lib.new_face(&path, index) {
+                if let Some(family) = face.family_name() {
+                    files.push(FontFile {
+                        path: path.clone(),
+                        index,
+                        family,
+                        style: FontStyle::from_flags(face.style_flags()),
+                        style_name: face.style_name().unwrap_or_default()
+                    });
+                }
+                index += 1;
+                if index >= face.num_faces() as isize {
+                    break;
+                }
+            }
+        }
+        files
+    }
+    fn find_font_paths(dir: &Path, paths: &mut Vec<PathBuf>) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.is_dir() {
+                FontManager::find_font_paths(&path, paths);
+                continue;
+            }
+            let ext = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+            if let Some("ttf") | Some("otf") | Some("ttc") = ext.as_deref() {
+                paths.push(path);
+            }
+        }
+    }
+    //`file_style` is the style of the font file, styles it lacks are synthesized
+    fn add_face(&mut self, ft: Face, font_data: &'static [u8], family: &str, style: FontStyle, file_style: FontStyle) -> Result<usize, String> {
+        ft.set_pixel_sizes(0, self.pixel_size * FontManager::RASTER_SCALE).map_err(|e| format!("Could not size font {}: {:?}", family, e))?;
+        let oblique = style.is_italic() && !file_style.is_italic();
+        if oblique {
+            let mut shear = Matrix { xx: 0x10000, xy: FontManager::OBLIQUE_SHEAR.into(), yx: 0, yy: 0x10000 };
+            ft.set_transform(&mut shear, &mut Vector { x: 0, y: 0 });
+        }
+        let slant = if oblique { FontManager::OBLIQUE_SHEAR as f32 / 0x10000 as f32 } else { 0. };
+        let shaper = Shaper::new(font_data, self.pixel_size).ok_or(format!("Could not parse font {} for shaping", family))?;
+        let embolden = style.is_bold() && !file_style.is_bold();
+        self.faces.push(FontFace { ft, shaper, family: family.to_string(), style, embolden, slant, data: font_data });
+        let id = self.faces.len() - 1;
+        self.loaded.insert((family.to_lowercase(), style), id);
+        Ok(id)
+    }
+    //only the regular face is bundled, the other styles are synthesized from it
+    fn load_bundled(&mut self, style: FontStyle) -> Result<usize, String> {
+        let ft = self.lib.new_memory_face(self.bundled.clone(), 0)
+            .map_err(|e| format!("Could not load bundled font: {:?}", e))?;
+        self.add_face(ft, FontManager::BUNDLED_FONT, FontManager::BUNDLED_FAMILY, style, FontStyle::Regular)
+    }
+    fn find_file(&self, key: &str, style: FontStyle) -> Option<(PathBuf, isize, String, FontStyle)> {
+        self.files.iter()
+            .filter(|f| f.family.to_lowercase() == key && f.style == style)
+            .min_by_key(|f| f.style_name.len())
+            .map(|f| (f.path.clone(), f.index, f.family.clone(), f.style))
+    }
+    //loads the face of a family in the requested style, the regular face is used
+    //with a synthesized style when the family doesn't have one
+    pub fn load_family(&mut self, family: &str, style: FontStyle) -> Option<usize> {
+        let key = family.to_lowercase();
+        if let Some(id) = self.loaded.get(&(key.clone(), style)) {
+            return Some(*id);
+        }
+        let (path, index, family, file_style) = self.find_file(&key, style)
+            .or_else(|| self.find_file(&key, FontStyle::Regular))?;
+        let result = std::fs::read(&path).map_err(|e| format!("Could not read {}: {}", path.display(), e))
+            .and_then(|data| {
+                let ft = self.lib.new_face(&path, index).map_err(|e| format!("Could not load {}: {:?}", path.display(), e))?;
+                //the shaper borrows the font data for as long as the app runs
+                let font_data: &'static [u8] = Box::leak(data.into_boxed_slice());
+                self.add_face(ft, font_data, &family, style, file_style)
+            });
+        result.map_err(|e| eprintln!("{}", e)).ok()
+    }
+    pub fn face(&self, id: usize) -> &FontFace {
+        &self.faces[id]
+    }
+    pub fn chain(&self, style: FontStyle) -> &[usize] {
+        &self.chains[&style]
+    }
+    //first face in the fallback chain with a glyph for the char
+    pub fn font_for_char(&self, style: FontStyle, ch: char) -> usize {
+        let chain = self.chain(style);
+        chain.iter().find(|id| self.faces[**id].has_char(ch)).cloned().unwrap_or(chain[0])
+    }
+    pub fn pixel_size(&self) -> u32 {
+        self.pixel_size
+    }
+}
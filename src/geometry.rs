@@ -0,0 +1,114 @@
+use crate::primitives::Point;
+
+//pixels a click can miss a line or an edge by and still hit it. hit tests are done in pixel space,
+//so it's the same on screen however big the shape is
+pub const HIT_TOLERANCE: f32 = 4.;
+
+//beziers are searched by sampling them this many times, then narrowing down around the closest sample
+const BEZIER_SAMPLES: usize = 32;
+const BEZIER_REFINE: usize = 24;
+
+fn cross(o: &Point, a: &Point, b: &Point) -> f32 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+//closest point to `p` on the segment from `a` to `b`
+pub fn closest_on_segment(p: &Point, a: &Point, b: &Point) -> Point {
+    let ab = *b - *a;
+    let len2 = ab.x * ab.x + ab.y * ab.y;
+    if len2 == 0. {
+        return *a;
+    }
+    let t = f32::clamp(((p.x - a.x) * ab.x + (p.y - a.y) * ab.y) / len2, 0., 1.);
+    Point::new(a.x + ab.x * t, a.y + ab.y * t)
+}
+
+pub fn dist_to_segment(p: &Point, a: &Point, b: &Point) -> f32 {
+    p.dist(&closest_on_segment(p, a, b))
+}
+
+//distance to the closest segment between consecutive points, infinite for no points
+pub fn dist_to_polyline(p: &Point, pts: &[Point]) -> f32 {
+    match pts {
+        [] => f32::INFINITY,
+        [a] => p.dist(a),
+        _ => pts.windows(2).map(|s| dist_to_segment(p, &s[0], &s[1])).fold(f32::INFINITY, f32::min)
+    }
+}
+
+//like dist_to_polyline with the last point joined back to the first
+pub fn dist_to_polygon_edge(p: &Point, pts: &[Point]) -> f32 {
+    match pts.first() {
+        Some(first) => f32::min(dist_to_polyline(p, pts), dist_to_segment(p, &pts[pts.len() - 1], first)),
+        None => f32::INFINITY
+    }
+}
+
+//point of the cubic bezier with control points `c` at `t` in 0..1
+pub fn bezier_point(c: &[Point; 4], t: f32) -> Point {
+    let u = 1. - t;
+    let w = [u * u * u, 3. * u * u * t, 3. * u * t * t, t * t * t];
+    Point::new(
+        c.iter().zip(w.iter()).map(|(p, w)| p.x * w).sum(),
+        c.iter().zip(w.iter()).map(|(p, w)| p.y * w).sum()
+    )
+}
+
+pub fn dist_to_bezier(p: &Point, c: &[Point; 4]) -> f32 {
+    let dist = |t: f32| p.dist(&bezier_point(c, t));
+    let step = 1. / BEZIER_SAMPLES as f32;
+    let closest = (0..=BEZIER_SAMPLES).map(|i| i as f32 * step)
+        .min_by(|a, b| dist(*a).partial_cmp(&dist(*b)).unwrap()).unwrap();
+    //the distance only has one minimum this close to the closest sample, so a ternary search finds it
+    let (mut lo, mut hi) = (f32::max(closest - step, 0.), f32::min(closest + step, 1.));
+    for _ in 0..BEZIER_REFINE {
+        let (m1, m2) = (lo + (hi - lo) / 3., hi - (hi - lo) / 3.);
+        if dist(m1) < dist(m2) { hi = m2; } else { lo = m1; }
+    }
+    f32::min(dist((lo + hi) / 2.), dist(closest))
+}
+
+//where the segments from `a` to `b` and `c` to `d` meet. segments that overlap along a line meet at an end of the overlap
+pub fn segment_intersection(a: &Point, b: &Point, c: &Point, d: &Point) -> Option<Point> {
+    let (d1, d2) = (cross(a, b, c), cross(a, b, d));
+    let (d3, d4) = (cross(c, d, a), cross(c, d, b));
+    if ((d1 > 0. && d2 < 0.) || (d1 < 0. && d2 > 0.)) && ((d3 > 0. && d4 < 0.) || (d3 < 0. && d4 > 0.)) {
+        let t = d3 / (d3 - d4);
+        return Some(Point::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t));
+    }
+    //the segments touch at an end, or are collinear
+    let on = |p: &Point, s1: &Point, s2: &Point, side: f32| side == 0.
+        && p.x >= f32::min(s1.x, s2.x) && p.x <= f32::max(s1.x, s2.x)
+        && p.y >= f32::min(s1.y, s2.y) && p.y <= f32::max(s1.y, s2.y);
+    if on(c, a, b, d1) { Some(*c) }
+    else if on(d, a, b, d2) { Some(*d) }
+    else if on(a, c, d, d3) { Some(*a) }
+    else if on(b, c, d, d4) { Some(*b) }
+    else { None }
+}
+
+pub fn segments_intersect(a: &Point, b: &Point, c: &Point, d: &Point) -> bool {
+    segment_intersection(a, b, c, d).is_some()
+}
+
+//whether any segment of one polyline crosses or touches any segment of the other
+pub fn polylines_intersect(a: &[Point], b: &[Point]) -> bool {
+    a.windows(2).any(|s| b.windows(2).any(|t| segments_intersect(&s[0], &s[1], &t[0], &t[1])))
+}
+
+//even-odd rule, so it works for concave and self intersecting polygons. the last point joins back to the first
+pub fn in_polygon(pts: &[Point], p: &Point) -> bool {
+    let mut inside = false;
+    for (i, a) in pts.iter().enumerate() {
+        let b = &pts[(i + 1) % pts.len()];
+        if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) * (b.x - a.x) / (b.y - a.y) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+//inside the polygon or within `tolerance` pixels of its edge
+pub fn polygon_hit(pts: &[Point], p: &Point, tolerance: f32) -> bool {
+    in_polygon(pts, p) || dist_to_polygon_edge(p, pts) <= tolerance
+}
@@ -0,0 +1,191 @@
+extern crate gl;
+extern crate nalgebra_glm;
+
+use std::collections::HashMap;
+use nalgebra_glm as glm;
+use gl::types::*;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph {
+    pub size: glm::Vec2,
+    pub bearing: glm::Vec2,          // Offset from baseline to left/top of glyph
+    pub advance: f32,
+    pub padding: f32,                // Border around the glyph in the texture, not part of size
+    pub uv: glm::Vec4,               // Top left and bottom right texture coords in the atlas
+}
+
+//font the glyph belongs to and its glyph ID in that font
+pub type GlyphKey = (usize, u32);
+
+pub struct GlyphBitmap<'a> {
+    pub width: i32,
+    pub rows: i32,
+    pub pitch: i32,
+    pub buffer: &'a [u8],
+}
+
+//signed distance from each pixel to the glyph outline, 0.5 is on the edge and inside is above it.
+//the field is padded by `spread` pixels on each side so outlines and shadows have room to draw
+pub fn distance_field(bitmap: &GlyphBitmap, spread: i32) -> (Vec<u8>, i32, i32) {
+    let (w, h) = (bitmap.width + 2 * spread, bitmap.rows + 2 * spread);
+    let inside = |x: i32, y: i32| {
+        let (bx, by) = (x - spread, y - spread);
+        bx >= 0 && by >= 0 && bx < bitmap.width && by < bitmap.rows &&
+            bitmap.buffer[(by * bitmap.pitch + bx) as usize] >= 128
+    };
+    let mask: Vec<bool> = (0..h).flat_map(|y| (0..w).map(move |x| (x, y))).map(|(x, y)| inside(x, y)).collect();
+    let mut field = Vec::with_capacity((w * h) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let is_in = mask[(y * w + x) as usize];
+            let mut min_sq = (spread * spread) as f32;
+            for sy in std::cmp::max(y - spread, 0)..std::cmp::min(y + spread + 1, h) {
+                for sx in std::cmp::max(x - spread, 0)..std::cmp::min(x + spread + 1, w) {
+                    if mask[(sy * w + sx) as usize] != is_in {
+                        let d = ((sx - x) * (sx - x) + (sy - y) * (sy - y)) as f32;
+                        min_sq = f32::min(min_sq, d);
+                    }
+                }
+            }
+            let d = f32::max(min_sq.sqrt() - 0.5, 0.);
+            let signed = if is_in { d } else { -d };
+            let v = 0.5 + signed / (2. * spread as f32);
+            field.push((v.clamp(0., 1.) * 255.).round() as u8);
+        }
+    }
+    (field, w, h)
+}
+
+//single red channel texture that glyphs are packed into row by row as they're first drawn
+pub struct GlyphAtlas {
+    texture: GLuint,
+    glyphs: HashMap<GlyphKey, Glyph>,
+    cursor: glm::TVec2<i32>,
+    row_height: i32,
+    generation: u32,
+}
+
+impl GlyphAtlas {
+    pub const SIZE: i32 = 1024;
+    //empty space between glyphs, shadows are sampled from up to this far outside a glyph
+    pub const PADDING: i32 = 8;
+
+    pub fn new() -> Self {
+        let mut texture: GLuint = 0;
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RED as GLint,
+                GlyphAtlas::SIZE,
+                GlyphAtlas::SIZE,
+                0,
+                gl::RED,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null()
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        GlyphAtlas::erase(texture);
+        GlyphAtlas {
+            texture,
+            glyphs: HashMap::new(),
+            cursor: glm::vec2(GlyphAtlas::PADDING, GlyphAtlas::PADDING),
+            row_height: 0,
+            generation: 0,
+        }
+    }
+    pub fn texture(&self) -> GLuint {
+        self.texture
+    }
+    pub fn get(&self, key: GlyphKey) -> Option<Glyph> {
+        self.glyphs.get(&key).cloned()
+    }
+    //changes every time the atlas is cleared, texture coords from an older generation are stale
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+    //a new texture's contents are undefined and a cleared one still has the old glyphs, the padding has to read as empty
+    fn erase(texture: GLuint) {
+        let zeros = vec![0u8; (GlyphAtlas::SIZE * GlyphAtlas::SIZE) as usize];
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                GlyphAtlas::SIZE,
+                GlyphAtlas::SIZE,
+                gl::RED,
+                gl::UNSIGNED_BYTE,
+                zeros.as_ptr() as *const GLvoid
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+    fn clear(&mut self) {
+        GlyphAtlas::erase(self.texture);
+        self.glyphs.clear();
+        self.cursor = glm::vec2(GlyphAtlas::PADDING, GlyphAtlas::PADDING);
+        self.row_height = 0;
+        self.generation += 1;
+    }
+    //the glyph's layout metrics are passed in, they may differ from the bitmap's pixel size
+    pub fn insert(&mut self, key: GlyphKey, bitmap: &GlyphBitmap, metrics: Glyph) -> Glyph {
+        let (w, h) = (bitmap.width, bitmap.rows);
+        if self.cursor.x + w + GlyphAtlas::PADDING > GlyphAtlas::SIZE {
+            self.cursor = glm::vec2(GlyphAtlas::PADDING, self.cursor.y + self.row_height + GlyphAtlas::PADDING);
+            self.row_height = 0;
+        }
+        if self.cursor.y + h + GlyphAtlas::PADDING > GlyphAtlas::SIZE {
+            self.clear();
+        }
+        if w > 0 && h > 0 {
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, self.texture);
+                gl::PixelStorei(gl::UNPACK_ROW_LENGTH, bitmap.pitch);
+                gl::TexSubImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    self.cursor.x,
+                    self.cursor.y,
+                    w,
+                    h,
+                    gl::RED,
+                    gl::UNSIGNED_BYTE,
+                    bitmap.buffer.as_ptr() as *const GLvoid
+                );
+                gl::PixelStorei(gl::UNPACK_ROW_LENGTH, 0);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+            }
+        }
+        let size = GlyphAtlas::SIZE as f32;
+        let uv = glm::vec4(
+            self.cursor.x as f32 / size, self.cursor.y as f32 / size,
+            (self.cursor.x + w) as f32 / size, (self.cursor.y + h) as f32 / size);
+        let glyph = Glyph { uv, ..metrics };
+        self.glyphs.insert(key, glyph);
+        self.cursor.x += w + GlyphAtlas::PADDING;
+        self.row_height = std::cmp::max(self.row_height, h);
+        glyph
+    }
+}
+
+impl Default for GlyphAtlas {
+    fn default() -> Self {
+        GlyphAtlas::new()
+    }
+}
+
+impl Drop for GlyphAtlas {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.texture); }
+    }
+}
@@ -1,9 +1,11 @@
 extern crate gl;
-use crate::primitives::{PrimType, DrawCtx, RotateRect};
+use crate::primitives::{PrimType, DrawCtx, RotateRect, Rect, Point, InBounds, ShapeBuilder, Shape, Radians, rgb_to_f32};
 use crate::render_gl::SendUniforms;
+use crate::render_text::RenderText;
 use gl::types::*;
+use nalgebra_glm as glm;
 
-pub struct HexColor(RotateRect);
+pub struct HexColor(RotateRect, PrimType);
 
 impl HexColor {
     pub unsafe fn buffer_verts(verts: &[f32]) -> GLuint {
@@ -27,26 +29,310 @@ impl HexColor {
         gl::EnableVertexAttribArray(0);
         gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE,
             (5 * std::mem::size_of::<f32>()) as GLint,
-            (3 * std::mem::size_of::<f32>()) as *const std::ffi::c_void);
+            (2 * std::mem::size_of::<f32>()) as *const std::ffi::c_void);
         gl::EnableVertexAttribArray(1);
         gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         gl::BindVertexArray(0);
         vao
     }
     pub fn new(r: RotateRect) -> Self {
-        HexColor(r)
+        HexColor(r, PrimType::HexColor)
+    }
+    pub fn wheel(r: RotateRect) -> Self {
+        HexColor(r, PrimType::ColorWheel)
     }
     pub fn draw(&self, ctx: &DrawCtx) {
-        let ptype = &PrimType::HexColor;
+        let ptype = &self.1;
         ctx.prog_map[ptype].set_used();
         let trans = self.0.transform(&ctx.viewport);
         let prog_id = ctx.prog_map[ptype].id();
-        let vao = ctx.prim_map[ptype];
+        let vao = ctx.vao(ptype);
         unsafe {
             gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL); 
             trans.send_uniforms(prog_id).unwrap();
             gl::BindVertexArray(vao);
-            gl::DrawArrays(gl::TRIANGLE_FAN, 0, ptype.size() as i32);
+            gl::DrawArrays(ptype.mode(), 0, ptype.size() as i32);
+        }
+    }
+}
+
+pub fn color_to_hex(color: &glm::Vec4) -> String {
+    let c: Vec<u8> = (0..4).map(|i| (color[i] * 255.).round() as u8).collect();
+    format!("#{:02X}{:02X}{:02X}{:02X}", c[0], c[1], c[2], c[3])
+}
+
+pub fn hex_to_color(hex: &str) -> Option<glm::Vec4> {
+    let hex = hex.trim_start_matches('#');
+    if !hex.is_ascii() || (hex.len() != 6 && hex.len() != 8) {
+        return None;
+    }
+    let mut color = glm::vec4(0., 0., 0., 1.);
+    for i in 0..hex.len() / 2 {
+        color[i] = u8::from_str_radix(&hex[2*i..2*i+2], 16).ok()? as f32 / 255.;
+    }
+    Some(color)
+}
+
+//hue in degrees, saturation and value in [0, 1]
+pub fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (f32, f32, f32) {
+    let c = v * s;
+    let hp = (h.rem_euclid(360.)) / 60.;
+    let x = c * (1. - f32::abs(hp % 2. - 1.));
+    let (r, g, b) = match hp as u32 {
+        0 => (c, x, 0.),
+        1 => (x, c, 0.),
+        2 => (0., c, x),
+        3 => (0., x, c),
+        4 => (x, 0., c),
+        _ => (c, 0., x)
+    };
+    let m = v - c;
+    (r + m, g + m, b + m)
+}
+
+pub fn rgb_to_hsv(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let d = max - min;
+    let h = if d == 0. { 0. }
+        else if max == r { 60. * ((g - b) / d).rem_euclid(6.) }
+        else if max == g { 60. * ((b - r) / d + 2.) }
+        else { 60. * ((r - g) / d + 4.) };
+    let s = if max == 0. { 0. } else { d / max };
+    (h, s, max)
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ColorTarget {
+    Fill,
+    Stroke,
+    Text,
+}
+
+impl ColorTarget {
+    pub const ALL: [ColorTarget; 3] = [ColorTarget::Fill, ColorTarget::Stroke, ColorTarget::Text];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorTarget::Fill => "Fill",
+            ColorTarget::Stroke => "Stroke",
+            ColorTarget::Text => "Text",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PickerHover {
+    Panel,
+    Wheel,
+    Value,
+    Alpha,
+    Hex,
+    Target(ColorTarget),
+    Swatch(usize),
+}
+
+pub struct ColorPicker {
+    pub open: bool,
+    pub target: ColorTarget,
+    pub hex_text: String,
+    hsv: (f32, f32, f32),
+    alpha: f32,
+    recent: Vec<glm::Vec4>,
+    draw_rect: Rect,
+}
+
+impl ColorPicker {
+    const WIDTH: f32 = 200.;
+    const PAD: f32 = 10.;
+    const WHEEL_SIZE: f32 = 160.;
+    const SLIDER_HEIGHT: f32 = 14.;
+    const FIELD_HEIGHT: f32 = 24.;
+    const SWATCH_SIZE: f32 = 18.;
+    const MAX_RECENT: usize = 8;
+    const TEXT_SCALE: f32 = 0.6;
+
+    //anchor is the top center of the panel
+    pub fn new(anchor: &Point) -> Self {
+        let c1 = *anchor - Point::new(ColorPicker::WIDTH / 2., 0.);
+        let mut picker = ColorPicker {
+            open: false,
+            target: ColorTarget::Fill,
+            hex_text: String::new(),
+            hsv: (0., 0., 0.),
+            alpha: 1.,
+            recent: Vec::new(),
+            draw_rect: Rect::new(c1, c1)
+        };
+        picker.draw_rect = Rect::new(c1, Point::new(c1.x + ColorPicker::WIDTH, picker.swatch_rect(0).c2.y + ColorPicker::PAD));
+        picker
+    }
+    pub fn set_anchor(&mut self, anchor: &Point) {
+        let off = *anchor - Point::new(ColorPicker::WIDTH / 2., 0.) - self.draw_rect.c1;
+        self.draw_rect = Rect::new(self.draw_rect.c1 + off, self.draw_rect.c2 + off);
+    }
+    pub fn color(&self) -> glm::Vec4 {
+        let (r, g, b) = hsv_to_rgb(self.hsv.0, self.hsv.1, self.hsv.2);
+        glm::vec4(r, g, b, self.alpha)
+    }
+    pub fn set_color(&mut self, color: &glm::Vec4) {
+        self.hsv = rgb_to_hsv(color[0], color[1], color[2]);
+        self.alpha = color[3];
+    }
+    pub fn add_recent(&mut self) {
+        let color = self.color();
+        self.recent.retain(|c| *c != color);
+        self.recent.insert(0, color);
+        self.recent.truncate(ColorPicker::MAX_RECENT);
+    }
+    //full width row starting at y pixels below the top of the panel
+    fn row(&self, y: f32, height: f32) -> Rect {
+        let c1 = self.draw_rect.c1 + Point::new(ColorPicker::PAD, y);
+        Rect::new(c1, c1 + Point::new(ColorPicker::WIDTH - 2. * ColorPicker::PAD, height))
+    }
+    fn wheel_rect(&self) -> Rect {
+        let c1 = self.draw_rect.c1 + Point::new((ColorPicker::WIDTH - ColorPicker::WHEEL_SIZE) / 2., ColorPicker::PAD);
+        Rect::new(c1, c1 + Point::new(ColorPicker::WHEEL_SIZE, ColorPicker::WHEEL_SIZE))
+    }
+    fn value_rect(&self) -> Rect {
+        self.row(ColorPicker::WHEEL_SIZE + 2. * ColorPicker::PAD, ColorPicker::SLIDER_HEIGHT)
+    }
+    fn alpha_rect(&self) -> Rect {
+        self.row(self.value_rect().c2.y - self.draw_rect.c1.y + ColorPicker::PAD, ColorPicker::SLIDER_HEIGHT)
+    }
+    fn hex_rect(&self) -> Rect {
+        let r = self.row(self.alpha_rect().c2.y - self.draw_rect.c1.y + ColorPicker::PAD, ColorPicker::FIELD_HEIGHT);
+        Rect::new(r.c1, r.c2 - Point::new(ColorPicker::FIELD_HEIGHT + ColorPicker::PAD, 0.))
+    }
+    fn preview_rect(&self) -> Rect {
+        let r = self.hex_rect();
+        let c1 = Point::new(r.c2.x + ColorPicker::PAD, r.c1.y);
+        Rect::new(c1, c1 + Point::new(ColorPicker::FIELD_HEIGHT, ColorPicker::FIELD_HEIGHT))
+    }
+    fn target_rect(&self, target: &ColorTarget) -> Rect {
+        let r = self.row(self.hex_rect().c2.y - self.draw_rect.c1.y + ColorPicker::PAD, ColorPicker::FIELD_HEIGHT);
+        let n = ColorTarget::ALL.len() as f32;
+        let width = (r.width() - (n - 1.) * ColorPicker::PAD / 2.) / n;
+        let i = ColorTarget::ALL.iter().position(|t| t == target).unwrap() as f32;
+        let c1 = r.c1 + Point::new(i * (width + ColorPicker::PAD / 2.), 0.);
+        Rect::new(c1, Point::new(c1.x + width, r.c2.y))
+    }
+    fn swatch_rect(&self, i: usize) -> Rect {
+        let r = self.row(self.target_rect(&ColorTarget::Fill).c2.y - self.draw_rect.c1.y + ColorPicker::PAD, ColorPicker::SWATCH_SIZE);
+        let c1 = r.c1 + Point::new(i as f32 * (ColorPicker::SWATCH_SIZE + 4.), 0.);
+        Rect::new(c1, c1 + Point::new(ColorPicker::SWATCH_SIZE, ColorPicker::SWATCH_SIZE))
+    }
+    pub fn get_hover(&self, p: &Point, vp: &Point) -> Option<PickerHover> {
+        if !self.open || !self.draw_rect.in_bounds(p, vp) {
+            return None;
+        }
+        let wheel = self.wheel_rect();
+        if p.dist(&wheel.center()) <= wheel.width() / 2. {
+            Some(PickerHover::Wheel)
+        }
+        else if self.value_rect().in_bounds(p, vp) {
+            Some(PickerHover::Value)
+        }
+        else if self.alpha_rect().in_bounds(p, vp) {
+            Some(PickerHover::Alpha)
+        }
+        else if self.hex_rect().in_bounds(p, vp) {
+            Some(PickerHover::Hex)
+        }
+        else if let Some(t) = ColorTarget::ALL.iter().find(|t| self.target_rect(t).in_bounds(p, vp)) {
+            Some(PickerHover::Target(*t))
+        }
+        else if let Some(i) = (0..self.recent.len()).find(|i| self.swatch_rect(*i).in_bounds(p, vp)) {
+            Some(PickerHover::Swatch(i))
+        }
+        else { Some(PickerHover::Panel) }
+    }
+    //move the wheel or slider handle under the mouse, returns whether the color changed
+    pub fn drag(&mut self, hover: &PickerHover, p: &Point) -> bool {
+        let slider_pos = |r: Rect| ((p.x - r.c1.x) / r.width()).clamp(0., 1.);
+        match hover {
+            PickerHover::Wheel => {
+                let wheel = self.wheel_rect();
+                let d = *p - wheel.center();
+                let sat = f32::min(1., d.mag() / (wheel.width() / 2.));
+                let hue = d.y.atan2(d.x).to_degrees().rem_euclid(360.);
+                self.hsv = (hue, sat, self.hsv.2);
+            }
+            PickerHover::Value => self.hsv.2 = slider_pos(self.value_rect()),
+            PickerHover::Alpha => self.alpha = slider_pos(self.alpha_rect()),
+            PickerHover::Swatch(i) => {
+                let color = self.recent[*i];
+                self.set_color(&color);
+            }
+            _ => return false
+        }
+        true
+    }
+    pub fn begin_hex_edit(&mut self) {
+        self.hex_text = color_to_hex(&self.color());
+    }
+    pub fn push_char(&mut self, ch: char) {
+        if ch.is_ascii_hexdigit() || ch == '#' {
+            self.hex_text.push(ch);
+        }
+    }
+    pub fn pop_char(&mut self) {
+        self.hex_text.pop();
+    }
+    //applies the typed hex color, returns whether it parsed
+    pub fn finish_hex_edit(&mut self) -> bool {
+        let color = hex_to_color(&self.hex_text);
+        self.hex_text.clear();
+        color.map(|c| self.set_color(&c)).is_some()
+    }
+    fn draw_swatch(&self, r: &Rect, color: &glm::Vec4, draw_ctx: &DrawCtx) {
+        if let Shape::Polygon(mut poly) = r.builder().line_width(1.).get() {
+            poly.color = *color;
+            poly.draw(draw_ctx);
+        }
+    }
+    fn draw_slider(&self, r: &Rect, color: &glm::Vec4, pos: f32, draw_ctx: &DrawCtx) {
+        self.draw_swatch(r, color, draw_ctx);
+        let x = r.c1.x + pos * r.width();
+        Rect::new(Point::new(x - 2., r.c1.y - 2.), Point::new(x + 2., r.c2.y + 2.))
+            .builder().color(0, 0, 0).fill(false).line_width(2.).get().draw(draw_ctx);
+    }
+    pub fn draw(&self, editing_hex: bool, rt: &RenderText, draw_ctx: &DrawCtx) {
+        if !self.open {
+            return;
+        }
+        let (h, s, v) = self.hsv;
+        let color = self.color();
+        self.draw_rect.builder().color(230, 230, 230).get().draw(draw_ctx);
+
+        let wheel = self.wheel_rect();
+        HexColor::wheel(RotateRect::new(wheel.c1, wheel.size(), Radians(0.))).draw(draw_ctx);
+        //darken the wheel to the current value
+        ShapeBuilder::new().circle(wheel.width() as u32).offset(wheel.c1.x as i32, wheel.c1.y as i32)
+            .color(0, 0, 0).alpha(1. - v).get().draw(draw_ctx);
+        let marker = wheel.center() + Point::new(h.to_radians().cos(), h.to_radians().sin()) * Point::new(s, s) * (wheel.size() / 2.);
+        ShapeBuilder::new().circle(8).offset(marker.x as i32 - 4, marker.y as i32 - 4)
+            .color(0, 0, 0).fill(false).get().draw(draw_ctx);
+
+        let (r, g, b) = hsv_to_rgb(h, s, 1.);
+        self.draw_slider(&self.value_rect(), &glm::vec4(r, g, b, 1.), v, draw_ctx);
+        self.draw_slider(&self.alpha_rect(), &color, self.alpha, draw_ctx);
+
+        let hex_rect = self.hex_rect();
+        hex_rect.builder().color(255, 255, 255).get().draw(draw_ctx);
+        let hex = if editing_hex { format!("{}_", self.hex_text) } else { color_to_hex(&color) };
+        let label_rect = |r: &Rect| Rect::new(r.c1 + Point::new(4., 0.), r.c2);
+        rt.draw_label(&hex, &label_rect(&hex_rect), &rgb_to_f32(0, 0, 0), ColorPicker::TEXT_SCALE, draw_ctx);
+        self.draw_swatch(&self.preview_rect(), &color, draw_ctx);
+
+        for target in ColorTarget::ALL.iter() {
+            let r = self.target_rect(target);
+            let width = if *target == self.target { 2. } else { 0. };
+            r.builder().color(255, 255, 255).stroke(0, 0, 0).line_width(width).get().draw(draw_ctx);
+            rt.draw_label(target.name(), &label_rect(&r), &rgb_to_f32(0, 0, 0), ColorPicker::TEXT_SCALE, draw_ctx);
+        }
+        for (i, c) in self.recent.iter().enumerate() {
+            self.draw_swatch(&self.swatch_rect(i), c, draw_ctx);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,378 @@
+extern crate nalgebra_glm;
+
+use nalgebra_glm as glm;
+use crate::primitives::*;
+use crate::render_text::RenderText;
+use crate::textedit::{TextAlign, VerticalAlign, TextFit, TextOverflow};
+use crate::hexcolor::{color_to_hex, hex_to_color};
+use crate::shape_kind;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ShapeProp {
+    Style,
+    X,
+    Y,
+    Width,
+    Height,
+    Rotation,
+    Fill,
+    Prim,
+    LineWidth,
+    StrokeColor,
+    FillColor,
+    TextScale,
+    TextAlign,
+    TextVAlign,
+    TextPadding,
+    TextFit,
+    TextOverflow,
+    LabelPosition,
+    LabelOffset,
+    LabelFollow,
+    Route,
+    CornerRadius,
+    Sides,
+    Link,
+}
+
+impl ShapeProp {
+    pub const ALL: [ShapeProp; 24] = [
+        ShapeProp::Style, ShapeProp::X, ShapeProp::Y, ShapeProp::Width, ShapeProp::Height, ShapeProp::Rotation,
+        ShapeProp::Fill, ShapeProp::Prim, ShapeProp::CornerRadius, ShapeProp::Sides, ShapeProp::LineWidth, ShapeProp::StrokeColor,
+        ShapeProp::FillColor, ShapeProp::TextScale, ShapeProp::TextAlign, ShapeProp::TextVAlign,
+        ShapeProp::TextPadding, ShapeProp::TextFit, ShapeProp::TextOverflow,
+        ShapeProp::LabelPosition, ShapeProp::LabelOffset, ShapeProp::LabelFollow, ShapeProp::Route, ShapeProp::Link
+    ];
+    fn label(&self) -> &'static str {
+        match self {
+            ShapeProp::Style => "Style",
+            ShapeProp::X => "X",
+            ShapeProp::Y => "Y",
+            ShapeProp::Width => "Width",
+            ShapeProp::Height => "Height",
+            ShapeProp::Rotation => "Rotation",
+            ShapeProp::Fill => "Fill",
+            ShapeProp::Prim => "Shape",
+            ShapeProp::LineWidth => "Line width",
+            ShapeProp::StrokeColor => "Stroke",
+            ShapeProp::FillColor => "Fill color",
+            ShapeProp::TextScale => "Text scale",
+            ShapeProp::TextAlign => "Text align",
+            ShapeProp::TextVAlign => "Vertical align",
+            ShapeProp::TextPadding => "Text padding",
+            ShapeProp::TextFit => "Text fit",
+            ShapeProp::TextOverflow => "Overflow",
+            ShapeProp::LabelPosition => "Label position",
+            ShapeProp::LabelOffset => "Label offset",
+            ShapeProp::LabelFollow => "Label follows",
+            ShapeProp::Route => "Routing",
+            ShapeProp::CornerRadius => "Corner radius",
+            ShapeProp::Sides => "Sides",
+            ShapeProp::Link => "Links to",
+        }
+    }
+    //properties of the text box of a shape rather than the shape
+    pub fn is_text(&self) -> bool {
+        matches!(self, ShapeProp::TextScale | ShapeProp::TextAlign | ShapeProp::TextVAlign | ShapeProp::TextPadding | ShapeProp::TextFit
+            | ShapeProp::TextOverflow)
+    }
+    //properties a style sets, a shape with one of them set on it no longer follows its style
+    pub fn is_styled(&self) -> bool {
+        matches!(self, ShapeProp::Fill | ShapeProp::LineWidth | ShapeProp::StrokeColor | ShapeProp::FillColor | ShapeProp::Route)
+            || self.is_text()
+    }
+    //amount one scroll wheel notch changes a numeric property by
+    pub fn step(&self) -> Option<f32> {
+        match self {
+            ShapeProp::X | ShapeProp::Y | ShapeProp::Width | ShapeProp::Height => Some(1.),
+            ShapeProp::Rotation => Some(1.),
+            ShapeProp::LineWidth | ShapeProp::TextPadding => Some(0.5),
+            ShapeProp::TextScale | ShapeProp::LabelPosition => Some(0.05),
+            ShapeProp::LabelOffset | ShapeProp::CornerRadius | ShapeProp::Sides => Some(1.),
+            _ => None
+        }
+    }
+    fn format_num(&self, v: f32) -> String {
+        match self {
+            ShapeProp::TextScale | ShapeProp::LabelPosition => format!("{:.2}", v),
+            ShapeProp::CornerRadius | ShapeProp::Sides => format!("{:.0}", v),
+            _ => format!("{:.1}", v)
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum PropValue {
+    Num(f32),
+    Bool(bool),
+    Prim(PrimType),
+    Color(glm::Vec4),
+    Style(String),
+    Align(TextAlign),
+    VAlign(VerticalAlign),
+    Fit(TextFit),
+    Overflow(TextOverflow),
+    Route(LineRoute),
+    Link(String),       // Name of the linked page
+    Mixed,
+}
+
+impl PropValue {
+    //collapse the values of every selected shape into a single displayed value
+    pub fn merge<I: Iterator<Item=PropValue>>(mut vals: I) -> Option<PropValue> {
+        let first = vals.next()?;
+        if vals.all(|v| v == first) { Some(first) } else { Some(PropValue::Mixed) }
+    }
+    fn display(&self, prop: &ShapeProp) -> String {
+        match self {
+            PropValue::Num(v) => prop.format_num(*v),
+            PropValue::Bool(b) => if *b { "on".to_string() } else { "off".to_string() },
+            PropValue::Prim(p) => p.name(),
+            PropValue::Color(c) => color_to_hex(c),
+            PropValue::Style(name) => name.clone(),
+            PropValue::Align(a) => a.name().to_string(),
+            PropValue::VAlign(a) => a.name().to_string(),
+            PropValue::Fit(f) => f.name().to_string(),
+            PropValue::Overflow(o) => o.name().to_string(),
+            PropValue::Route(r) => r.name().to_string(),
+            PropValue::Link(page) => page.clone(),
+            PropValue::Mixed => "--".to_string()
+        }
+    }
+    fn parse(prop: &ShapeProp, text: &str) -> Option<PropValue> {
+        match prop {
+            ShapeProp::StrokeColor | ShapeProp::FillColor => hex_to_color(text).map(PropValue::Color),
+            _ if prop.step().is_some() => text.trim().parse::<f32>().ok().map(PropValue::Num),
+            _ => None
+        }
+    }
+}
+
+fn next_prim(prim: &PrimType) -> PrimType {
+    match prim {
+        PrimType::Triangle => PrimType::Circle,
+        PrimType::Circle | PrimType::Ring => PrimType::Rect,
+        PrimType::Rect => PrimType::RoundRect(PrimType::DEFAULT_RADIUS),
+        PrimType::RoundRect(_) => PrimType::Diamond,
+        PrimType::Diamond => PrimType::Polygon(6),
+        PrimType::Polygon(_) => PrimType::Parallelogram,
+        PrimType::Parallelogram => PrimType::Cylinder,
+        PrimType::Cylinder => PrimType::Star(5),
+        //then through the registered shape kinds
+        PrimType::Star(_) if shape_kind::count() > 0 => PrimType::Custom(0),
+        PrimType::Custom(id) if (*id as usize + 1) < shape_kind::count() => PrimType::Custom(id + 1),
+        _ => PrimType::Triangle
+    }
+}
+
+impl Shape {
+    pub fn get_prop(&self, prop: ShapeProp) -> Option<PropValue> {
+        match self {
+            Shape::Polygon(ref poly) => {
+                let r = &poly.rect;
+                match prop {
+                    ShapeProp::X => Some(PropValue::Num(r.offset.x)),
+                    ShapeProp::Y => Some(PropValue::Num(r.offset.y)),
+                    ShapeProp::Width => Some(PropValue::Num(r.size.x)),
+                    ShapeProp::Height => Some(PropValue::Num(r.size.y)),
+                    ShapeProp::Rotation => Some(PropValue::Num(Degrees::from(r.rot).0)),
+                    ShapeProp::Fill => Some(PropValue::Bool(poly.fill)),
+                    ShapeProp::Prim => Some(PropValue::Prim(poly.prim)),
+                    ShapeProp::CornerRadius => match poly.prim {
+                        PrimType::RoundRect(r) => Some(PropValue::Num(r as f32)),
+                        _ => None
+                    },
+                    ShapeProp::Sides => poly.prim.sides().map(|n| PropValue::Num(n as f32)),
+                    ShapeProp::LineWidth => Some(PropValue::Num(poly.line_width)),
+                    ShapeProp::StrokeColor => Some(PropValue::Color(poly.stroke_color)),
+                    ShapeProp::FillColor => Some(PropValue::Color(poly.color)),
+                    _ => None
+                }
+            }
+            Shape::Line(ref line) => {
+                match prop {
+                    ShapeProp::X => Some(PropValue::Num(line.p1.x)),
+                    ShapeProp::Y => Some(PropValue::Num(line.p1.y)),
+                    ShapeProp::Width => Some(PropValue::Num(line.p2.x - line.p1.x)),
+                    ShapeProp::Height => Some(PropValue::Num(line.p2.y - line.p1.y)),
+                    ShapeProp::LineWidth => Some(PropValue::Num(line.line_width)),
+                    ShapeProp::StrokeColor => Some(PropValue::Color(line.color)),
+                    ShapeProp::LabelPosition => Some(PropValue::Num(line.label.t)),
+                    ShapeProp::LabelOffset => Some(PropValue::Num(line.label.offset)),
+                    ShapeProp::LabelFollow => Some(PropValue::Bool(line.label.follow_line)),
+                    ShapeProp::Route => Some(PropValue::Route(line.route)),
+                    _ => None
+                }
+            }
+        }
+    }
+    pub fn set_prop(&mut self, prop: ShapeProp, val: &PropValue) {
+        match (self, val) {
+            (Shape::Polygon(ref mut poly), PropValue::Num(v)) => {
+                match prop {
+                    ShapeProp::X => poly.rect.offset.x = *v,
+                    ShapeProp::Y => poly.rect.offset.y = *v,
+                    ShapeProp::Width => poly.rect.size.x = f32::max(*v, 1.),
+                    ShapeProp::Height => poly.rect.size.y = f32::max(*v, 1.),
+                    ShapeProp::Rotation => poly.rect.set_radians(Degrees(*v).into()),
+                    ShapeProp::LineWidth => poly.line_width = f32::max(*v, 0.),
+                    ShapeProp::CornerRadius => if let PrimType::RoundRect(_) = poly.prim {
+                        poly.prim = PrimType::RoundRect(v.round().clamp(0., 50.) as u8);
+                    },
+                    ShapeProp::Sides => poly.prim = poly.prim.with_sides(v.round().clamp(0., 255.) as u8),
+                    _ => {}
+                }
+            }
+            (Shape::Polygon(ref mut poly), PropValue::Bool(b)) => {
+                if let ShapeProp::Fill = prop { poly.set_fill(*b); }
+            }
+            (Shape::Polygon(ref mut poly), PropValue::Prim(p)) => {
+                if let ShapeProp::Prim = prop {
+                    poly.prim = *p;
+                    let fill = poly.fill;
+                    poly.set_fill(fill);
+                }
+            }
+            (Shape::Polygon(ref mut poly), PropValue::Color(c)) => {
+                match prop {
+                    ShapeProp::StrokeColor => poly.stroke_color = *c,
+                    ShapeProp::FillColor => poly.color = *c,
+                    _ => {}
+                }
+            }
+            (Shape::Line(ref mut line), PropValue::Num(v)) => {
+                match prop {
+                    ShapeProp::X => { let off = Point::new(*v - line.p1.x, 0.); line.drag(&off); }
+                    ShapeProp::Y => { let off = Point::new(0., *v - line.p1.y); line.drag(&off); }
+                    ShapeProp::Width => line.p2.x = line.p1.x + *v,
+                    ShapeProp::Height => line.p2.y = line.p1.y + *v,
+                    ShapeProp::LineWidth => line.line_width = f32::max(*v, 1.),
+                    ShapeProp::LabelPosition => line.label.t = v.clamp(0., 1.),
+                    ShapeProp::LabelOffset => line.label.offset = *v,
+                    _ => {}
+                }
+            }
+            (Shape::Line(ref mut line), PropValue::Bool(b)) => {
+                if let ShapeProp::LabelFollow = prop { line.label.follow_line = *b; }
+            }
+            (Shape::Line(ref mut line), PropValue::Route(r)) => {
+                if let ShapeProp::Route = prop { line.route = *r; }
+            }
+            (Shape::Line(ref mut line), PropValue::Color(c)) => {
+                if let ShapeProp::StrokeColor = prop { line.color = *c; }
+            }
+            _ => {}
+        }
+    }
+}
+
+//value a click on a toggle style property changes the selection to
+pub fn toggle_value(prop: &ShapeProp, val: &PropValue) -> Option<PropValue> {
+    match (prop, val) {
+        (_, PropValue::Bool(b)) => Some(PropValue::Bool(!b)),
+        (_, PropValue::Prim(p)) => Some(PropValue::Prim(next_prim(p))),
+        (_, PropValue::Align(a)) => Some(PropValue::Align(a.next())),
+        (_, PropValue::VAlign(a)) => Some(PropValue::VAlign(a.next())),
+        (_, PropValue::Fit(f)) => Some(PropValue::Fit(f.next())),
+        (_, PropValue::Overflow(o)) => Some(PropValue::Overflow(o.next())),
+        (_, PropValue::Route(r)) => Some(PropValue::Route(r.next())),
+        (ShapeProp::Fill, PropValue::Mixed) | (ShapeProp::LabelFollow, PropValue::Mixed) => Some(PropValue::Bool(true)),
+        (ShapeProp::Prim, PropValue::Mixed) => Some(PropValue::Prim(PrimType::Triangle)),
+        (ShapeProp::TextAlign, PropValue::Mixed) => Some(PropValue::Align(TextAlign::Left)),
+        (ShapeProp::TextVAlign, PropValue::Mixed) => Some(PropValue::VAlign(VerticalAlign::Top)),
+        (ShapeProp::TextFit, PropValue::Mixed) => Some(PropValue::Fit(TextFit::Fixed)),
+        (ShapeProp::TextOverflow, PropValue::Mixed) => Some(PropValue::Overflow(TextOverflow::Clip)),
+        (ShapeProp::Route, PropValue::Mixed) => Some(PropValue::Route(LineRoute::Orthogonal)),
+        _ => None
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum InspectorHover {
+    Panel,
+    Prop(ShapeProp),
+}
+
+pub struct Inspector {
+    pub edit_text: String,
+    viewport: Point,
+}
+
+impl Inspector {
+    const WIDTH: f32 = 220.;
+    const ROW_HEIGHT: f32 = 24.;
+    const TEXT_SCALE: f32 = 0.6;
+
+    pub fn new(viewport: &Point) -> Self {
+        Inspector { edit_text: String::new(), viewport: *viewport }
+    }
+    fn panel_rect(&self, nrows: usize) -> Rect {
+        let c1 = Point::new(self.viewport.x - Inspector::WIDTH, self.viewport.y / 12.);
+        Rect::new(c1, c1 + Point::new(Inspector::WIDTH, (nrows + 1) as f32 * Inspector::ROW_HEIGHT))
+    }
+    fn title_rect(&self) -> Rect {
+        let c1 = self.panel_rect(0).c1;
+        Rect::new(c1, c1 + Point::new(Inspector::WIDTH, Inspector::ROW_HEIGHT))
+    }
+    fn row_rect(&self, row: usize) -> Rect {
+        let c1 = self.panel_rect(0).c1 + Point::new(0., (row + 1) as f32 * Inspector::ROW_HEIGHT);
+        Rect::new(c1, c1 + Point::new(Inspector::WIDTH, Inspector::ROW_HEIGHT))
+    }
+    fn value_rect(&self, row: usize) -> Rect {
+        let r = self.row_rect(row);
+        Rect::new(Point::new(r.center().x, r.c1.y), r.c2)
+    }
+    pub fn get_hover(&self, p: &Point, props: &[(ShapeProp, PropValue)]) -> Option<InspectorHover> {
+        if props.is_empty() || !self.panel_rect(props.len()).in_bounds(p, &self.viewport) {
+            return None;
+        }
+        props.iter().enumerate().find(|(i, _)| self.value_rect(*i).in_bounds(p, &self.viewport))
+            .map(|(_, (prop, _))| InspectorHover::Prop(*prop))
+            .or(Some(InspectorHover::Panel))
+    }
+    pub fn begin_edit(&mut self, prop: &ShapeProp, val: &PropValue) {
+        self.edit_text = match val {
+            PropValue::Mixed => String::new(),
+            _ => val.display(prop)
+        };
+    }
+    pub fn push_char(&mut self, ch: char) {
+        if ch.is_ascii_hexdigit() || ch == '.' || ch == '-' || ch == '#' {
+            self.edit_text.push(ch);
+        }
+    }
+    pub fn pop_char(&mut self) {
+        self.edit_text.pop();
+    }
+    pub fn finish_edit(&mut self, prop: &ShapeProp) -> Option<PropValue> {
+        let val = PropValue::parse(prop, &self.edit_text);
+        self.edit_text.clear();
+        val
+    }
+    fn draw_label(&self, text: &str, r: &Rect, rt: &RenderText, draw_ctx: &DrawCtx) {
+        let r = Rect::new(r.c1 + Point::new(4., 0.), r.c2);
+        rt.draw_label(text, &r, &rgb_to_f32(0, 0, 0), Inspector::TEXT_SCALE, draw_ctx);
+    }
+    pub fn draw(&self, props: &[(ShapeProp, PropValue)], editing: Option<ShapeProp>, rt: &RenderText, draw_ctx: &DrawCtx) {
+        self.panel_rect(props.len()).builder().color(230, 230, 230).get().draw(draw_ctx);
+        self.draw_label("Properties", &self.title_rect(), rt, draw_ctx);
+        for (i, (prop, val)) in props.iter().enumerate() {
+            let value_rect = self.value_rect(i);
+            self.draw_label(prop.label(), &self.row_rect(i), rt, draw_ctx);
+            value_rect.builder().color(255, 255, 255).get().draw(draw_ctx);
+            let mut text = val.display(prop);
+            if editing == Some(*prop) {
+                text = format!("{}_", self.edit_text);
+            }
+            else if let PropValue::Color(c) = val {
+                let side = Inspector::ROW_HEIGHT - 8.;
+                let swatch = Rect::new(value_rect.c2 - Point::new(side + 4., side + 4.), value_rect.c2 - Point::new(4., 4.));
+                if let Shape::Polygon(mut poly) = swatch.builder().line_width(1.).get() {
+                    poly.color = *c;
+                    poly.draw(draw_ctx);
+                }
+            }
+            self.draw_label(&text, &value_rect, rt, draw_ctx);
+        }
+    }
+}
@@ -2,17 +2,38 @@ extern crate sdl2;
 
 use num_derive::FromPrimitive;    
 use num_traits::FromPrimitive;
-use std::collections::{HashMap};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
 use sdl2::event::Event;
 use sdl2::keyboard::{Keycode, Mod};
 use sdl2::mouse::{Cursor, SystemCursor};
-use std::iter::FromIterator;
+use sdl2::clipboard::ClipboardUtil;
+use sdl2::keyboard::TextInputUtil;
+use sdl2::VideoSubsystem;
 use std::time::SystemTime;
+use std::sync::atomic::{AtomicU64, Ordering};
 use crate::primitives::*;
 //use crate::primitives::ShapeProps as Shape;
 use crate::render_text::RenderText;
-use crate::textedit::{TextBox, get_char_from_keycode, get_dir_from_keycode};
-use crate::hexcolor::HexColor;
+use crate::fonts::FontConfig;
+use crate::textedit::{TextBox, get_dir_from_keycode, get_dir_from_chord, get_text_input, TextInput};
+use crate::text_spans::{TextAttr, TextAttrs};
+use std::ops::Range;
+use crate::hexcolor::{HexColor, ColorPicker, ColorTarget, PickerHover};
+use crate::style::{Style, StyleSheet};
+use nalgebra_glm as glm;
+use crate::inspector::{Inspector, InspectorHover, ShapeProp, PropValue, toggle_value};
+use crate::commands::{CommandId, KeyChord, Keymap};
+use crate::palette::CommandPalette;
+use crate::routing::RouteQuery;
+use crate::shape_kind::{self, ShapeKindId};
+use crate::geometry;
+use crate::layers::{Layer, LayerPanel, LayerHover, LayerPart};
+use crate::document::{self, Document};
+use crate::pages::{PageTabs, TabHover};
+use crate::pdf::{self, PdfDocument, PdfPage, PdfFace};
+use crate::svg::{self, SvgPage, SvgFace};
+use crate::minimap::Minimap;
 
 pub struct CursorMap(HashMap<SystemCursor, Cursor>);
 impl CursorMap {
@@ -38,8 +59,7 @@ impl Shape {
     pub fn drag(&mut self, off: &Point) {
         match self {
             Shape::Line(ref mut draw_line) => {
-                draw_line.p1 += *off;
-                draw_line.p2 += *off;
+                draw_line.drag(off);
             }
             Shape::Polygon(ref mut draw_poly) => {
                 draw_poly.rect.drag(off);
@@ -56,8 +76,31 @@ impl Shape {
             }
         }
     }
-    fn in_select_box(&self, r: &Rect, vp: &Point) -> bool {
-        self.verts(&vp).iter().any(|v| r.in_bounds(v, vp))
+    //whether a selection region, a box or a lasso, picks the shape
+    pub fn in_select_region(&self, region: &[Point], mode: SelectMode, vp: &Point) -> bool {
+        let (pts, edges) = match self {
+            Shape::Polygon(ref draw_poly) => {
+                let pts = draw_poly.outline(vp);
+                let mut edges = pts.clone();
+                edges.extend(pts.first().cloned());
+                (pts, edges)
+            }
+            Shape::Line(ref draw_line) => (draw_line.points(), draw_line.points())
+        };
+        match mode {
+            SelectMode::Contain => pts.iter().all(|p| geometry::in_polygon(region, p)),
+            SelectMode::Intersect => {
+                let mut closed = region.to_vec();
+                closed.extend(region.first().cloned());
+                pts.iter().any(|p| geometry::in_polygon(region, p))
+                    || geometry::polylines_intersect(&edges, &closed)
+                    //a region entirely inside a shape
+                    || match self {
+                        Shape::Polygon(_) => region.iter().any(|p| geometry::in_polygon(&pts, p)),
+                        Shape::Line(_) => false
+                    }
+            }
+        }
     }
     fn set_rect(&mut self, r: &RotateRect) {
         match self {
@@ -82,23 +125,53 @@ impl DrawLine {
         match v {
             LineVertex::P1 => self.p1 = *pt,
             LineVertex::P2 => self.p2 = *pt,
+            LineVertex::Waypoint(i) => {
+                if let Some(p) = self.waypoints.get_mut(*i) {
+                    *p = *pt;
+                }
+            }
         };
     }
 }
 
+//a layer and the shapes on it in drawing order
+#[derive(Clone)]
+struct LayerShapes {
+    layer: Layer,
+    order: Vec<ShapeID>,
+}
+
+#[derive(Clone)]
 pub struct DrawList {
     m: HashMap<ShapeID, Shape>,
-    draw_order: Vec<ShapeID>,
-    next_id: ShapeID 
+    layers: Vec<LayerShapes>,       // Bottom layer first
+    active: usize,                  // Layer new shapes are added to
+    next_id: ShapeID,
+    generation: u64,                // Changes whenever the shapes or layers might have
 }
 
+//generations are unique across draw lists, so a page's never matches one another page had
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
 impl DrawList {
     pub fn new() -> DrawList {
-        DrawList {m: HashMap::new(), draw_order: Vec::new(), next_id: 0}
+        let layers = vec![LayerShapes { layer: Layer::new("Layer 1"), order: Vec::new() }];
+        DrawList {m: HashMap::new(), layers, active: 0, next_id: 0, generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)}
+    }
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    fn touch(&mut self) {
+        self.generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
     }
     pub fn add(&mut self, s: Shape) -> ShapeID {
+        let layer = self.active;
+        self.add_to_layer(s, layer)
+    }
+    pub fn add_to_layer(&mut self, s: Shape, layer: usize) -> ShapeID {
+        self.touch();
         self.m.insert(self.next_id, s);
-        self.draw_order.push(self.next_id);
+        self.layers[layer].order.push(self.next_id);
         self.next_id += 1;
         self.next_id - 1
     }
@@ -106,77 +179,269 @@ impl DrawList {
         self.m.get(id)
     }
     fn get_mut(&mut self, id: &ShapeID) -> Option<&mut Shape> {
+        self.touch();
         self.m.get_mut(id)
     }
     fn remove(&mut self, id: &ShapeID) {
+        self.touch();
         if self.m.remove(id).is_some() {
-            self.draw_order.iter().position(|idx| *idx == *id)
-                .map(|idx| self.draw_order.remove(idx));
+            for l in self.layers.iter_mut() {
+                l.order.retain(|idx| *idx != *id);
+            }
         }
     }
+    fn layer_of(&self, id: &ShapeID) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.order.contains(id)).map(|l| &l.layer)
+    }
+    fn is_interactive(&self, id: &ShapeID) -> bool {
+        self.layer_of(id).is_some_and(|l| l.interactive())
+    }
+    fn is_visible(&self, id: &ShapeID) -> bool {
+        self.layer_of(id).is_some_and(|l| l.visible)
+    }
     fn click_shape(&mut self, p: &Point, vp: &Point) -> Option<u32> {
-        let draw_idx = self.draw_order.iter().rev().position(|idx| {
-            self.m.get(idx).map_or(ClickResponse::NotClicked, |s| s.click(p, vp)) != ClickResponse::NotClicked
-        }).map(|idx| self.draw_order.len() - 1 - idx); //go in reverse to get shape that's rendered last
-        if let Some(idx) = draw_idx {
-            let elem = self.draw_order.remove(idx as usize);
-            self.draw_order.push(elem);
-            return Some(elem) 
+        //go in reverse to get shape that's rendered last
+        self.ids().into_iter().rev().find(|id| self.is_interactive(id) &&
+            self.m.get(id).map_or(ClickResponse::NotClicked, |s| s.click(p, vp)) != ClickResponse::NotClicked)
+    }
+    //every shape in drawing order, layer by layer
+    fn ids(&self) -> Vec<ShapeID> {
+        self.layers.iter().flat_map(|l| l.order.iter().cloned()).collect()
+    }
+    fn interactive_ids(&self) -> Vec<ShapeID> {
+        self.layers.iter().filter(|l| l.layer.interactive()).flat_map(|l| l.order.iter().cloned()).collect()
+    }
+    //shapes are only reordered within their own layer
+    fn bring_to_front(&mut self, ids: &HashSet<ShapeID>) {
+        self.touch();
+        for l in self.layers.iter_mut() {
+            let (mut order, front): (Vec<ShapeID>, Vec<ShapeID>) = l.order.iter().partition(|id| !ids.contains(id));
+            order.extend(front);
+            l.order = order;
         }
-        None
     }
-    #[inline]
-    fn get_box_selection(&self, r: &Rect, vp: &Point) 
-        -> (Vec<(ShapeID, &DrawPolygon)>, Vec<(ShapeID, &DrawLine)>)
-    {
-        let mut v0 = Vec::new();
-        let mut v1 = Vec::new();
-        self.m.iter().filter(|(_,s)| s.in_select_box(r, vp)).for_each(|(id, s)| 
-            match s {
-                Shape::Polygon(ref draw_poly) => v0.push((*id, draw_poly)),
-                Shape::Line(ref draw_line) => v1.push((*id, draw_line)) 
-            });
-        (v0, v1)
+    fn send_to_back(&mut self, ids: &HashSet<ShapeID>) {
+        self.touch();
+        for l in self.layers.iter_mut() {
+            let (mut order, back): (Vec<ShapeID>, Vec<ShapeID>) = l.order.iter().partition(|id| ids.contains(id));
+            order.extend(back);
+            l.order = order;
+        }
+    }
+    //each selected shape moves one step past its unselected neighbour
+    fn bring_forward(&mut self, ids: &HashSet<ShapeID>) {
+        self.touch();
+        for order in self.layers.iter_mut().map(|l| &mut l.order) {
+            for i in (0..order.len().saturating_sub(1)).rev() {
+                if ids.contains(&order[i]) && !ids.contains(&order[i + 1]) {
+                    order.swap(i, i + 1);
+                }
+            }
+        }
+    }
+    fn send_backward(&mut self, ids: &HashSet<ShapeID>) {
+        self.touch();
+        for order in self.layers.iter_mut().map(|l| &mut l.order) {
+            for i in 1..order.len() {
+                if ids.contains(&order[i]) && !ids.contains(&order[i - 1]) {
+                    order.swap(i - 1, i);
+                }
+            }
+        }
+    }
+    pub fn get_region_selection(&self, region: &[Point], mode: SelectMode, vp: &Point) -> Vec<ShapeID> {
+        if region.len() < 3 {
+            return Vec::new();
+        }
+        self.m.iter().filter(|(id, s)| self.is_interactive(id) && s.in_select_region(region, mode, vp))
+            .map(|(id, _)| *id).collect()
+    }
+    pub fn layers(&self) -> Vec<&Layer> {
+        self.layers.iter().map(|l| &l.layer).collect()
+    }
+    fn layer_mut(&mut self, layer: usize) -> Option<&mut Layer> {
+        self.touch();
+        self.layers.get_mut(layer).map(|l| &mut l.layer)
+    }
+    //the new layer goes above the active one and becomes active
+    pub fn add_layer(&mut self, name: &str) -> usize {
+        self.touch();
+        self.active = usize::min(self.active + 1, self.layers.len());
+        self.layers.insert(self.active, LayerShapes { layer: Layer::new(name), order: Vec::new() });
+        self.active
+    }
+    //the shapes that were on the layer, the last layer can't be removed
+    fn remove_layer(&mut self, layer: usize) -> Vec<ShapeID> {
+        if self.layers.len() < 2 || layer >= self.layers.len() {
+            return Vec::new();
+        }
+        self.touch();
+        let removed = self.layers.remove(layer).order;
+        removed.iter().for_each(|id| { self.m.remove(id); });
+        self.active = usize::min(self.active, self.layers.len() - 1);
+        removed
+    }
+    //swaps the layer with the one above or below it, it stays active if it was
+    fn move_layer(&mut self, layer: usize, up: bool) {
+        let other = if up { layer + 1 } else { layer.wrapping_sub(1) };
+        if layer >= self.layers.len() || other >= self.layers.len() {
+            return;
+        }
+        self.touch();
+        self.layers.swap(layer, other);
+        if self.active == layer { self.active = other; }
+        else if self.active == other { self.active = layer; }
+    }
+    //moved shapes go on top of the layer, keeping their order
+    fn move_to_layer(&mut self, ids: &HashSet<ShapeID>, layer: usize) {
+        if layer >= self.layers.len() {
+            return;
+        }
+        self.touch();
+        let moved: Vec<ShapeID> = self.ids().into_iter().filter(|id| ids.contains(id)).collect();
+        for l in self.layers.iter_mut() {
+            l.order.retain(|id| !ids.contains(id));
+        }
+        self.layers[layer].order.extend(moved);
+    }
+    //shapes on visible layers in drawing order, with their layer's opacity
+    fn visible_shapes(&self) -> Vec<(&Shape, f32)> {
+        self.layers.iter().filter(|l| l.layer.visible)
+            .flat_map(|l| l.order.iter().map(move |id| (&self.m[id], l.layer.opacity))).collect()
     }
     pub fn draw(&self, ctx: &DrawCtx) {
-        self.draw_order.iter().for_each(|idx| {
-            let s = &self.m[idx];
-            s.draw(ctx);
-        });
+        for l in self.layers.iter().filter(|l| l.layer.visible) {
+            ctx.opacity.set(l.layer.opacity);
+            l.order.iter().for_each(|idx| self.m[idx].draw(ctx));
+        }
+        ctx.opacity.set(1.);
     }
 }
 
 type ShapeID = u32;
+type PageID = u32;
+
+//a line end attached to a shape, kept at the same place in the shape's rect as the shape moves
+#[derive(Copy, Clone, PartialEq)]
+struct LineEnd {
+    shape: ShapeID,
+    model: Point,
+}
+
+//a sheet of the diagram with its own shapes and view. the page being shown
+//has its contents swapped out into the app state, see show_page
+#[derive(Clone)]
+struct Page {
+    id: PageID,
+    name: String,
+    draw_list: DrawList,
+    text_boxes: HashMap<ShapeID, TextBox>,
+    shape_styles: HashMap<ShapeID, String>,
+    line_ends: HashMap<ShapeID, [Option<LineEnd>; 2]>,
+    routes: HashMap<ShapeID, RouteQuery>,
+    links: HashMap<ShapeID, PageID>,
+    camera: Point,
+}
+
+impl Page {
+    fn new(id: PageID, name: &str) -> Self {
+        Page {
+            id, name: name.to_string(), draw_list: DrawList::new(), text_boxes: HashMap::new(),
+            shape_styles: HashMap::new(), line_ends: HashMap::new(), routes: HashMap::new(),
+            links: HashMap::new(), camera: Point::origin()
+        }
+    }
+}
 
 pub struct AppState {
     draw_list: DrawList,
     selection: HashMap<ShapeID, ShapeSelectBox>,
     line_select: HashMap<ShapeID, SelectLine>,
+    select_mode: SelectMode,
+    //shapes selected before a drag selection started, and the points of the lasso being drawn
+    select_base: HashSet<ShapeID>,
+    lasso: Vec<Point>,
     text_boxes: HashMap<ShapeID, TextBox>,
+    line_ends: HashMap<ShapeID, [Option<LineEnd>; 2]>,
+    routes: HashMap<ShapeID, RouteQuery>,
+    links: HashMap<ShapeID, PageID>,
+    //world point at the top left of the window
+    camera: Point,
+    pages: Vec<Page>,
+    page: usize,
+    next_page_id: PageID,
+    page_tabs: PageTabs,
     shape_bar: ShapeBar,
     drag_mode: DragMode,
     key_mode: KeyboardMode,
+    field_composing: bool,          // The IME is composing text for a field other than a text box
     render_text: RenderText,
     hover_item: HoverItem,
+    inspector: Inspector,
+    layer_panel: LayerPanel,
+    minimap: Minimap,
+    color_picker: ColorPicker,
+    styles: StyleSheet,
+    shape_styles: HashMap<ShapeID, String>,
+    keymap: Keymap,
+    palette: CommandPalette,
+    mouse_pt: Point,
+    quit: bool,
+    clipboard: ClipboardUtil,
+    text_input: TextInputUtil,
     pub draw_ctx: DrawCtx,
     cursors: CursorMap
 }
 
+//whether a selection box or lasso picks the shapes it touches or only the ones entirely inside it
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SelectMode {
+    Intersect,
+    Contain,
+}
+
+impl SelectMode {
+    pub fn next(&self) -> SelectMode {
+        match self {
+            SelectMode::Intersect => SelectMode::Contain,
+            SelectMode::Contain => SelectMode::Intersect,
+        }
+    }
+}
+
+//how a click or drag selection combines with what's already selected
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SelectOp {
+    Replace,
+    Add,
+    Subtract,
+}
+
 #[derive(Clone, Copy)]
 pub enum DragMode {
     DragNone,
-    SelectBox {start_pt: Point, last_pt: Point},
+    SelectBox {start_pt: Point, last_pt: Point, op: SelectOp},
+    Lasso { op: SelectOp },
     CreateShape { shape_id: ShapeBarShape, start_pt: Point, last_pt: Point },
     DragShapes { last_pt: Point, click_shape: ShapeID, clear_select: bool },
     DragResize { click_box: ShapeID, drag_vertex: DragVertex },
     DragRotate { click_box: ShapeID, last_angle: Radians },
-    DragLineVertex { shape_id: ShapeID, line_vertex: LineVertex }
+    DragLineVertex { shape_id: ShapeID, line_vertex: LineVertex },
+    DragPicker(PickerHover),
+    SelectText { shape_id: ShapeID },
+    Pan { last_pt: Point },
+    DragMinimap,
 }
 
 #[derive(Clone, Copy)]
 pub enum KeyboardMode {
     KeyboardNone,
     TextEdit(ShapeID, SystemTime),
+    InspectorEdit(ShapeProp),
+    PickerHexEdit,
+    LayerRename(usize),
+    PageRename(usize),
+    Palette,
 }
 
 #[derive(PartialEq, Clone)]
@@ -189,31 +454,80 @@ pub enum HoverItem {
    HoverText(ShapeID, usize),
    HoverRect(ShapeID),
    HoverLine(ShapeID),
+   HoverInspector(InspectorHover),
+   HoverPicker(PickerHover),
+   HoverLayers(LayerHover),
+   HoverTabs(TabHover),
+   HoverMinimap,
    HoverCreateLine {start_pt: Point, last_pt: Point, color: (u8, u8, u8)}
 }
 
 impl AppState {
-    pub fn new(viewport: &Point) -> AppState {
-        AppState {
+    pub fn new(viewport: &Point, video: &VideoSubsystem) -> AppState {
+        //SDL starts with text input on, it's only wanted while a text box is edited
+        let text_input = video.text_input();
+        text_input.stop();
+        let styles = StyleSheet::load_or_default(StyleSheet::PATH);
+        let shape_bar = ShapeBar::new(viewport, &styles);
+        let picker_anchor = shape_bar.picker_anchor();
+        let mut app = AppState {
             draw_list: DrawList::new(),
             draw_ctx: DrawCtx::new(viewport),
-            shape_bar: ShapeBar::new(viewport),
+            color_picker: ColorPicker::new(&picker_anchor),
+            shape_bar,
+            styles,
+            shape_styles: HashMap::new(),
             selection: HashMap::new(),
             line_select: HashMap::new(),
+            select_mode: SelectMode::Intersect,
+            select_base: HashSet::new(),
+            lasso: Vec::new(),
             drag_mode: DragMode::DragNone,
             hover_item: HoverItem::HoverNone,
             key_mode: KeyboardMode::KeyboardNone,
-            render_text: RenderText::new().unwrap(),
+            field_composing: false,
+            render_text: RenderText::new(&FontConfig::load_or_default(FontConfig::PATH))
+                .expect("Could not initialize text rendering"),
             text_boxes: HashMap::new(),
+            line_ends: HashMap::new(),
+            routes: HashMap::new(),
+            links: HashMap::new(),
+            camera: Point::origin(),
+            pages: vec![Page::new(0, "Page 1")],
+            page: 0,
+            next_page_id: 1,
+            page_tabs: PageTabs::new(viewport),
+            inspector: Inspector::new(viewport),
+            layer_panel: LayerPanel::new(viewport, viewport.y - PageTabs::HEIGHT),
+            minimap: Minimap::new(viewport, viewport.y - PageTabs::HEIGHT),
+            keymap: Keymap::load_or_default(Keymap::PATH),
+            palette: CommandPalette::new(viewport),
+            mouse_pt: Point::new(viewport.x / 2., viewport.y / 2.),
+            quit: false,
+            clipboard: video.clipboard(),
+            text_input,
             cursors: CursorMap::new()
+        };
+        if std::path::Path::new(Document::PATH).exists() {
+            app.open_document(Document::PATH);
         }
+        app
     }
     fn get_shape_select_box(&self, s: &DrawPolygon) -> ShapeSelectBox {
         ShapeSelectBox(s.rect.clone())
     }
+    fn text_rect(&self, id: &ShapeID) -> RotateRect {
+        match self.draw_list.get(id) {
+            Some(shape) => shape_text_rect(shape, self.text_boxes.get(id), &self.render_text),
+            None => RotateRect::default()
+        }
+    }
     fn is_hover_text(&self, p: &Point, vp: &Point) -> Option<(ShapeID, usize)> {
-        self.text_boxes.iter().find(|(id, _)| self.draw_list.get(id).unwrap().in_bounds(p, vp))
-            .map(|(id, tb)| (id, tb, self.draw_list.get(id).unwrap().rect()))
+        self.text_boxes.iter().filter(|(id, _)| self.draw_list.is_interactive(id)).find(|(id, _)| match self.draw_list.get(id).unwrap() {
+                Shape::Line(_) => self.text_rect(id).in_bounds(p, vp),
+                s => s.in_bounds(p, vp)
+            })
+            .map(|(id, tb)| (id, tb, self.text_rect(id)))
             .and_then(|(id, tb, rect)| tb.hover_text(p, &rect, &self.render_text, vp).map(|pos| (*id, pos)))
     }
     fn is_hover_select_box(&self, p: &Point, vp: &Point) -> Option<(ShapeID, BoxHover)> {
@@ -222,14 +536,266 @@ impl AppState {
     fn is_hover_line(&self, p: &Point, vp: &Point) -> Option<(ShapeID, LineHover)> {
         self.line_select.iter().filter_map(|(id, l)| l.get_hover(p, vp).map(|lh| (*id, lh))).nth(0)
     }
-    pub fn handle_hover_click(&mut self, pt: &Point, clear_select: bool, cursor: &mut SystemCursor) {
+    fn selected_ids(&self) -> Vec<ShapeID> {
+        self.selection.keys().chain(self.line_select.keys()).cloned().collect()
+    }
+    fn prop_value(&self, id: &ShapeID, prop: ShapeProp) -> Option<PropValue> {
+        match prop {
+            ShapeProp::Style => self.shape_styles.get(id).map(|name| PropValue::Style(name.clone())),
+            ShapeProp::Link => {
+                let page = self.links.get(id).and_then(|link| self.pages.iter().find(|p| p.id == *link));
+                Some(PropValue::Link(page.map_or("none".to_string(), |p| p.name.clone())))
+            }
+            ShapeProp::TextScale => self.text_boxes.get(id).map(|tb| PropValue::Num(tb.text_scale())),
+            ShapeProp::TextAlign => self.text_boxes.get(id).map(|tb| PropValue::Align(tb.align())),
+            ShapeProp::TextVAlign => self.text_boxes.get(id).map(|tb| PropValue::VAlign(tb.valign())),
+            ShapeProp::TextPadding => self.text_boxes.get(id).map(|tb| PropValue::Num(tb.padding())),
+            ShapeProp::TextFit => self.text_boxes.get(id).map(|tb| PropValue::Fit(tb.fit())),
+            ShapeProp::TextOverflow => self.text_boxes.get(id).map(|tb| PropValue::Overflow(tb.overflow())),
+            //lines only show label properties once they have a label
+            ShapeProp::LabelPosition | ShapeProp::LabelOffset | ShapeProp::LabelFollow if !self.text_boxes.contains_key(id) => None,
+            _ => self.draw_list.get(id).and_then(|s| s.get_prop(prop))
+        }
+    }
+    fn inspector_props(&self) -> Vec<(ShapeProp, PropValue)> {
+        let ids = self.selected_ids();
+        ShapeProp::ALL.iter().filter_map(|prop| 
+            PropValue::merge(ids.iter().filter_map(|id| self.prop_value(id, *prop))).map(|val| (*prop, val))
+        ).collect()
+    }
+    fn set_prop_value(&mut self, id: &ShapeID, prop: ShapeProp, val: &PropValue) {
+        if let PropValue::Style(name) = val {
+            self.set_shape_style(*id, name);
+            return;
+        }
+        //an override would be lost the next time the style is updated
+        if prop.is_styled() {
+            self.shape_styles.remove(id);
+        }
+        if prop.is_text() {
+            if let Some(tbox) = self.text_boxes.get_mut(id) {
+                match (prop, val) {
+                    (ShapeProp::TextScale, PropValue::Num(scale)) => tbox.set_text_scale(f32::max(*scale, 0.1)),
+                    (ShapeProp::TextPadding, PropValue::Num(padding)) => tbox.set_padding(*padding),
+                    (_, PropValue::Align(align)) => tbox.set_align(*align),
+                    (_, PropValue::VAlign(valign)) => tbox.set_valign(*valign),
+                    (_, PropValue::Fit(fit)) => tbox.set_fit(*fit),
+                    (_, PropValue::Overflow(overflow)) => tbox.set_overflow(*overflow),
+                    _ => {}
+                }
+            }
+            return;
+        }
+        if let Some(s) = self.draw_list.get_mut(id) {
+            s.set_prop(prop, val);
+            match s {
+                Shape::Polygon(ref draw_poly) => {
+                    self.selection.insert(*id, ShapeSelectBox(draw_poly.rect.clone()));
+                }
+                Shape::Line(ref draw_line) => {
+                    self.line_select.insert(*id, SelectLine(draw_line.clone()));
+                }
+            }
+        }
+    }
+    fn set_selection_prop(&mut self, prop: ShapeProp, val: &PropValue) {
+        for id in self.selected_ids() {
+            self.set_prop_value(&id, prop, val);
+        }
+    }
+    fn adjust_selection_prop(&mut self, prop: ShapeProp, notches: f32) {
+        if let Some(step) = prop.step() {
+            for id in self.selected_ids() {
+                if let Some(PropValue::Num(v)) = self.prop_value(&id, prop) {
+                    self.set_prop_value(&id, prop, &PropValue::Num(v + notches * step));
+                }
+            }
+        }
+    }
+    fn picker_prop(&self, id: &ShapeID) -> ShapeProp {
+        match (self.color_picker.target, self.draw_list.get(id)) {
+            (ColorTarget::Fill, Some(Shape::Polygon(_))) => ShapeProp::FillColor,
+            _ => ShapeProp::StrokeColor
+        }
+    }
+    //color of the first selected shape, or of new shapes if nothing is selected
+    fn current_pick_color(&self) -> glm::Vec4 {
+        if let ColorTarget::Text = self.color_picker.target {
+            return self.text_targets().into_iter().nth(0)
+                .map(|(id, range)| self.text_boxes[&id].color_at(range))
+                .unwrap_or(self.shape_bar.style(ShapeBarShape::TextBox, &self.styles).text_color);
+        }
+        self.selected_ids().iter()
+            .filter_map(|id| match self.prop_value(id, self.picker_prop(id)) {
+                Some(PropValue::Color(c)) => Some(c),
+                _ => None
+            }).nth(0)
+            .unwrap_or_else(|| {
+                let style = self.shape_bar.style(ShapeBarShape::Rect, &self.styles);
+                match self.color_picker.target {
+                    ColorTarget::Fill => style.fill_color,
+                    ColorTarget::Stroke | ColorTarget::Text => style.stroke_color
+                }
+            })
+    }
+    fn apply_picked_color(&mut self) {
+        let color = self.color_picker.color();
+        //with nothing to color the pick is kept for new shapes, the styles existing shapes use are left alone
+        if let ColorTarget::Text = self.color_picker.target {
+            if self.text_targets().is_empty() {
+                self.shape_bar.pick_color(ColorTarget::Text, color, &self.styles);
+            }
+            self.update_text_attrs(|attrs| attrs.color = Some(color));
+            return;
+        }
+        let ids = self.selected_ids();
+        if ids.is_empty() {
+            self.shape_bar.pick_color(self.color_picker.target, color, &self.styles);
+        }
+        for id in ids {
+            let prop = self.picker_prop(&id);
+            self.set_prop_value(&id, prop, &PropValue::Color(color));
+        }
+    }
+    //text attributes are edited in the box being typed in, or over the whole text of the selected boxes
+    fn text_targets(&self) -> Vec<(ShapeID, Option<Range<usize>>)> {
+        match self.key_mode {
+            KeyboardMode::TextEdit(id, _) => vec![(id, self.text_boxes[&id].selection())],
+            _ => self.selection.keys()
+                .filter_map(|id| self.text_boxes.get(id).map(|tb| (*id, Some(tb.all_text())))).collect()
+        }
+    }
+    fn update_text_attrs<F: Fn(&mut TextAttrs)>(&mut self, f: F) {
+        for (id, range) in self.text_targets() {
+            if let Some(tbox) = self.text_boxes.get_mut(&id) {
+                tbox.update_attrs(range, &f);
+            }
+        }
+    }
+    //turns the attribute off if all the targeted text has it, on otherwise
+    fn toggle_text_attr(&mut self, attr: TextAttr) {
+        let on = !self.text_targets().into_iter().all(|(id, range)| self.text_boxes[&id].attr_on(attr, range));
+        self.update_text_attrs(|attrs| attrs.set(attr, on));
+    }
+    //a new shape refers to its style, unless colors were picked for new shapes. then it only starts out with the style's look
+    fn style_new_shape(&mut self, id: ShapeID, shape_id: ShapeBarShape) {
+        if !self.shape_bar.has_picked() {
+            self.set_shape_style(id, shape_id.style_name());
+            return;
+        }
+        let style = self.shape_bar.style(shape_id, &self.styles);
+        self.apply_look(&id, &style);
+    }
+    fn set_shape_style(&mut self, id: ShapeID, name: &str) {
+        self.shape_styles.insert(id, name.to_string());
+        self.apply_style(&id);
+    }
+    fn apply_style(&mut self, id: &ShapeID) {
+        let style = match self.shape_styles.get(id) {
+            Some(name) => self.styles.get(name).clone(),
+            None => return
+        };
+        self.apply_look(id, &style);
+    }
+    fn apply_look(&mut self, id: &ShapeID, style: &Style) {
+        if let Some(s) = self.draw_list.get_mut(id) {
+            style.apply(s);
+            if let (Shape::Line(ref draw_line), Some(sline)) = (s, self.line_select.get_mut(id)) {
+                sline.0 = draw_line.clone();
+            }
+        }
+        if let Some(tbox) = self.text_boxes.get_mut(id) {
+            style.apply_text(tbox);
+        }
+    }
+    //reapply a style to every shape referencing it after it was edited
+    fn update_style(&mut self, name: &str) {
+        let ids: Vec<ShapeID> = self.shape_styles.iter().filter(|(_, n)| *n == name).map(|(id, _)| *id).collect();
+        for id in ids {
+            self.apply_style(&id);
+        }
+        self.shape_bar.update_items(&self.styles);
+    }
+    //make the style of the first selected shape match that shape
+    fn update_style_from_selection(&mut self) {
+        let id = match self.selected_ids().into_iter().find(|id| self.shape_styles.contains_key(id)) {
+            Some(id) => id,
+            None => return
+        };
+        let name = self.shape_styles[&id].clone();
+        if let (Some(style), Some(s)) = (self.styles.get_mut(&name), self.draw_list.get(&id)) {
+            style.update_from(s, self.text_boxes.get(&id));
+        }
+        self.update_style(&name);
+    }
+    fn click_picker(&mut self, hover: PickerHover, pt: &Point) {
+        match hover {
+            PickerHover::Wheel | PickerHover::Value | PickerHover::Alpha => {
+                self.color_picker.drag(&hover, pt);
+                self.apply_picked_color();
+                self.drag_mode = DragMode::DragPicker(hover);
+            }
+            PickerHover::Swatch(_) => {
+                self.color_picker.drag(&hover, pt);
+                self.apply_picked_color();
+            }
+            PickerHover::Target(target) => {
+                self.color_picker.target = target;
+                let color = self.current_pick_color();
+                self.color_picker.set_color(&color);
+            }
+            PickerHover::Hex => {
+                self.color_picker.begin_hex_edit();
+                self.set_key_mode(KeyboardMode::PickerHexEdit);
+            }
+            PickerHover::Panel => {}
+        }
+    }
+    fn click_inspector(&mut self, prop: ShapeProp) {
+        let val = match self.inspector_props().into_iter().find(|(p, _)| *p == prop) {
+            Some((_, val)) => val,
+            None => return
+        };
+        if let ShapeProp::Style = prop {
+            let name = match val {
+                PropValue::Style(name) => self.styles.next_name(&name),
+                _ => StyleSheet::DEFAULT.to_string()
+            };
+            self.set_selection_prop(prop, &PropValue::Style(name));
+        }
+        else if let ShapeProp::Link = prop {
+            self.cycle_links();
+        }
+        else if let Some(new_val) = toggle_value(&prop, &val) {
+            self.set_selection_prop(prop, &new_val);
+        }
+        else {
+            self.inspector.begin_edit(&prop, &val);
+            self.set_key_mode(KeyboardMode::InspectorEdit(prop));
+        }
+    }
+    pub fn handle_hover_click(&mut self, screen_pt: &Point, op: SelectOp, clicks: u8, extend: bool, cursor: &mut SystemCursor) {
+        let pt = &self.to_world(screen_pt);
+        let clear_select = op == SelectOp::Replace;
         match self.hover_item {
+            HoverItem::HoverRect(select_id) | HoverItem::HoverLine(select_id) if op == SelectOp::Subtract => {
+                self.selection.remove(&select_id);
+                self.line_select.remove(&select_id);
+                self.hover_item = HoverItem::HoverNone;
+            }
+            HoverItem::HoverRect(select_id) if clicks == 2 && self.links.contains_key(&select_id) => {
+                self.follow_link(select_id);
+            }
             HoverItem::HoverRect(select_id) => {
                 if self.selection[&select_id].in_bounds(pt, &self.draw_ctx.viewport) {
                     self.drag_mode = DragMode::DragShapes { last_pt: *pt, click_shape: select_id, clear_select };
                     *cursor = SystemCursor::Hand;
                 }
             }
+            HoverItem::HoverLine(select_id) if clicks == 2 => {
+                self.edit_label(select_id);
+                *cursor = SystemCursor::IBeam;
+            }
             HoverItem::HoverLine(select_id) => {
                 if self.line_select[&select_id].in_bounds(pt, &self.draw_ctx.viewport) {
                     self.drag_mode = DragMode::DragShapes { last_pt: *pt, click_shape: select_id, clear_select };
@@ -258,90 +824,159 @@ impl AppState {
                 *cursor = SystemCursor::Crosshair;
             }
             HoverItem::HoverText(tb_id, cursor_pos) => {
-                self.text_boxes.get_mut(&tb_id).map(|tb| tb.set_cursor_pos(cursor_pos));
-                self.key_mode = KeyboardMode::TextEdit(tb_id, SystemTime::now());
+                //shift extends the selection only in the box already being edited
+                let extend = extend && self.editing_text() == Some(tb_id);
+                if let Some(tb) = self.text_boxes.get_mut(&tb_id) {
+                    match clicks {
+                        2 => tb.select_word(cursor_pos),
+                        n if n >= 3 => tb.select_line(cursor_pos),
+                        _ => tb.set_cursor_pos(cursor_pos, extend)
+                    }
+                }
+                self.set_key_mode(KeyboardMode::TextEdit(tb_id, SystemTime::now()));
+                self.drag_mode = DragMode::SelectText { shape_id: tb_id };
                 *cursor = SystemCursor::IBeam;
             }
             HoverItem::HoverLineVertex(shape_id, line_vertex) => {
+                //a dragged end comes loose from its shape until it's dropped
+                let end = match line_vertex {
+                    LineVertex::P1 => Some(0),
+                    LineVertex::P2 => Some(1),
+                    LineVertex::Waypoint(_) => None
+                };
+                if let (Some(end), Some(ends)) = (end, self.line_ends.get_mut(&shape_id)) {
+                    ends[end] = None;
+                }
                 self.drag_mode = DragMode::DragLineVertex { shape_id, line_vertex };
                 *cursor = SystemCursor::Hand;
             }
+            HoverItem::HoverInspector(InspectorHover::Prop(prop)) => {
+                self.click_inspector(prop);
+            }
+            HoverItem::HoverInspector(InspectorHover::Panel) => {}
+            HoverItem::HoverLayers(hover) => {
+                self.click_layers(hover, clicks);
+            }
+            HoverItem::HoverTabs(hover) => {
+                self.click_tabs(hover, clicks);
+            }
+            HoverItem::HoverMinimap => {
+                self.center_on_minimap(screen_pt);
+                self.drag_mode = DragMode::DragMinimap;
+                *cursor = SystemCursor::Hand;
+            }
+            HoverItem::HoverPicker(hover) => {
+                self.click_picker(hover, screen_pt);
+            }
             HoverItem::HoverCreateLine { start_pt, last_pt, color } => {
                 let id = self.draw_list.add(LineBuilder::new().points2(&start_pt, &last_pt).color(color.0, color.1, color.2).get());
                 self.line_select.insert(id, SelectLine::new(start_pt, last_pt));
+                self.style_new_shape(id, ShapeBarShape::Line);
+                self.attach_line(id);
                 self.hover_item = HoverItem::HoverNone;
             }
             HoverItem::HoverNone => {}
         }
     }
-    pub fn handle_select(&mut self, pt: &Point, clear_select: bool, cursor: &mut SystemCursor) {
+    //a double clicked line gets a label if it doesn't have one, which is then edited
+    fn edit_label(&mut self, id: ShapeID) {
+        if let Entry::Vacant(entry) = self.text_boxes.entry(id) {
+            entry.insert(TextBox::new());
+            self.apply_style(&id);
+        }
+        if let Some(tb) = self.text_boxes.get_mut(&id) {
+            tb.select_all();
+        }
+        self.set_key_mode(KeyboardMode::TextEdit(id, SystemTime::now()));
+    }
+    pub fn handle_select(&mut self, screen_pt: &Point, op: SelectOp, cursor: &mut SystemCursor) {
+        let pt = &self.to_world(screen_pt);
+        let clear_select = op == SelectOp::Replace;
         if clear_select {
             self.clear_selection();
         }
-        if let Some(shape_action) = self.shape_bar.click_shape(&pt, &self.draw_ctx.viewport) {
+        if let Some(shape_action) = self.shape_bar.click_shape(screen_pt, &self.draw_ctx.viewport) {
             match shape_action {
                 ShapeBarAction::CreateShape(item_id) => {
                     *cursor =  SystemCursor::Crosshair;
-                    let size = Point::new(ShapeBarShape::DEFAULT_SIZE, ShapeBarShape::DEFAULT_SIZE);
-                    let r = Rect::new(*pt - size / 2., *pt + size / 2.);
-                    let s = self.shape_bar.get_shape(item_id, &r, false);
-                    self.hover_item = HoverItem::HoverShape(item_id, s);
-                }
-                ShapeBarAction::PickColor => {
-
+                    self.start_create(item_id, pt);
                 }
+                ShapeBarAction::PickColor => self.toggle_color_picker()
             };
          }
-        else if let Some(click_shape) = self.draw_list.click_shape(&pt, &self.draw_ctx.viewport) {
-            let s = self.draw_list.get(&click_shape).unwrap();
-            match s {
-                Shape::Polygon(ref draw_poly) => {
-                    self.selection.insert(click_shape, self.get_shape_select_box(draw_poly));
-                }
-                Shape::Line(ref draw_line) => {
-                    self.line_select.insert(click_shape, SelectLine(draw_line.clone()));
-                }
-            };
+        else if let Some(click_shape) = self.draw_list.click_shape(pt, &self.draw_ctx.viewport).filter(|_| op != SelectOp::Subtract) {
+            self.select_shape(click_shape);
             self.drag_mode = DragMode::DragShapes { last_pt: *pt, click_shape, clear_select };
             //self.hover_item = HoverItem::HoverRect(click_shape);
             *cursor = SystemCursor::Hand;
         }
-        else if clear_select {
-            self.drag_mode = DragMode::SelectBox{start_pt: *pt, last_pt: *pt};
+        else {
+            self.start_region_select(pt, op, false);
         }
     }
-    fn handle_drag(&mut self, pt: &Point, cursor: &mut SystemCursor) {
+    fn start_region_select(&mut self, pt: &Point, op: SelectOp, lasso: bool) {
+        if op == SelectOp::Replace {
+            self.clear_selection();
+        }
+        self.select_base = self.selection.keys().chain(self.line_select.keys()).cloned().collect();
+        if lasso {
+            self.lasso = vec![*pt];
+            self.drag_mode = DragMode::Lasso { op };
+        }
+        else {
+            self.drag_mode = DragMode::SelectBox { start_pt: *pt, last_pt: *pt, op };
+        }
+    }
+    //selects what the region picks combined with what was selected when the drag started
+    fn select_region(&mut self, region: &[Point], op: SelectOp) {
+        let picked: HashSet<ShapeID> = self.draw_list.get_region_selection(region, self.select_mode, &self.draw_ctx.viewport)
+            .into_iter().collect();
+        let ids: Vec<ShapeID> = match op {
+            SelectOp::Replace => picked.into_iter().collect(),
+            SelectOp::Add => self.select_base.union(&picked).cloned().collect(),
+            SelectOp::Subtract => self.select_base.difference(&picked).cloned().collect(),
+        };
+        self.selection.clear();
+        self.line_select.clear();
+        ids.into_iter().for_each(|id| self.select_shape(id));
+    }
+    fn handle_drag(&mut self, screen_pt: &Point, cursor: &mut SystemCursor) {
+        let pt = &self.to_world(screen_pt);
         let vp = &self.draw_ctx.viewport;
         match self.drag_mode {
-            DragMode::DragShapes { ref mut last_pt, ref mut clear_select, .. } => {
-                *clear_select = false;
-                *cursor = SystemCursor::Hand;
-                for (id, rect) in self.selection.iter_mut() {
-                    self.draw_list.get_mut(id).map(|s| s.drag(&(*pt - *last_pt)));
-                    rect.drag(&(*pt - *last_pt));
-                }
-                for (id, line) in self.line_select.iter_mut() {
-                    self.draw_list.get_mut(id).map(|s| s.drag(&(*pt - *last_pt)));
-                    line.drag(&(*pt - *last_pt));
+            DragMode::SelectText { shape_id } => {
+                *cursor = SystemCursor::IBeam;
+                let rect = self.text_rect(&shape_id);
+                if let Some(tb) = self.text_boxes.get_mut(&shape_id) {
+                    let pos = tb.nearest_text_pos(pt, &rect, &self.render_text, vp);
+                    tb.set_cursor_pos(pos, true);
                 }
-                *last_pt = *pt;
             }
-            DragMode::SelectBox {start_pt, ref mut last_pt} => {
-                *last_pt = *pt;
-                let (shapes, lines)= self.draw_list.get_box_selection(&Rect::new(start_pt, *pt), vp);
-                self.selection = HashMap::from_iter(shapes.iter().map(|(id, shape)|
-                            (*id, self.get_shape_select_box(shape))
-                    ));
-                self.line_select = HashMap::from_iter(lines.into_iter().map(|(id, line)| 
-                        (id, SelectLine(line.clone()))
-                    ));
+            DragMode::DragShapes { last_pt, click_shape, .. } => {
+                *cursor = SystemCursor::Hand;
+                self.drag_selection(&(*pt - last_pt));
+                self.drag_mode = DragMode::DragShapes { last_pt: *pt, click_shape, clear_select: false };
+            }
+            DragMode::SelectBox { start_pt, op, .. } => {
+                self.drag_mode = DragMode::SelectBox { start_pt, last_pt: *pt, op };
+                self.select_region(&Rect::new(start_pt, *pt).verts(), op);
+            }
+            DragMode::Lasso { op } => {
+                const LASSO_STEP: f32 = 3.;
+                if self.lasso.last().is_none_or(|last| last.dist(pt) >= LASSO_STEP) {
+                    self.lasso.push(*pt);
+                }
+                let region = self.lasso.clone();
+                self.select_region(&region, op);
             }
             DragMode::DragRotate { click_box, ref mut last_angle } => {
                 *cursor = SystemCursor::Hand;
                 if let Some(sbox) = self.selection.get_mut(&click_box) {
                     let angle = sbox.get_rotate_angle(pt, vp);
                     sbox.0.set_radians(sbox.0.rot + angle - *last_angle);
-                    self.draw_list.get_mut(&click_box).map(|s| s.set_rect(&sbox.0.clone()));
+                    if let Some(s) = self.draw_list.get_mut(&click_box) {
+                        s.set_rect(&sbox.0);
+                    }
                     *last_angle = angle;
                 }
             }
@@ -349,27 +984,42 @@ impl AppState {
                 *cursor = get_drag_hover_cursor(&drag_vertex);
                 if let Some(sbox) = self.selection.get_mut(&click_box) {
                     *drag_vertex = sbox.drag_side(&drag_vertex, &pt, vp);
-                    self.draw_list.get_mut(&click_box).map(|s| s.set_rect(&sbox.0.clone()));
-               }
-               if let Some(tbox) = self.text_boxes.get_mut(&click_box) {
-                   let rect = self.draw_list.get(&click_box).unwrap().rect();
-                   tbox.format_text(&rect, 0, &self.render_text);
+                    if let Some(s) = self.draw_list.get_mut(&click_box) {
+                        s.set_rect(&sbox.0);
+                    }
                }
             }
             DragMode::DragLineVertex { shape_id, line_vertex } => {
                 if let Some(sline) = self.line_select.get_mut(&shape_id) {
                   sline.drag_vertex(&line_vertex, &pt);  
-                  self.draw_list.get_mut(&shape_id).map(|s| s.drag_vertex(&line_vertex, &pt));
+                  if let Some(s) = self.draw_list.get_mut(&shape_id) {
+                      s.drag_vertex(&line_vertex, pt);
+                  }
                 }
             }
             DragMode::CreateShape { ref mut last_pt, .. } => {
                 *last_pt = *pt;
                 *cursor = SystemCursor::Crosshair;
             }
+            DragMode::DragPicker(hover) => {
+                if self.color_picker.drag(&hover, screen_pt) {
+                    self.apply_picked_color();
+                }
+            }
+            DragMode::Pan { last_pt } => {
+                *cursor = SystemCursor::Hand;
+                self.camera -= *screen_pt - last_pt;
+                self.drag_mode = DragMode::Pan { last_pt: *screen_pt };
+            }
+            DragMode::DragMinimap => {
+                *cursor = SystemCursor::Hand;
+                self.center_on_minimap(screen_pt);
+            }
             DragMode::DragNone => {}
         }
     }
-    fn handle_hover(&mut self, pt: &Point, cursor: &mut SystemCursor) {
+    fn handle_hover(&mut self, screen_pt: &Point, cursor: &mut SystemCursor) {
+        let pt = &self.to_world(screen_pt);
         let vp = &self.draw_ctx.viewport;
         if let HoverItem::HoverShape(_, ref mut s) = self.hover_item {
             *cursor = SystemCursor::Crosshair;
@@ -386,6 +1036,21 @@ impl AppState {
             *last_pt = *pt;
             *cursor = SystemCursor::Crosshair;
         }
+        else if let Some(picker_hover) = self.color_picker.get_hover(screen_pt, vp) {
+            self.hover_item = HoverItem::HoverPicker(picker_hover);
+        }
+        else if let Some(inspector_hover) = self.inspector.get_hover(screen_pt, &self.inspector_props()) {
+            self.hover_item = HoverItem::HoverInspector(inspector_hover);
+        }
+        else if let Some(layer_hover) = self.layer_panel.get_hover(screen_pt, &self.draw_list.layers()) {
+            self.hover_item = HoverItem::HoverLayers(layer_hover);
+        }
+        else if self.minimap.get_hover(screen_pt) {
+            self.hover_item = HoverItem::HoverMinimap;
+        }
+        else if let Some(tab_hover) = self.page_tabs.get_hover(screen_pt, self.pages.len()) {
+            self.hover_item = HoverItem::HoverTabs(tab_hover);
+        }
         else if let Some((select_id, box_hover)) = self.is_hover_select_box(&pt, vp) {
             match box_hover {
                 BoxHover::Rect => { 
@@ -420,28 +1085,47 @@ impl AppState {
     fn clear_selection(&mut self) {
         self.selection.clear();
         self.line_select.clear();
-        self.key_mode = KeyboardMode::KeyboardNone;
+        self.set_key_mode(KeyboardMode::KeyboardNone);
     }
     pub fn handle_mouse_event(&mut self, ev: &Event, kmod: &Mod) {
         match *ev {
-            Event::MouseButtonDown { mouse_btn, x, y, .. } => {
+            Event::MouseButtonDown { mouse_btn, x, y, clicks, .. } => {
                 if mouse_btn == sdl2::mouse::MouseButton::Left {
                     let pt = Point{x: x as f32,y: y as f32};
                     let mut use_cursor = SystemCursor::Arrow;
-                    let clear_select = (*kmod & Mod::LCTRLMOD) == Mod::NOMOD;
-                    if self.hover_item != HoverItem::HoverNone {
-                        self.handle_hover_click(&pt, clear_select, &mut use_cursor);
+                    //shift extends the selection, adding shapes to it like it extends selected text. ctrl adds too,
+                    //ctrl+shift takes shapes away from it and alt draws a lasso
+                    let shift = kmod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    let ctrl = kmod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+                    let op = if ctrl && shift { SelectOp::Subtract }
+                        else if ctrl || shift { SelectOp::Add }
+                        else { SelectOp::Replace };
+                    let lasso = kmod.intersects(Mod::LALTMOD | Mod::RALTMOD) && self.editing_text().is_none();
+                    if lasso {
+                        let pt = self.to_world(&pt);
+                        self.start_region_select(&pt, op, true);
+                    }
+                    else if self.hover_item != HoverItem::HoverNone {
+                        self.handle_hover_click(&pt, op, clicks, shift, &mut use_cursor);
                     }
                     else {
-                        self.handle_select(&pt, clear_select, &mut use_cursor);
+                        self.handle_select(&pt, op, &mut use_cursor);
                     }
                     self.cursors.get(&use_cursor).set();
                 }
+                else if let (sdl2::mouse::MouseButton::Middle, DragMode::DragNone) = (mouse_btn, self.drag_mode) {
+                    self.drag_mode = DragMode::Pan { last_pt: Point::new(x as f32, y as f32) };
+                }
             } 
             Event::MouseButtonUp{mouse_btn, .. } => {
+                if let (sdl2::mouse::MouseButton::Middle, DragMode::Pan { .. }) = (mouse_btn, self.drag_mode) {
+                    self.drag_mode = DragMode::DragNone;
+                }
                 if mouse_btn == sdl2::mouse::MouseButton::Left {
                     match self.drag_mode {
                         DragMode::DragShapes { click_shape, clear_select, .. } => {
+                            let lines: Vec<ShapeID> = self.line_select.keys().cloned().collect();
+                            lines.into_iter().for_each(|id| self.attach_line(id));
                             if clear_select { 
                                 if let Some(s) = self.selection.get(&click_shape) {
                                     let s = s.clone();
@@ -459,14 +1143,24 @@ impl AppState {
                         },
                         DragMode::CreateShape { shape_id, start_pt, last_pt } => {
                             let r = Rect::new(start_pt, last_pt);
-                            let fill = shape_id != ShapeBarShape::TextBox;
+                            let fill = self.shape_bar.style(shape_id, &self.styles).fill;
                             let s = self.shape_bar.get_shape(
-                                shape_id, &r, fill);
+                                shape_id, &r, fill, &self.styles);
                             let id = self.draw_list.add(s);
                             if let ShapeBarShape::TextBox = shape_id {
                                 self.text_boxes.insert(id, TextBox::new());
-                                self.key_mode = KeyboardMode::TextEdit(id, SystemTime::now());
+                                self.set_key_mode(KeyboardMode::TextEdit(id, SystemTime::now()));
                             }
+                            self.style_new_shape(id, shape_id);
+                        }
+                        DragMode::DragPicker(_) => {
+                            self.color_picker.add_recent();
+                        }
+                        DragMode::DragLineVertex { shape_id, .. } => {
+                            self.attach_line(shape_id);
+                        }
+                        DragMode::Lasso { .. } => {
+                            self.lasso.clear();
                         }
                         _ => {}
                     }
@@ -475,6 +1169,7 @@ impl AppState {
             }
             Event::MouseMotion{ x, y, ..} => {
                 let pt = Point{x:x as f32, y:y as f32};
+                self.mouse_pt = pt;
                 let mut use_cursor = SystemCursor::Arrow;
                 if let DragMode::DragNone = self.drag_mode {
                     self.handle_hover(&pt, &mut use_cursor);
@@ -484,40 +1179,774 @@ impl AppState {
                 }
                 self.cursors.get(&use_cursor).set();
             }
+            Event::MouseWheel{ x, y, .. } => {
+                const PAN_STEP: f32 = 40.;
+                if let HoverItem::HoverInspector(InspectorHover::Prop(prop)) = self.hover_item {
+                    self.adjust_selection_prop(prop, y as f32);
+                }
+                else if let HoverItem::HoverLayers(LayerHover::Row(layer, LayerPart::Opacity)) = self.hover_item {
+                    if let Some(l) = self.draw_list.layer_mut(layer) {
+                        l.opacity = (l.opacity + y as f32 * LayerPanel::OPACITY_STEP).clamp(0., 1.);
+                    }
+                }
+                else if let Some(id) = self.editing_text()
+                    .filter(|id| self.text_rect(id).in_bounds(&self.to_world(&self.mouse_pt), &self.draw_ctx.viewport)) {
+                    let rect = self.text_rect(&id);
+                    if let Some(tb) = self.text_boxes.get_mut(&id) {
+                        tb.scroll(-y, &rect, &self.render_text);
+                    }
+                }
+                //nothing under the mouse uses the wheel, so it pans the page
+                else {
+                    self.camera += Point::new(x as f32 * PAN_STEP, -y as f32 * PAN_STEP);
+                }
+            }
             _ => {}
         }
     }
+    //text input events and the IME are enabled in every mode that takes typed text
+    fn set_key_mode(&mut self, mode: KeyboardMode) {
+        if let Some(id) = self.editing_text() {
+            if let Some(tb) = self.text_boxes.get_mut(&id) {
+                tb.end_edit();
+            }
+            //a label left empty is removed from its line
+            if let (Some(Shape::Line(_)), Some(tb)) = (self.draw_list.get(&id), self.text_boxes.get(&id)) {
+                if tb.text().is_empty() {
+                    self.text_boxes.remove(&id);
+                }
+            }
+        }
+        //keeps the IME candidate window next to the box
+        if let KeyboardMode::TextEdit(id, _) = mode {
+            if self.draw_list.get(&id).is_some() {
+                let verts = self.text_rect(&id).verts(&self.draw_ctx.viewport);
+                let verts: Vec<Point> = verts.iter().map(|v| *v - self.camera).collect();
+                let min = verts.iter().fold(verts[0], |m, v| Point::new(f32::min(m.x, v.x), f32::min(m.y, v.y)));
+                let max = verts.iter().fold(verts[0], |m, v| Point::new(f32::max(m.x, v.x), f32::max(m.y, v.y)));
+                self.text_input.set_rect(sdl2::rect::Rect::new(
+                    min.x as i32, min.y as i32, (max.x - min.x) as u32, (max.y - min.y) as u32));
+            }
+        }
+        match mode {
+            KeyboardMode::KeyboardNone => self.text_input.stop(),
+            _ if !self.text_input.is_active() => self.text_input.start(),
+            _ => {}
+        }
+        self.field_composing = false;
+        self.key_mode = mode;
+    }
+    fn editing_text(&self) -> Option<ShapeID> {
+        match self.key_mode {
+            KeyboardMode::TextEdit(id, _) => Some(id),
+            _ => None
+        }
+    }
+    fn is_text_editing(&self) -> bool {
+        !matches!(self.key_mode, KeyboardMode::KeyboardNone)
+    }
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+    //while text is being edited it gets every plain key, escape stops editing
+    //and only bound ctrl/alt chords fall through to the keymap.
+    //keys belong to the IME while it is composing
     pub fn handle_keyboard_event(&mut self, ev: &Event) {
+        let chord = match *ev {
+            Event::KeyDown { keycode: Some(keycode), keymod, .. } => KeyChord::new(keycode, keymod),
+            _ => return
+        };
+        if self.composing() {
+            return;
+        }
+        if self.is_text_editing() && (!chord.is_command_chord() || self.keymap.lookup(&chord).is_none()) {
+            if chord.keycode == Keycode::Escape {
+                self.set_key_mode(KeyboardMode::KeyboardNone);
+            }
+            else {
+                self.handle_text_key(&chord);
+            }
+            return;
+        }
+        if let Some(cmd) = self.keymap.lookup(&chord) {
+            self.run_command(cmd);
+        }
+    }
+    fn composing(&self) -> bool {
+        match self.key_mode {
+            KeyboardMode::TextEdit(id, _) => self.text_boxes[&id].composition().is_some(),
+            KeyboardMode::Palette => self.palette.composing(),
+            _ => self.field_composing
+        }
+    }
+    //typed chars come from text input events in every mode, so they follow the keyboard layout and the IME
+    pub fn handle_text_input_event(&mut self, ev: &Event) {
+        let input = match get_text_input(ev) {
+            Some(input) => input,
+            None => return
+        };
+        match (self.key_mode, input) {
+            (KeyboardMode::TextEdit(id, _), TextInput::Commit(text)) => self.text_boxes.get_mut(&id).unwrap().commit_text(&text),
+            (KeyboardMode::TextEdit(id, _), TextInput::Compose(comp)) => self.text_boxes.get_mut(&id).unwrap().set_composition(comp),
+            (KeyboardMode::Palette, input) => self.palette.input_text(input),
+            //fields show only committed text, the IME shows what it's composing itself
+            (_, TextInput::Compose(comp)) => self.field_composing = !comp.text.is_empty(),
+            (mode, TextInput::Commit(text)) => {
+                self.field_composing = false;
+                for ch in text.chars().filter(|c| !c.is_control()) {
+                    match mode {
+                        KeyboardMode::InspectorEdit(_) => self.inspector.push_char(ch),
+                        KeyboardMode::PickerHexEdit => self.color_picker.push_char(ch),
+                        KeyboardMode::LayerRename(_) => self.layer_panel.push_char(ch),
+                        KeyboardMode::PageRename(_) => self.page_tabs.push_char(ch),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    //keys that edit a text box without typing
+    fn handle_text_key(&mut self, chord: &KeyChord) {
+        let keycode = chord.keycode;
         if let KeyboardMode::TextEdit(shape_id, _) = self.key_mode {
-            if let Event::KeyDown { keycode: Some(keycode), .. } = *ev {
-                if let Some(ch) = get_char_from_keycode(keycode) {
-                    let rect = self.draw_list.get(&shape_id).unwrap().rect();
-                    self.text_boxes.get_mut(&shape_id).unwrap().insert_char(ch, &rect, &self.render_text);
+            let rect = self.text_rect(&shape_id);
+            let tb = self.text_boxes.get_mut(&shape_id).unwrap();
+            if let Some(dir) = get_dir_from_chord(chord) {
+                tb.move_cursor(dir, chord.shift, &rect, &self.render_text);
+            }
+            else if keycode == Keycode::Backspace {
+                tb.delete_char();
+            }
+            else if keycode == Keycode::Delete {
+                tb.delete_forward();
+            }
+            //a line break starts a new paragraph, it's an undo step of its own
+            else if keycode == Keycode::Return || keycode == Keycode::KpEnter {
+                tb.insert_char('\n');
+            }
+            return;
+        }
+        if chord.is_command_chord() {
+            return;
+        }
+        match self.key_mode {
+            KeyboardMode::TextEdit(..) => {}
+            KeyboardMode::InspectorEdit(prop) => {
+                match keycode {
+                    Keycode::Return | Keycode::KpEnter => {
+                        if let Some(val) = self.inspector.finish_edit(&prop) {
+                            self.set_selection_prop(prop, &val);
+                        }
+                        self.set_key_mode(KeyboardMode::KeyboardNone);
+                    }
+                    Keycode::Backspace => self.inspector.pop_char(),
+                    _ => {}
                 }
-                else if let Some(dir) = get_dir_from_keycode(keycode) {
-                    self.text_boxes.get_mut(&shape_id).unwrap().move_cursor(dir);
+            }
+            KeyboardMode::PickerHexEdit => {
+                match keycode {
+                    Keycode::Return | Keycode::KpEnter => {
+                        if self.color_picker.finish_hex_edit() {
+                            self.apply_picked_color();
+                            self.color_picker.add_recent();
+                        }
+                        self.set_key_mode(KeyboardMode::KeyboardNone);
+                    }
+                    Keycode::Backspace => self.color_picker.pop_char(),
+                    _ => {}
                 }
-                else if keycode == Keycode::Backspace {
-                    let rect = self.draw_list.get(&shape_id).unwrap().rect();
-                    self.text_boxes.get_mut(&shape_id).unwrap().delete_char(&rect, &self.render_text);
+            }
+            KeyboardMode::LayerRename(layer) => {
+                match keycode {
+                    Keycode::Return | Keycode::KpEnter => {
+                        if let (Some(name), Some(l)) = (self.layer_panel.finish_rename(), self.draw_list.layer_mut(layer)) {
+                            l.name = name;
+                        }
+                        self.set_key_mode(KeyboardMode::KeyboardNone);
+                    }
+                    Keycode::Backspace => self.layer_panel.pop_char(),
+                    _ => {}
                 }
             }
+            KeyboardMode::PageRename(page) => {
+                match keycode {
+                    Keycode::Return | Keycode::KpEnter => {
+                        if let (Some(name), Some(p)) = (self.page_tabs.finish_rename(), self.pages.get_mut(page)) {
+                            p.name = name;
+                        }
+                        self.set_key_mode(KeyboardMode::KeyboardNone);
+                    }
+                    Keycode::Backspace => self.page_tabs.pop_char(),
+                    _ => {}
+                }
+            }
+            KeyboardMode::Palette => {
+                match keycode {
+                    Keycode::Return | Keycode::KpEnter => {
+                        self.set_key_mode(KeyboardMode::KeyboardNone);
+                        if let Some(cmd) = self.palette.accept() {
+                            self.run_command(cmd);
+                        }
+                    }
+                    Keycode::Backspace => self.palette.pop_char(),
+                    _ => {
+                        if let Some(dir) = get_dir_from_keycode(keycode) {
+                            self.palette.move_cursor(dir, &self.render_text);
+                        }
+                    }
+                }
+            }
+            KeyboardMode::KeyboardNone => {}
         }
-        match *ev {
-            Event::KeyDown { keycode: Some(Keycode::Delete), .. } => self.delete_selection(),
-            _ => {}
+    }
+    pub fn run_command(&mut self, cmd: CommandId) {
+        const NUDGE: f32 = 1.;
+        const NUDGE_FAR: f32 = 10.;
+        const TEXT_SIZE_STEP: f32 = 1.25;
+        let pt = self.to_world(&self.mouse_pt);
+        match cmd {
+            CommandId::Delete => self.delete_selection(),
+            CommandId::SelectAll => {
+                if let Some(id) = self.editing_text() {
+                    if let Some(tb) = self.text_boxes.get_mut(&id) {
+                        tb.select_all();
+                    }
+                    return;
+                }
+                for id in self.draw_list.interactive_ids() {
+                    self.select_shape(id);
+                }
+            }
+            CommandId::Copy => self.copy_text(false),
+            CommandId::Cut => self.copy_text(true),
+            CommandId::Paste => {
+                let text = match self.clipboard.clipboard_text() {
+                    Ok(text) => text,
+                    Err(e) => return eprintln!("Could not read clipboard: {}", e)
+                };
+                self.edit_text(|tb| tb.insert_text(&text));
+            }
+            CommandId::Undo => self.edit_text(|tb| tb.undo()),
+            CommandId::Redo => self.edit_text(|tb| tb.redo()),
+            CommandId::Deselect => {
+                self.clear_selection();
+                self.hover_item = HoverItem::HoverNone;
+            }
+            CommandId::NudgeLeft => self.drag_selection(&Point::new(-NUDGE, 0.)),
+            CommandId::NudgeRight => self.drag_selection(&Point::new(NUDGE, 0.)),
+            CommandId::NudgeUp => self.drag_selection(&Point::new(0., -NUDGE)),
+            CommandId::NudgeDown => self.drag_selection(&Point::new(0., NUDGE)),
+            CommandId::NudgeLeftFar => self.drag_selection(&Point::new(-NUDGE_FAR, 0.)),
+            CommandId::NudgeRightFar => self.drag_selection(&Point::new(NUDGE_FAR, 0.)),
+            CommandId::NudgeUpFar => self.drag_selection(&Point::new(0., -NUDGE_FAR)),
+            CommandId::NudgeDownFar => self.drag_selection(&Point::new(0., NUDGE_FAR)),
+            CommandId::BringForward => {
+                let ids = self.selected_ids().into_iter().collect();
+                self.draw_list.bring_forward(&ids);
+            }
+            CommandId::SendBackward => {
+                let ids = self.selected_ids().into_iter().collect();
+                self.draw_list.send_backward(&ids);
+            }
+            CommandId::BringToFront => {
+                let ids = self.selected_ids().into_iter().collect();
+                self.draw_list.bring_to_front(&ids);
+            }
+            CommandId::SendToBack => {
+                let ids = self.selected_ids().into_iter().collect();
+                self.draw_list.send_to_back(&ids);
+            }
+            CommandId::ToolCircle => self.start_create(ShapeBarShape::Circle, &pt),
+            CommandId::ToolTriangle => self.start_create(ShapeBarShape::Triangle, &pt),
+            CommandId::ToolRect => self.start_create(ShapeBarShape::Rect, &pt),
+            CommandId::ToolTextBox => self.start_create(ShapeBarShape::TextBox, &pt),
+            CommandId::ToolLine => self.start_create(ShapeBarShape::Line, &pt),
+            CommandId::ToolColorPicker => self.toggle_color_picker(),
+            CommandId::SaveStyles => {
+                if let Err(e) = self.styles.save(StyleSheet::PATH) {
+                    eprintln!("{}", e);
+                }
+            }
+            CommandId::UpdateStyle => self.update_style_from_selection(),
+            CommandId::TextBold => self.toggle_text_attr(TextAttr::Bold),
+            CommandId::TextItalic => self.toggle_text_attr(TextAttr::Italic),
+            CommandId::TextUnderline => self.toggle_text_attr(TextAttr::Underline),
+            CommandId::TextStrikethrough => self.toggle_text_attr(TextAttr::Strikethrough),
+            CommandId::TextGrow => self.update_text_attrs(|attrs| {
+                attrs.size = f32::min(attrs.size * TEXT_SIZE_STEP, TextAttrs::MAX_SIZE);
+            }),
+            CommandId::TextShrink => self.update_text_attrs(|attrs| {
+                attrs.size = f32::max(attrs.size / TEXT_SIZE_STEP, TextAttrs::MIN_SIZE);
+            }),
+            CommandId::ToggleRoute => {
+                self.update_line(|line| line.route = line.route.next());
+                //the lines keep their own route from now on, like a route set in the inspector
+                for id in self.line_select.keys() {
+                    self.shape_styles.remove(id);
+                }
+            }
+            CommandId::PinWaypoint => {
+                let ids: Vec<ShapeID> = self.line_select.keys().cloned().collect();
+                ids.into_iter().for_each(|id| self.pin_waypoint(id, pt));
+            }
+            CommandId::ClearWaypoints => self.update_line(|line| line.waypoints.clear()),
+            CommandId::ToggleSelectMode => self.select_mode = self.select_mode.next(),
+            CommandId::ToggleLayers => {
+                self.layer_panel.open = !self.layer_panel.open;
+                if let KeyboardMode::LayerRename(_) = self.key_mode {
+                    self.set_key_mode(KeyboardMode::KeyboardNone);
+                }
+            }
+            CommandId::NewLayer => {
+                let name = format!("Layer {}", self.draw_list.layers.len() + 1);
+                self.draw_list.add_layer(&name);
+            }
+            CommandId::DeleteLayer => {
+                let layer = self.draw_list.active;
+                for id in self.draw_list.remove_layer(layer) {
+                    self.forget_shape(&id);
+                }
+            }
+            CommandId::MoveToLayer => {
+                let ids = self.selected_ids().into_iter().collect();
+                let layer = self.draw_list.active;
+                self.draw_list.move_to_layer(&ids, layer);
+            }
+            CommandId::SaveDocument => {
+                if let Err(e) = self.save_document(Document::PATH) {
+                    eprintln!("{}", e);
+                }
+            }
+            CommandId::OpenDocument => self.open_document(Document::PATH),
+            CommandId::ExportPdf => {
+                if let Err(e) = self.export_pdf(PdfDocument::PATH) {
+                    eprintln!("Error exporting PDF: {}", e);
+                }
+            }
+            CommandId::ExportSvg => {
+                if let Err(e) = self.export_svg(SvgPage::path) {
+                    eprintln!("Error exporting SVG: {}", e);
+                }
+            }
+            CommandId::NewPage => {
+                let name = format!("Page {}", self.pages.len() + 1);
+                self.insert_page(Page::new(self.next_page_id, &name));
+            }
+            CommandId::DuplicatePage => {
+                let current = self.page;
+                let mut page = self.with_pages(|app| app.pages[current].clone());
+                page.id = self.next_page_id;
+                page.name = format!("{} copy", page.name);
+                self.insert_page(page);
+            }
+            CommandId::DeletePage => self.delete_page(self.page),
+            CommandId::RenamePage => {
+                self.page_tabs.begin_rename(&self.pages[self.page].name);
+                self.set_key_mode(KeyboardMode::PageRename(self.page));
+            }
+            CommandId::NextPage => self.show_page((self.page + 1) % self.pages.len()),
+            CommandId::PrevPage => self.show_page((self.page + self.pages.len() - 1) % self.pages.len()),
+            CommandId::MovePageLeft => self.move_page(false),
+            CommandId::MovePageRight => self.move_page(true),
+            CommandId::FollowLink => {
+                if let Some(id) = self.selected_ids().into_iter().find(|id| self.links.contains_key(id)) {
+                    self.follow_link(id);
+                }
+            }
+            CommandId::ResetView => self.camera = Point::origin(),
+            CommandId::ToggleMinimap => {
+                self.minimap.open = !self.minimap.open;
+                if self.hover_item == HoverItem::HoverMinimap {
+                    self.hover_item = HoverItem::HoverNone;
+                }
+            }
+            CommandId::CommandPalette => {
+                self.palette.open();
+                self.set_key_mode(KeyboardMode::Palette);
+            }
+            CommandId::Quit => self.quit = true,
+        }
+    }
+    //clipboard and undo commands only act on the text box being edited
+    fn edit_text<F: FnOnce(&mut TextBox)>(&mut self, f: F) {
+        if let Some(id) = self.editing_text() {
+            self.text_boxes.get_mut(&id).map(f);
+        }
+    }
+    fn copy_text(&mut self, cut: bool) {
+        let text = self.editing_text().and_then(|id| self.text_boxes[&id].selected_text());
+        if let Some(text) = text {
+            if let Err(e) = self.clipboard.set_clipboard_text(&text) {
+                return eprintln!("Could not write clipboard: {}", e);
+            }
+            if cut {
+                self.edit_text(|tb| tb.delete_char());
+            }
+        }
+    }
+    fn start_create(&mut self, item_id: ShapeBarShape, pt: &Point) {
+        let size = item_id.default_size();
+        let r = Rect::new(*pt - size / 2., *pt + size / 2.);
+        let s = self.shape_bar.get_shape(item_id, &r, false, &self.styles);
+        self.hover_item = HoverItem::HoverShape(item_id, s);
+        self.cursors.get(&SystemCursor::Crosshair).set();
+    }
+    fn toggle_color_picker(&mut self) {
+        self.color_picker.open = !self.color_picker.open;
+        let color = self.current_pick_color();
+        self.color_picker.set_color(&color);
+    }
+    fn select_shape(&mut self, id: ShapeID) {
+        match self.draw_list.get(&id) {
+            Some(Shape::Polygon(ref draw_poly)) => {
+                self.selection.insert(id, self.get_shape_select_box(draw_poly));
+            }
+            Some(Shape::Line(ref draw_line)) => {
+                self.line_select.insert(id, SelectLine(draw_line.clone()));
+            }
+            None => {}
+        };
+    }
+    fn drag_selection(&mut self, off: &Point) {
+        for (id, rect) in self.selection.iter_mut() {
+            if let Some(s) = self.draw_list.get_mut(id) {
+                s.drag(off);
+            }
+            rect.drag(off);
+        }
+        let selection = &self.selection;
+        for (id, line) in self.line_select.iter_mut() {
+            if let Some(s) = self.draw_list.get_mut(id) {
+                s.drag(off);
+            }
+            line.drag(off);
+            //ends attached to shapes that aren't moving with the line come loose
+            if let Some(ends) = self.line_ends.get_mut(id) {
+                for end in ends.iter_mut() {
+                    if end.is_some_and(|e| !selection.contains_key(&e.shape)) {
+                        *end = None;
+                    }
+                }
+            }
         }
     }
     fn delete_selection(&mut self) {
-        for id in self.selection.keys() {
-            self.draw_list.remove(id); 
-            self.text_boxes.remove(id);
+        for id in self.selected_ids() {
+            self.draw_list.remove(&id); 
+            self.forget_shape(&id);
         }
-        for id in self.line_select.keys() {
-            self.draw_list.remove(id); 
+    }
+    //drops everything kept about a shape that's been taken out of the draw list
+    fn forget_shape(&mut self, id: &ShapeID) {
+        if self.editing_text() == Some(*id) {
+            self.set_key_mode(KeyboardMode::KeyboardNone);
         }
-        self.line_select.clear();
-        self.selection.clear();
+        self.selection.remove(id);
+        self.line_select.remove(id);
+        self.text_boxes.remove(id);
+        self.shape_styles.remove(id);
+        self.line_ends.remove(id);
+        self.routes.remove(id);
+        self.links.remove(id);
+    }
+    fn click_layers(&mut self, hover: LayerHover, clicks: u8) {
+        let active = self.draw_list.active;
+        match hover {
+            LayerHover::Add => self.run_command(CommandId::NewLayer),
+            LayerHover::Delete => self.run_command(CommandId::DeleteLayer),
+            LayerHover::Raise => self.draw_list.move_layer(active, true),
+            LayerHover::Lower => self.draw_list.move_layer(active, false),
+            LayerHover::Row(layer, LayerPart::Visible) => {
+                if let Some(l) = self.draw_list.layer_mut(layer) {
+                    l.visible = !l.visible;
+                }
+                self.deselect_locked();
+            }
+            LayerHover::Row(layer, LayerPart::Lock) => {
+                if let Some(l) = self.draw_list.layer_mut(layer) {
+                    l.locked = !l.locked;
+                }
+                self.deselect_locked();
+            }
+            LayerHover::Row(layer, LayerPart::Name) if clicks == 2 => {
+                if let Some(l) = self.draw_list.layer_mut(layer) {
+                    self.layer_panel.begin_rename(l);
+                    self.set_key_mode(KeyboardMode::LayerRename(layer));
+                }
+            }
+            LayerHover::Row(layer, LayerPart::Name) | LayerHover::Row(layer, LayerPart::Opacity) => {
+                self.draw_list.active = usize::min(layer, self.draw_list.layers.len() - 1);
+            }
+            LayerHover::Row(layer, LayerPart::MoveHere) => {
+                let ids = self.selected_ids().into_iter().collect();
+                self.draw_list.move_to_layer(&ids, layer);
+            }
+            LayerHover::Panel => {}
+        }
+        //the panel changes size as layers come and go
+        self.hover_item = HoverItem::HoverNone;
+    }
+    //shapes on hidden or locked layers can't stay selected or be edited
+    fn deselect_locked(&mut self) {
+        let locked: Vec<ShapeID> = self.selected_ids().into_iter().filter(|id| !self.draw_list.is_interactive(id)).collect();
+        for id in locked {
+            self.selection.remove(&id);
+            self.line_select.remove(&id);
+        }
+        if self.editing_text().is_some_and(|id| !self.draw_list.is_interactive(&id)) {
+            self.set_key_mode(KeyboardMode::KeyboardNone);
+        }
+    }
+    fn save_document(&mut self, path: &str) -> Result<(), String> {
+        let out = self.with_pages(|app| {
+            let pages = &app.pages;
+            let mut out = String::new();
+            for page in pages {
+                document::write_page(&mut out, &page.name, &page.camera);
+                for l in page.draw_list.layers.iter() {
+                    document::write_layer(&mut out, &l.layer);
+                    for id in l.order.iter() {
+                        let style = page.shape_styles.get(id).map(|s| s.as_str());
+                        //links are saved as the index of the page in the file
+                        let link = page.links.get(id).and_then(|link| pages.iter().position(|p| p.id == *link));
+                        document::write_shape(&mut out, &page.draw_list.m[id], style, page.text_boxes.get(id), link);
+                    }
+                }
+            }
+            out
+        });
+        std::fs::write(path, out).map_err(|e| format!("Could not write {}: {}", path, e))
+    }
+    //every page with the shapes and text of its visible layers, the page fits its shapes
+    fn export_pdf(&mut self, path: &str) -> Result<(), String> {
+        let vp = self.draw_ctx.viewport;
+        let doc: Result<PdfDocument, String> = self.with_pages(|app| {
+            let rt = &app.render_text;
+            let mut doc = PdfDocument::new();
+            let mut faces: HashMap<usize, PdfFace> = HashMap::new();
+            for page in app.pages.iter() {
+                let layers: Vec<&LayerShapes> = page.draw_list.layers.iter().filter(|l| l.layer.visible).collect();
+                let text_rect = |id: &ShapeID| shape_text_rect(&page.draw_list.m[id], page.text_boxes.get(id), rt);
+                let mut out = PdfPage::new(&export_bounds(page, &layers, rt, &vp));
+                for l in layers.iter() {
+                    let opacity = l.layer.opacity;
+                    for id in l.order.iter() {
+                        pdf::draw_shape(&mut out, &page.draw_list.m[id], opacity, &vp);
+                    }
+                    for (id, tb) in l.order.iter().filter_map(|id| page.text_boxes.get(id).map(|tb| (id, tb))) {
+                        let rect = text_rect(id);
+                        if let Shape::Line(_) = page.draw_list.m[id] {
+                            out.fill_color(&rgb_to_f32(255, 255, 255), opacity);
+                            out.polygon(&rect.verts(&vp));
+                            out.fill();
+                        }
+                        let text = tb.place_glyphs(&rect, rt, &vp);
+                        for g in text.glyphs.iter() {
+                            if let Entry::Vacant(entry) = faces.entry(g.font) {
+                                let face = rt.fonts().face(g.font);
+                                let font = doc.add_font(face.data, &face.family, face.slant)?;
+                                entry.insert(PdfFace { font, slant: face.slant, bold: face.embolden });
+                            }
+                        }
+                        pdf::draw_text(&mut out, &text, &faces, &rect, opacity, &vp);
+                    }
+                }
+                doc.add_page(out);
+            }
+            Ok(doc)
+        });
+        doc?.save(path)
+    }
+    //each page to its own file, named by `path` from the page's index
+    fn export_svg(&mut self, path: fn(usize) -> String) -> Result<(), String> {
+        let vp = self.draw_ctx.viewport;
+        let pages: Vec<SvgPage> = self.with_pages(|app| {
+            let rt = &app.render_text;
+            let mut faces: HashMap<usize, SvgFace> = HashMap::new();
+            app.pages.iter().map(|page| {
+                let layers: Vec<&LayerShapes> = page.draw_list.layers.iter().filter(|l| l.layer.visible).collect();
+                let text_rect = |id: &ShapeID| shape_text_rect(&page.draw_list.m[id], page.text_boxes.get(id), rt);
+                let mut out = SvgPage::new(&export_bounds(page, &layers, rt, &vp));
+                for l in layers.iter() {
+                    let opacity = l.layer.opacity;
+                    for id in l.order.iter() {
+                        svg::draw_shape(&mut out, &page.draw_list.m[id], opacity, &vp);
+                    }
+                    for (id, tb) in l.order.iter().filter_map(|id| page.text_boxes.get(id).map(|tb| (id, tb))) {
+                        let rect = text_rect(id);
+                        if let Shape::Line(_) = page.draw_list.m[id] {
+                            out.polygon(&rect.verts(&vp));
+                            out.fill(&rgb_to_f32(255, 255, 255), opacity);
+                        }
+                        let text = tb.place_glyphs(&rect, rt, &vp);
+                        for g in text.glyphs.iter() {
+                            faces.entry(g.font).or_insert_with(|| {
+                                let face = rt.fonts().face(g.font);
+                                SvgFace { family: face.family.clone(), bold: face.style.is_bold(), italic: face.style.is_italic() }
+                            });
+                        }
+                        svg::draw_text(&mut out, &text, &faces, &rect, opacity, &vp);
+                    }
+                }
+                out
+            }).collect()
+        });
+        for (i, page) in pages.iter().enumerate() {
+            page.save(&path(i))?;
+        }
+        Ok(())
+    }
+    //replaces the diagram with the one saved at `path`, it's left alone if the file can't be read
+    fn open_document(&mut self, path: &str) {
+        let doc = match Document::load(path) {
+            Ok(doc) => doc,
+            Err(e) => return eprintln!("Error opening diagram: {}", e)
+        };
+        self.clear_selection();
+        self.hover_item = HoverItem::HoverNone;
+        self.drag_mode = DragMode::DragNone;
+        self.draw_list = DrawList::new();
+        self.text_boxes.clear();
+        self.shape_styles.clear();
+        self.line_ends.clear();
+        self.routes.clear();
+        self.links.clear();
+        //page ids follow the order in the file, which is what links refer to
+        let mut pages = Vec::new();
+        for (n, doc_page) in doc.pages.into_iter().enumerate() {
+            let mut page = Page::new(n as PageID, &doc_page.name);
+            page.camera = doc_page.camera;
+            page.draw_list.layers.clear();
+            for (i, doc_layer) in doc_page.layers.into_iter().enumerate() {
+                page.draw_list.layers.push(LayerShapes { layer: doc_layer.layer, order: Vec::new() });
+                for doc_shape in doc_layer.shapes {
+                    let id = page.draw_list.add_to_layer(doc_shape.shape, i);
+                    if let Some(style) = doc_shape.style {
+                        page.shape_styles.insert(id, style);
+                    }
+                    if let Some(tb) = doc_shape.text {
+                        page.text_boxes.insert(id, tb);
+                    }
+                    if let Some(link) = doc_shape.link {
+                        page.links.insert(id, link as PageID);
+                    }
+                }
+            }
+            page.draw_list.active = page.draw_list.layers.len() - 1;
+            pages.push(page);
+        }
+        self.next_page_id = pages.len() as PageID;
+        self.pages = pages;
+        self.page = 0;
+        self.swap_page();
+        //lines are attached on each page in turn, ending on the first
+        for i in (0..self.pages.len()).rev() {
+            self.show_page(i);
+            for id in self.draw_list.ids() {
+                self.attach_line(id);
+            }
+        }
+    }
+    //the shown page's contents live in the app state, the page's slot holds them while another page is shown
+    fn swap_page(&mut self) {
+        let page = &mut self.pages[self.page];
+        std::mem::swap(&mut self.draw_list, &mut page.draw_list);
+        std::mem::swap(&mut self.text_boxes, &mut page.text_boxes);
+        std::mem::swap(&mut self.shape_styles, &mut page.shape_styles);
+        std::mem::swap(&mut self.line_ends, &mut page.line_ends);
+        std::mem::swap(&mut self.routes, &mut page.routes);
+        std::mem::swap(&mut self.links, &mut page.links);
+        std::mem::swap(&mut self.camera, &mut page.camera);
+    }
+    fn show_page(&mut self, i: usize) {
+        if i >= self.pages.len() {
+            return;
+        }
+        self.clear_selection();
+        self.hover_item = HoverItem::HoverNone;
+        self.drag_mode = DragMode::DragNone;
+        self.swap_page();
+        self.page = i;
+        self.swap_page();
+    }
+    //runs `f` with every page holding its contents, the shown one included
+    fn with_pages<T, F: FnOnce(&Self) -> T>(&mut self, f: F) -> T {
+        self.swap_page();
+        let result = f(self);
+        self.swap_page();
+        result
+    }
+    //new pages go after the shown one and are shown
+    fn insert_page(&mut self, page: Page) {
+        self.next_page_id += 1;
+        self.pages.insert(self.page + 1, page);
+        self.show_page(self.page + 1);
+    }
+    //the last page can't be deleted. links to a deleted page are dropped
+    fn delete_page(&mut self, i: usize) {
+        if self.pages.len() < 2 || i >= self.pages.len() {
+            return;
+        }
+        if i == self.page {
+            self.show_page(if i == 0 { 1 } else { i - 1 });
+        }
+        let id = self.pages.remove(i).id;
+        if self.page > i {
+            self.page -= 1;
+        }
+        self.links.retain(|_, link| *link != id);
+        for page in self.pages.iter_mut() {
+            page.links.retain(|_, link| *link != id);
+        }
+    }
+    fn move_page(&mut self, right: bool) {
+        let to = if right { self.page + 1 } else { self.page.wrapping_sub(1) };
+        if to < self.pages.len() {
+            self.pages.swap(self.page, to);
+            self.page = to;
+        }
+    }
+    //the view is centered on the world point under the minimap point
+    fn center_on_minimap(&mut self, screen_pt: &Point) {
+        if let Some(pt) = self.minimap.to_world(screen_pt) {
+            self.camera = pt - self.draw_ctx.viewport / 2.;
+        }
+    }
+    fn follow_link(&mut self, id: ShapeID) {
+        let pages = &self.pages;
+        if let Some(i) = self.links.get(&id).and_then(|link| pages.iter().position(|p| p.id == *link)) {
+            self.show_page(i);
+        }
+    }
+    //links the selection to the page after the one it links to, skipping the shown page, then to none
+    fn cycle_links(&mut self) {
+        let ids = self.selected_ids();
+        let pages = &self.pages;
+        let current = ids.first().and_then(|id| self.links.get(id)).and_then(|link| pages.iter().position(|p| p.id == *link));
+        let shown = self.page;
+        let next = pages.iter().enumerate().skip(current.map_or(0, |i| i + 1))
+            .find(|(i, _)| *i != shown).map(|(_, p)| p.id);
+        for id in ids {
+            match next {
+                Some(page) => self.links.insert(id, page),
+                None => self.links.remove(&id)
+            };
+        }
+    }
+    fn click_tabs(&mut self, hover: TabHover, clicks: u8) {
+        match hover {
+            TabHover::Tab(i) if clicks == 2 => {
+                self.show_page(i);
+                self.run_command(CommandId::RenamePage);
+            }
+            TabHover::Tab(i) => self.show_page(i),
+            TabHover::Add => self.run_command(CommandId::NewPage),
+            TabHover::Strip => {}
+        }
+        self.hover_item = HoverItem::HoverNone;
+    }
+    fn to_world(&self, pt: &Point) -> Point {
+        *pt + self.camera
     }
     fn draw_hover_item(&self) {
         match self.hover_item {
@@ -532,12 +1961,20 @@ impl AppState {
     }
     fn draw_drag_item(&self) {
         match self.drag_mode {
-            DragMode::SelectBox{start_pt, last_pt} => {
+            DragMode::SelectBox{start_pt, last_pt, ..} => {
                 Rect::new(start_pt, last_pt).builder().color(0,0,0).fill(false).get().draw(&self.draw_ctx);
             }
+            DragMode::Lasso { .. } if !self.lasso.is_empty() => {
+                //closed back to where it started, the way it selects
+                let line = DrawLine {
+                    p1: self.lasso[0], p2: self.lasso[0], waypoints: self.lasso[1..].to_vec(),
+                    line_width: 1., color: rgb_to_f32(0, 0, 0), ..DrawLine::default()
+                };
+                line.draw(&self.draw_ctx);
+            }
             DragMode::CreateShape{shape_id, start_pt, last_pt} => {
                 let r = Rect::new(start_pt, last_pt);
-                self.shape_bar.get_shape(shape_id, &r, false).draw(&self.draw_ctx);
+                self.shape_bar.get_shape(shape_id, &r, false, &self.styles).draw(&self.draw_ctx);
             }
             _ => {}
         }
@@ -550,35 +1987,253 @@ impl AppState {
             l.draw(&self.draw_ctx);
         }
     }
-    fn draw_text_boxes(&self) {
-        for (id, tb) in self.text_boxes.iter() {
-            let select_time = match self.key_mode {
-                KeyboardMode::TextEdit(edit_id, select_time) => {
-                    if edit_id == *id { Some(select_time) } else { None }
+    //keeps the shapes that depend on other shapes up to date after every batch of events
+    pub fn update(&mut self) {
+        //shape kinds registered since the shape bar was laid out get a spot on it
+        if self.shape_bar.nkinds != shape_kind::count() {
+            self.shape_bar = ShapeBar::new(&self.draw_ctx.viewport, &self.styles);
+            self.color_picker.set_anchor(&self.shape_bar.picker_anchor());
+        }
+        self.update_lines();
+        self.update_text_boxes();
+    }
+    //topmost shape other than a line under a point
+    fn shape_at(&self, pt: &Point) -> Option<ShapeID> {
+        let vp = &self.draw_ctx.viewport;
+        self.draw_list.ids().into_iter().rev().find(|id| match self.draw_list.get(id) {
+            Some(s @ Shape::Polygon(_)) => self.draw_list.is_visible(id) && s.in_bounds(pt, vp),
+            _ => false
+        })
+    }
+    //attaches each end of a line to the shape it was dropped on, or detaches it
+    fn attach_line(&mut self, id: ShapeID) {
+        let (p1, p2) = match self.draw_list.get(&id) {
+            Some(Shape::Line(line)) => (line.p1, line.p2),
+            _ => return
+        };
+        let vp = self.draw_ctx.viewport;
+        let attach = |pt: Point| self.shape_at(&pt).map(|shape| {
+            let model = self.draw_list.get(&shape).unwrap().rect().transform(&vp).pixel_to_model(&pt);
+            LineEnd { shape, model: Point::new(model.x, model.y) }
+        });
+        let ends = [attach(p1), attach(p2)];
+        self.line_ends.insert(id, ends);
+    }
+    fn end_point(&self, end: &LineEnd) -> Option<Point> {
+        match self.draw_list.get(&end.shape) {
+            Some(s @ Shape::Polygon(_)) => Some(s.rect().transform(&self.draw_ctx.viewport).model_to_pixel(&end.model.to_vec4())),
+            _ => None
+        }
+    }
+    //moves attached line ends along with their shapes, then routes orthogonal lines again
+    //if their ends, their waypoints or the shapes around them moved
+    fn update_lines(&mut self) {
+        let vp = self.draw_ctx.viewport;
+        let nodes: Vec<(ShapeID, Rect)> = self.draw_list.ids().into_iter().filter_map(|id| match self.draw_list.get(&id) {
+            Some(s @ Shape::Polygon(_)) if self.draw_list.is_visible(&id) => Some((id, Rect::bounding_box(&s.rect().verts(&vp)))),
+            _ => None
+        }).collect();
+        for id in self.draw_list.ids() {
+            let mut line = match self.draw_list.get(&id) {
+                Some(Shape::Line(line)) => line.clone(),
+                _ => continue
+            };
+            let ends = self.line_ends.get(&id).cloned().unwrap_or([None, None]);
+            if let Some(p) = ends[0].and_then(|e| self.end_point(&e)) {
+                line.p1 = p;
+            }
+            if let Some(p) = ends[1].and_then(|e| self.end_point(&e)) {
+                line.p2 = p;
+            }
+            match line.route {
+                LineRoute::Orthogonal => {
+                    //the shapes the line is attached to are where it starts and ends, not in its way
+                    let obstacles: Vec<Rect> = nodes.iter()
+                        .filter(|(n, _)| !ends.iter().any(|e| e.is_some_and(|e| e.shape == *n)))
+                        .map(|(_, r)| r.clone()).collect();
+                    let mut query = RouteQuery::new(line.p1, line.p2, &line.waypoints, &obstacles, &line.bends);
+                    if self.routes.get(&id) != Some(&query) {
+                        line.bends = query.route(&obstacles);
+                        self.routes.insert(id, query);
+                    }
                 }
-                _ => None
+                LineRoute::Straight => {
+                    line.bends.clear();
+                    self.routes.remove(&id);
+                }
+            }
+            //this runs every frame, the draw list is only touched when the line has moved
+            let moved = match self.draw_list.get(&id) {
+                Some(Shape::Line(old)) => *old != line,
+                _ => false
             };
-            let rect = self.draw_list.get(&id).unwrap().rect();
-            tb.draw(&rect, select_time, &self.render_text, &self.draw_ctx);
+            if moved {
+                if let Some(s) = self.draw_list.get_mut(&id) {
+                    *s = Shape::Line(line.clone());
+                }
+                if let Some(sline) = self.line_select.get_mut(&id) {
+                    sline.0 = line;
+                }
+            }
+        }
+    }
+    //the waypoint goes between the two points of the line it's closest to
+    fn pin_waypoint(&mut self, id: ShapeID, pt: Point) {
+        if let Some(Shape::Line(line)) = self.draw_list.get_mut(&id) {
+            let pts: Vec<Point> = std::iter::once(line.p1).chain(line.waypoints.iter().cloned())
+                .chain(std::iter::once(line.p2)).collect();
+            let idx = (0..pts.len() - 1).min_by(|a, b| {
+                geometry::dist_to_segment(&pt, &pts[*a], &pts[*a + 1])
+                    .partial_cmp(&geometry::dist_to_segment(&pt, &pts[*b], &pts[*b + 1])).unwrap()
+            }).unwrap_or(0);
+            line.waypoints.insert(idx, pt);
+            let line = line.clone();
+            if let Some(sline) = self.line_select.get_mut(&id) {
+                sline.0 = line;
+            }
         }
     }
+    fn update_line<F: Fn(&mut DrawLine)>(&mut self, f: F) {
+        for (id, sline) in self.line_select.iter_mut() {
+            if let Some(Shape::Line(line)) = self.draw_list.get_mut(id) {
+                f(line);
+                sline.0 = line.clone();
+            }
+        }
+    }
+    //shapes that grow with their text are resized to it after every event, keeping their top edge in place.
+    //the box being edited is then scrolled to its cursor
+    fn update_text_boxes(&mut self) {
+        let vp = &self.draw_ctx.viewport;
+        let editing = self.editing_text();
+        let rects: HashMap<ShapeID, RotateRect> = self.text_boxes.keys().map(|id| (*id, self.text_rect(id))).collect();
+        for (id, tb) in self.text_boxes.iter_mut() {
+            if self.draw_list.get(id).is_none() {
+                continue;
+            }
+            let mut rect = rects[id].clone();
+            match tb.fit_height(&rect, &self.render_text) {
+                Some(height) if height != rect.size.y => {
+                    let height = f32::max(height, 1.);
+                    rect.resize(&Rect::new(Point::origin(), Point::new(1., height / rect.size.y)), vp);
+                    if let Some(s) = self.draw_list.get_mut(id) {
+                        s.set_rect(&rect);
+                    }
+                    if let Some(sbox) = self.selection.get_mut(id) {
+                        sbox.0 = rect.clone();
+                    }
+                }
+                _ => {}
+            }
+            if editing == Some(*id) {
+                tb.scroll_to_cursor(&rect, &self.render_text);
+            }
+        }
+    }
+    fn draw_text_box(&self, id: &ShapeID, tb: &TextBox) {
+        let select_time = match self.key_mode {
+            KeyboardMode::TextEdit(edit_id, select_time) => {
+                if edit_id == *id { Some(select_time) } else { None }
+            }
+            _ => None
+        };
+        let rect = self.text_rect(id);
+        //labels are drawn on a backing that covers the line under them
+        if let Some(Shape::Line(_)) = self.draw_list.get(id) {
+            rect.builder().color(255, 255, 255).get().draw(&self.draw_ctx);
+        }
+        tb.draw(&rect, select_time, &self.render_text, &self.draw_ctx);
+    }
+    //each visible layer's shapes then the text on them, so a layer covers the text of the ones under it
+    fn draw_layers(&self) {
+        for l in self.draw_list.layers.iter().filter(|l| l.layer.visible) {
+            self.draw_ctx.opacity.set(l.layer.opacity);
+            for id in l.order.iter() {
+                self.draw_list.m[id].draw(&self.draw_ctx);
+            }
+            for id in l.order.iter() {
+                if let Some(tb) = self.text_boxes.get(id) {
+                    self.draw_text_box(id, tb);
+                }
+            }
+            //shapes linking to another page get a dot in their top right corner
+            for id in l.order.iter().filter(|id| self.links.contains_key(id)) {
+                let r = Rect::bounding_box(&self.draw_list.m[id].rect().verts(&self.draw_ctx.viewport));
+                let radi = 8.;
+                ShapeBuilder::new().color(40, 110, 230).circle(radi as u32)
+                    .offset((r.c2.x - radi / 2.) as i32, (r.c1.y - radi / 2.) as i32).get().draw(&self.draw_ctx);
+            }
+        }
+        self.draw_ctx.opacity.set(1.);
+    }
+    fn draw_inspector(&self) {
+        let props = self.inspector_props();
+        if props.is_empty() {
+            return;
+        }
+        let editing = match self.key_mode {
+            KeyboardMode::InspectorEdit(prop) => Some(prop),
+            _ => None
+        };
+        self.inspector.draw(&props, editing, &self.render_text, &self.draw_ctx);
+    }
     pub fn render(&self) {
-        self.shape_bar.draw(&self.draw_ctx);
-        self.draw_list.draw(&self.draw_ctx);
-        self.draw_text_boxes();
+        self.draw_ctx.set_camera(&self.camera);
+        self.draw_layers();
         self.draw_hover_item();
         self.draw_drag_item();
         self.draw_shape_select_boxes();
+        self.draw_ctx.set_camera(&Point::origin());
+        self.shape_bar.draw(&self.draw_ctx);
+        self.draw_inspector();
+        if self.layer_panel.open {
+            let renaming = match self.key_mode { KeyboardMode::LayerRename(layer) => Some(layer), _ => None };
+            self.layer_panel.draw(&self.draw_list.layers(), self.draw_list.active, renaming, &self.render_text, &self.draw_ctx);
+        }
+        if self.minimap.open {
+            self.minimap.draw(self.draw_list.generation(), || self.draw_list.visible_shapes(), &self.camera, &self.draw_ctx);
+        }
+        let renaming = match self.key_mode { KeyboardMode::PageRename(page) => Some(page), _ => None };
+        let names: Vec<&str> = self.pages.iter().map(|p| p.name.as_str()).collect();
+        self.page_tabs.draw(&names, self.page, renaming, &self.render_text, &self.draw_ctx);
+        let editing_hex = matches!(self.key_mode, KeyboardMode::PickerHexEdit);
+        self.color_picker.draw(editing_hex, &self.render_text, &self.draw_ctx);
+        if let KeyboardMode::Palette = self.key_mode {
+            self.palette.draw(&self.keymap, &self.render_text, &self.draw_ctx);
+        }
+    }
+}
+
+//rect a shape's text box is laid out in, a line's label is sized to its text
+fn shape_text_rect(shape: &Shape, tb: Option<&TextBox>, rt: &RenderText) -> RotateRect {
+    match (shape, tb) {
+        (Shape::Line(line), Some(tb)) => line.label_rect(&tb.natural_size(rt)),
+        (s, _) => s.rect()
     }
 }
 
+//the shapes and text of the layers being exported, an empty page is the view it was left at
+fn export_bounds(page: &Page, layers: &[&LayerShapes], rt: &RenderText, vp: &Point) -> Rect {
+    let mut pts: Vec<Point> = layers.iter().flat_map(|l| l.order.iter()).flat_map(|id| {
+        let shape = &page.draw_list.m[id];
+        let mut pts = shape.verts(vp);
+        pts.extend(page.text_boxes.get(id).map_or(Vec::new(), |tb| shape_text_rect(shape, Some(tb), rt).verts(vp)));
+        pts
+    }).collect();
+    if pts.is_empty() {
+        pts = vec![page.camera, page.camera + *vp];
+    }
+    Rect::bounding_box(&pts)
+}
+
 #[derive(Clone)]
 struct SelectLine(DrawLine);
 
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum LineVertex {
-    P1, P2
+    P1, P2,
+    Waypoint(usize),
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -590,14 +2245,16 @@ pub enum LineHover {
 impl SelectLine {
     const MIN_VERT_DIST: f32 = 20.;
     fn new(p1: Point, p2: Point) -> Self {
-        SelectLine(DrawLine { p1, p2, line_width: 3., color: Point::origin().to_vec4() })
+        SelectLine(DrawLine { p1, p2, line_width: 3., color: Point::origin().to_vec4(), ..DrawLine::default() })
     }
     fn drag(&mut self, off: &Point) {
-        self.0.p1 += *off;
-        self.0.p2 += *off;
+        self.0.drag(off);
     }
     fn get_hover(&self, pt: &Point, vp: &Point) -> Option<LineHover> {
-        if pt.dist(&self.0.p1) <= SelectLine::MIN_VERT_DIST {
+        if let Some(i) = self.0.waypoints.iter().position(|w| pt.dist(w) <= SelectLine::MIN_VERT_DIST) {
+            Some(LineHover::Vertex(LineVertex::Waypoint(i)))
+        }
+        else if pt.dist(&self.0.p1) <= SelectLine::MIN_VERT_DIST {
             Some(LineHover::Vertex(LineVertex::P1))
         }
         else if pt.dist(&self.0.p2) <= SelectLine::MIN_VERT_DIST {
@@ -617,6 +2274,11 @@ impl SelectLine {
             .map(|v| ShapeBuilder::new().color(255,255,255).circle(radi as u32)
                 .offset((v.x - radi/2.) as i32, (v.y - radi/2.) as i32).get())
             .for_each(|s| s.draw(draw_ctx));
+        //pinned waypoints are drawn as squares
+        for w in self.0.waypoints.iter() {
+            ShapeBuilder::new().color(255, 255, 255).square(radi as u32)
+                .offset((w.x - radi/2.) as i32, (w.y - radi/2.) as i32).get().draw(draw_ctx);
+        }
     }
     fn draw(&self, draw_ctx: &DrawCtx) {
         self.draw_verts(draw_ctx);
@@ -817,12 +2479,13 @@ impl ShapeSelectBox {
         self.draw_rotate_circles(draw_ctx);
     }
     fn get_hover(&self, p: &Point, vp: &Point) -> Option<BoxHover> {
-        if self.0.in_bounds(p,vp) {
-            Some(BoxHover::Rect)
-        }
-        else if let Some(v) = self.get_drag_vertex(p, vp) {
+        //the handles sit on the corners, within the hit tolerance of the box
+        if let Some(v) = self.get_drag_vertex(p, vp) {
             Some(BoxHover::Drag(v))
         }
+        else if self.0.in_bounds(p,vp) {
+            Some(BoxHover::Rect)
+        }
         else if self.is_hover_rotate(p, vp) {
             Some(BoxHover::RotateVert)
         }
@@ -838,16 +2501,24 @@ impl InBounds for ShapeSelectBox {
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum ShapeBarShape {
-    Circle = 0,
-    Triangle = 1,
-    Rect = 2,
-    TextBox = 3,
-    Line = 4,
-    ColorPicker = 5,
+    Circle,
+    Triangle,
+    Rect,
+    TextBox,
+    Line,
+    RoundRect,
+    Diamond,
+    Hexagon,
+    Parallelogram,
+    Cylinder,
+    Star,
+    Custom(ShapeKindId),
+    ColorPicker,
 }
 
 pub enum ShapeBarItem {
     Shape(Shape),
+    Icon(DrawLine),
     ColorPicker(HexColor)
 }
 
@@ -855,6 +2526,7 @@ impl ShapeBarItem {
     fn draw(&self, draw_ctx: &DrawCtx) {
         match self {
             ShapeBarItem::Shape(shape) => shape.draw(draw_ctx),
+            ShapeBarItem::Icon(line) => line.draw(draw_ctx),
             ShapeBarItem::ColorPicker(hexcolor) => hexcolor.draw(draw_ctx)
         };
     }
@@ -862,40 +2534,71 @@ impl ShapeBarItem {
 
 impl ShapeBarShape {
     const DEFAULT_SIZE: f32 = 30.;
-    const DEFAULT_COLOR: (u8, u8, u8) = (255, 0, 0);
+    fn style_name(&self) -> &'static str {
+        match self {
+            ShapeBarShape::TextBox => StyleSheet::TEXT,
+            ShapeBarShape::Line => StyleSheet::LINE,
+            _ => StyleSheet::DEFAULT
+        }
+    }
     fn prim_type(&self) -> PrimType {
         match self {
             ShapeBarShape::Circle => PrimType::Circle,
             ShapeBarShape::Triangle => PrimType::Triangle,
             ShapeBarShape::Rect | ShapeBarShape::TextBox => PrimType::Rect,
             ShapeBarShape::Line => PrimType::Line,
+            ShapeBarShape::RoundRect => PrimType::RoundRect(PrimType::DEFAULT_RADIUS),
+            ShapeBarShape::Diamond => PrimType::Diamond,
+            ShapeBarShape::Hexagon => PrimType::Polygon(6),
+            ShapeBarShape::Parallelogram => PrimType::Parallelogram,
+            ShapeBarShape::Cylinder => PrimType::Cylinder,
+            ShapeBarShape::Star => PrimType::Star(5),
+            ShapeBarShape::Custom(id) => PrimType::Custom(*id),
             _ => PrimType::Rect
         }
     }
-    fn get_item(&self, r: &Rect, fill: bool) -> ShapeBarItem {
-        let color = 
-            match self {
-                ShapeBarShape::TextBox => (255, 255, 255),
-                _ => ShapeBarShape::DEFAULT_COLOR
-            };
+    //size of a shape placed without dragging it out
+    fn default_size(&self) -> Point {
+        match self {
+            ShapeBarShape::Custom(id) => shape_kind::get(*id).map_or(
+                Point::new(ShapeBarShape::DEFAULT_SIZE, ShapeBarShape::DEFAULT_SIZE), |kind| kind.default_size()),
+            _ => Point::new(ShapeBarShape::DEFAULT_SIZE, ShapeBarShape::DEFAULT_SIZE)
+        }
+    }
+    fn get_shape(&self, r: &Rect, fill: bool, style: &Style) -> Shape {
+        let (color, stroke_color) = (style.fill_color, style.stroke_color);
         match self {
             ShapeBarShape::Line => {
-                ShapeBarItem::Shape(
-                    Shape::Line(DrawLine { p1: r.left_center(), p2: r.right_center(), line_width: 3., 
-                        color: rgb_to_f32(color.0, color.1, color.2)}))
-            },
-            ShapeBarShape::ColorPicker => {
-                ShapeBarItem::ColorPicker(HexColor::new(RotateRect::new(r.c1, r.size(), Radians(0.))))
+                let mut line = Shape::Line(DrawLine { p1: r.left_center(), p2: r.right_center(), ..DrawLine::default() });
+                style.apply(&mut line);
+                line
             },
             _ => {
                 let rect = RotateRect::new(r.c1, r.size(), Radians(0.));
                 let ptype = self.prim_type();
                 let prim = if !fill && ptype == PrimType::Circle { PrimType::Ring } else { ptype };
-                ShapeBarItem::Shape(
-                    Shape::Polygon(
-                        DrawPolygon { rect, fill, prim, color: rgb_to_f32(color.0, color.1, color.2) })
-                )
+                Shape::Polygon(DrawPolygon { rect, fill, prim, color, stroke_color, line_width: style.stroke_width })
+            }
+        }
+    }
+    fn get_item(&self, r: &Rect, fill: bool, style: &Style) -> ShapeBarItem {
+        match self {
+            ShapeBarShape::ColorPicker => {
+                ShapeBarItem::ColorPicker(HexColor::wheel(RotateRect::new(r.c1, r.size(), Radians(0.))))
+            },
+            ShapeBarShape::Custom(id) => {
+                match shape_kind::get(*id).and_then(|kind| kind.icon()).filter(|icon| !icon.is_empty()) {
+                    Some(icon) => {
+                        let pts: Vec<Point> = icon.iter().map(|p| r.c1 + Point::new(p.x * r.width(), p.y * r.height())).collect();
+                        ShapeBarItem::Icon(DrawLine {
+                            p1: pts[0], p2: pts[0], waypoints: pts[1..].to_vec(),
+                            color: style.stroke_color, line_width: 2., ..DrawLine::default()
+                        })
+                    }
+                    None => ShapeBarItem::Shape(self.get_shape(r, fill, style))
+                }
             }
+            _ => ShapeBarItem::Shape(self.get_shape(r, fill, style))
         }
     }
 }
@@ -903,7 +2606,9 @@ impl ShapeBarShape {
 struct ShapeBar {
     items: HashMap<ShapeBarShape, ShapeBarItem>,
     click_rects: HashMap<ShapeBarShape, Rect>,
-    draw_rect: Rect
+    draw_rect: Rect,
+    nkinds: usize,
+    picked: HashMap<ColorTarget, glm::Vec4>,  // Colors picked with nothing selected, new shapes get them over their style
 }
 
 enum ShapeBarAction {
@@ -912,18 +2617,22 @@ enum ShapeBarAction {
 }
 
 impl ShapeBar {
-    fn new(viewport: &Point) -> Self {
+    fn new(viewport: &Point, styles: &StyleSheet) -> Self {
         let draw_rect = Rect::new(
             Point { x: viewport.x / 5., y: 0. }, 
             Point { x: 4. * viewport.x / 5., y: viewport.y / 12.});
-        let mut items = HashMap::new();
         let mut click_rects = HashMap::new();
-        let shape_bar_shapes = [ShapeBarShape::Circle, ShapeBarShape::Triangle, 
+        let mut shape_bar_shapes = vec![ShapeBarShape::Circle, ShapeBarShape::Triangle, 
                                 ShapeBarShape::Rect, ShapeBarShape::TextBox, ShapeBarShape::Line,
-                                ShapeBarShape::ColorPicker];
+                                ShapeBarShape::RoundRect, ShapeBarShape::Diamond, ShapeBarShape::Hexagon,
+                                ShapeBarShape::Parallelogram, ShapeBarShape::Cylinder, ShapeBarShape::Star];
+        //registered shape kinds go after the built in ones
+        let nkinds = shape_kind::count();
+        shape_bar_shapes.extend((0..nkinds).map(|id| ShapeBarShape::Custom(id as ShapeKindId)));
+        shape_bar_shapes.push(ShapeBarShape::ColorPicker);
         let shapes_rect = Rect::new( 
-            draw_rect.c1 + Point {x: draw_rect.width() / 4., y: draw_rect.height() / 5.},
-            draw_rect.c2 - Point {x: draw_rect.width() / 4., y: draw_rect.height() / 5. } 
+            draw_rect.c1 + Point {x: draw_rect.width() / 10., y: draw_rect.height() / 5.},
+            draw_rect.c2 - Point {x: draw_rect.width() / 10., y: draw_rect.height() / 5. } 
         );
         let npoly = shape_bar_shapes.len() as u32;
         let rect_size = Point::new(ShapeBarShape::DEFAULT_SIZE, ShapeBarShape::DEFAULT_SIZE);
@@ -933,21 +2642,48 @@ impl ShapeBar {
                 shapes_rect.center().y);
             let rect = Rect::new(center - rect_size / 2., center + rect_size / 2.);
             click_rects.insert(s.clone(), rect.clone());
-            let fill = *s != ShapeBarShape::TextBox;
-            
-            items.insert(s.clone(), s.get_item(&rect, fill));
         }
-        ShapeBar {
-            items,
+        let mut shape_bar = ShapeBar {
+            items: HashMap::new(),
             click_rects,
             draw_rect,
-        }
+            nkinds,
+            picked: HashMap::new(),
+        };
+        shape_bar.update_items(styles);
+        shape_bar
+    }
+    //top center of the color picker panel
+    fn picker_anchor(&self) -> Point {
+        Point::new(self.click_rects[&ShapeBarShape::ColorPicker].center().x, self.draw_rect.c2.y)
     }
-    fn get_shape(&self, id: ShapeBarShape, r: &Rect, fill: bool) -> Shape {
-        match id.get_item(r, fill) {
-            ShapeBarItem::Shape(s) => s,
-            _ => Shape::Polygon(DrawPolygon::default())
+    //the style new shapes of a kind get, with the picked colors over it
+    fn style(&self, id: ShapeBarShape, styles: &StyleSheet) -> Style {
+        let mut style = styles.get(id.style_name()).clone();
+        for (target, color) in self.picked.iter() {
+            match target {
+                ColorTarget::Fill => style.fill_color = *color,
+                ColorTarget::Stroke => style.stroke_color = *color,
+                ColorTarget::Text => style.text_color = *color
+            }
         }
+        style
+    }
+    fn pick_color(&mut self, target: ColorTarget, color: glm::Vec4, styles: &StyleSheet) {
+        self.picked.insert(target, color);
+        self.update_items(styles);
+    }
+    fn has_picked(&self) -> bool {
+        !self.picked.is_empty()
+    }
+    fn update_items(&mut self, styles: &StyleSheet) {
+        self.items = self.click_rects.iter().map(|(s, rect)| {
+            let style = self.style(*s, styles);
+            (*s, s.get_item(rect, style.fill, &style))
+        }).collect();
+    }
+    fn get_shape(&self, id: ShapeBarShape, r: &Rect, fill: bool, styles: &StyleSheet) -> Shape {
+        id.get_shape(r, fill, &self.style(id, styles))
     }
     fn click_shape(&mut self, p: &Point, vp: &Point) -> Option<ShapeBarAction> {
         self.click_rects.iter().find(|(_, r)| r.in_bounds(p, vp)).map(|(id, _)| *id)
@@ -0,0 +1,176 @@
+extern crate nalgebra_glm;
+
+use crate::primitives::*;
+use crate::render_text::RenderText;
+
+//a named group of shapes drawn together, above the layers before it
+#[derive(Clone, PartialEq, Debug)]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub locked: bool,       // Shapes can't be hovered, clicked or selected
+    pub opacity: f32,       // Multiplies the alpha of everything on the layer
+}
+
+impl Layer {
+    pub fn new(name: &str) -> Self {
+        Layer { name: name.to_string(), visible: true, locked: false, opacity: 1. }
+    }
+    //whether shapes on the layer respond to the mouse
+    pub fn interactive(&self) -> bool {
+        self.visible && !self.locked
+    }
+    pub fn set_field(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let bool_val = || value.parse::<bool>().map_err(|_| format!("Invalid bool {:?}", value));
+        match key {
+            "name" => self.name = value.to_string(),
+            "visible" => self.visible = bool_val()?,
+            "locked" => self.locked = bool_val()?,
+            "opacity" => self.opacity = value.parse::<f32>().ok().filter(|o| *o >= 0. && *o <= 1.)
+                .ok_or(format!("Invalid opacity {:?}", value))?,
+            _ => return Err(format!("Unknown layer property {:?}", key))
+        }
+        Ok(())
+    }
+    pub fn write(&self, out: &mut String) {
+        out.push_str(&format!("name = {}\n", self.name));
+        out.push_str(&format!("visible = {}\n", self.visible));
+        out.push_str(&format!("locked = {}\n", self.locked));
+        out.push_str(&format!("opacity = {}\n", self.opacity));
+    }
+}
+
+//the parts of a layer's row in the panel
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LayerPart {
+    Visible,
+    Lock,
+    Name,
+    Opacity,
+    MoveHere,   // Moves the selection onto the layer
+}
+
+//layers are by index from the bottom, the panel lists them top first
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LayerHover {
+    Panel,
+    Add,
+    Delete,
+    Raise,
+    Lower,
+    Row(usize, LayerPart),
+}
+
+pub struct LayerPanel {
+    pub open: bool,
+    pub edit_text: String,
+    viewport: Point,
+    bottom: f32,
+}
+
+impl LayerPanel {
+    const WIDTH: f32 = 240.;
+    const ROW_HEIGHT: f32 = 24.;
+    const TEXT_SCALE: f32 = 0.6;
+    const OPACITY_WIDTH: f32 = 44.;
+    pub const OPACITY_STEP: f32 = 0.1;
+
+    //`bottom` is the y the panel sits on
+    pub fn new(viewport: &Point, bottom: f32) -> Self {
+        LayerPanel { open: false, edit_text: String::new(), viewport: *viewport, bottom }
+    }
+    //the bottom left corner, growing up as layers are added
+    fn panel_rect(&self, nlayers: usize) -> Rect {
+        let height = (nlayers + 1) as f32 * LayerPanel::ROW_HEIGHT;
+        Rect::new(Point::new(0., self.bottom - height), Point::new(LayerPanel::WIDTH, self.bottom))
+    }
+    fn row_rect(&self, row: usize, nlayers: usize) -> Rect {
+        let c1 = self.panel_rect(nlayers).c1 + Point::new(0., row as f32 * LayerPanel::ROW_HEIGHT);
+        Rect::new(c1, c1 + Point::new(LayerPanel::WIDTH, LayerPanel::ROW_HEIGHT))
+    }
+    //square cells from the left of a row, or from the right when `i` is negative
+    fn cell_rect(&self, row: usize, nlayers: usize, i: i32) -> Rect {
+        let r = self.row_rect(row, nlayers);
+        let side = LayerPanel::ROW_HEIGHT;
+        let x = if i >= 0 { r.c1.x + i as f32 * side } else { r.c2.x + i as f32 * side };
+        Rect::new(Point::new(x + 3., r.c1.y + 3.), Point::new(x + side - 3., r.c2.y - 3.))
+    }
+    fn part_rect(&self, layer: usize, nlayers: usize, part: LayerPart) -> Rect {
+        let row = nlayers - layer;
+        let r = self.row_rect(row, nlayers);
+        let side = LayerPanel::ROW_HEIGHT;
+        match part {
+            LayerPart::Visible => self.cell_rect(row, nlayers, 0),
+            LayerPart::Lock => self.cell_rect(row, nlayers, 1),
+            LayerPart::MoveHere => self.cell_rect(row, nlayers, -1),
+            LayerPart::Opacity => Rect::new(Point::new(r.c2.x - side - LayerPanel::OPACITY_WIDTH, r.c1.y), Point::new(r.c2.x - side, r.c2.y)),
+            LayerPart::Name => Rect::new(Point::new(r.c1.x + 2. * side, r.c1.y), Point::new(r.c2.x - side - LayerPanel::OPACITY_WIDTH, r.c2.y)),
+        }
+    }
+    fn buttons(&self, nlayers: usize) -> [(LayerHover, &'static str, Rect); 4] {
+        [
+            (LayerHover::Lower, "▼", self.cell_rect(0, nlayers, -1)),
+            (LayerHover::Raise, "▲", self.cell_rect(0, nlayers, -2)),
+            (LayerHover::Delete, "-", self.cell_rect(0, nlayers, -3)),
+            (LayerHover::Add, "+", self.cell_rect(0, nlayers, -4)),
+        ]
+    }
+    pub fn get_hover(&self, p: &Point, layers: &[&Layer]) -> Option<LayerHover> {
+        let n = layers.len();
+        if !self.open || !self.panel_rect(n).in_bounds(p, &self.viewport) {
+            return None;
+        }
+        if let Some((hover, _, _)) = self.buttons(n).iter().find(|(_, _, r)| r.contains(p)) {
+            return Some(*hover);
+        }
+        const PARTS: [LayerPart; 5] = [LayerPart::Visible, LayerPart::Lock, LayerPart::Name, LayerPart::Opacity, LayerPart::MoveHere];
+        (0..n).flat_map(|i| PARTS.iter().map(move |part| (i, *part)))
+            .find(|(i, part)| self.part_rect(*i, n, *part).contains(p))
+            .map(|(i, part)| LayerHover::Row(i, part))
+            .or(Some(LayerHover::Panel))
+    }
+    pub fn begin_rename(&mut self, layer: &Layer) {
+        self.edit_text = layer.name.clone();
+    }
+    pub fn push_char(&mut self, ch: char) {
+        self.edit_text.push(ch);
+    }
+    pub fn pop_char(&mut self) {
+        self.edit_text.pop();
+    }
+    //the new name, layers can't be left without one
+    pub fn finish_rename(&mut self) -> Option<String> {
+        let name = self.edit_text.trim().to_string();
+        self.edit_text.clear();
+        Some(name).filter(|name| !name.is_empty())
+    }
+    fn draw_label(&self, text: &str, r: &Rect, rt: &RenderText, draw_ctx: &DrawCtx) {
+        let r = Rect::new(r.c1 + Point::new(4., 0.), r.c2);
+        rt.draw_label(text, &r, &rgb_to_f32(0, 0, 0), LayerPanel::TEXT_SCALE, draw_ctx);
+    }
+    fn draw_toggle(&self, text: &str, on: bool, r: &Rect, rt: &RenderText, draw_ctx: &DrawCtx) {
+        let color = if on { (150, 190, 255) } else { (255, 255, 255) };
+        r.builder().color(color.0, color.1, color.2).stroke(0, 0, 0).line_width(1.).get().draw(draw_ctx);
+        self.draw_label(text, &Rect::new(r.c1 - Point::new(1., 0.), r.c2), rt, draw_ctx);
+    }
+    pub fn draw(&self, layers: &[&Layer], active: usize, renaming: Option<usize>, rt: &RenderText, draw_ctx: &DrawCtx) {
+        let n = layers.len();
+        self.panel_rect(n).builder().color(230, 230, 230).get().draw(draw_ctx);
+        self.draw_label("Layers", &self.row_rect(0, n), rt, draw_ctx);
+        for (_, text, r) in self.buttons(n).iter() {
+            self.draw_toggle(text, false, r, rt, draw_ctx);
+        }
+        for (i, layer) in layers.iter().enumerate() {
+            if i == active {
+                self.row_rect(n - i, n).builder().color(200, 215, 240).get().draw(draw_ctx);
+            }
+            self.draw_toggle("V", layer.visible, &self.part_rect(i, n, LayerPart::Visible), rt, draw_ctx);
+            self.draw_toggle("L", layer.locked, &self.part_rect(i, n, LayerPart::Lock), rt, draw_ctx);
+            self.draw_toggle("←", false, &self.part_rect(i, n, LayerPart::MoveHere), rt, draw_ctx);
+            let name = if renaming == Some(i) { format!("{}_", self.edit_text) } else { layer.name.clone() };
+            self.draw_label(&name, &self.part_rect(i, n, LayerPart::Name), rt, draw_ctx);
+            let opacity = format!("{}%", (layer.opacity * 100.).round());
+            self.draw_label(&opacity, &self.part_rect(i, n, LayerPart::Opacity), rt, draw_ctx);
+        }
+    }
+}
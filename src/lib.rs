@@ -1,9 +1,27 @@
 pub mod interface;
 pub mod render_gl;
 pub mod render_text;
+pub mod glyph_atlas;
+pub mod shaping;
+pub mod fonts;
 #[macro_use]
 //pub mod displaytree;
 //pub mod app;
 pub mod primitives;
 pub mod hexcolor;
 pub mod textedit;
+pub mod text_spans;
+pub mod inspector;
+pub mod style;
+pub mod commands;
+pub mod palette;
+pub mod routing;
+pub mod shape_kind;
+pub mod geometry;
+pub mod layers;
+pub mod document;
+pub mod pages;
+pub mod truetype;
+pub mod pdf;
+pub mod svg;
+pub mod minimap;
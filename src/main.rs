@@ -4,18 +4,35 @@ extern crate rand;
 extern crate nalgebra_glm;
 
 use sdl2::event::Event;
-use sdl2::keyboard::{Keycode};
 use std::time::{SystemTime, Duration};
 
 pub mod interface;
 pub mod render_gl;
 pub mod render_text;
+pub mod glyph_atlas;
+pub mod shaping;
+pub mod fonts;
 #[macro_use]
 pub mod primitives;
 pub mod hexcolor;
 #[macro_use]
 //pub mod app;
 pub mod textedit;
+pub mod text_spans;
+pub mod inspector;
+pub mod style;
+pub mod commands;
+pub mod palette;
+pub mod routing;
+pub mod shape_kind;
+pub mod geometry;
+pub mod layers;
+pub mod document;
+pub mod pages;
+pub mod truetype;
+pub mod pdf;
+pub mod svg;
+pub mod minimap;
 use interface::{AppState};
 use primitives::{*};
 
@@ -44,7 +61,7 @@ fn main() {
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
     }
 
-    let mut app_state = AppState::new(&VIEWPORT);
+    let mut app_state = AppState::new(&VIEWPORT, &video_subsystem);
 
     let mut event_pump = sdl.event_pump().unwrap();
     let mut timer = SystemTime::now();
@@ -55,19 +72,27 @@ fn main() {
         for event in event_pump.poll_iter() {
             let kmod = sdl.keyboard().mod_state();
             match event {
-                Event::Quit {..} | 
-                Event::KeyDown { keycode: Some(Keycode::Escape), ..} => break 'main,
+                Event::Quit {..} => break 'main,
                 ev @ Event::MouseMotion{..} | 
                 ev @ Event::MouseButtonDown{..} | 
-                ev @ Event::MouseButtonUp{..} => { 
+                ev @ Event::MouseButtonUp{..} |
+                ev @ Event::MouseWheel{..} => { 
                     app_state.handle_mouse_event(&ev, &kmod);
                 }
+                ev @ Event::TextInput {..} |
+                ev @ Event::TextEditing {..} => {
+                    app_state.handle_text_input_event(&ev);
+                }
                 ev @ Event::KeyDown {..} => {
                     app_state.handle_keyboard_event(&ev);
+                    if app_state.should_quit() {
+                        break 'main;
+                    }
                 }
                 _ => {},
             }
         }
+        app_state.update();
         unsafe { 
             gl::Clear(gl::COLOR_BUFFER_BIT); 
         }
@@ -0,0 +1,186 @@
+extern crate gl;
+extern crate nalgebra_glm;
+
+use std::cell::{Cell, RefCell};
+use gl::types::*;
+use nalgebra_glm as glm;
+use crate::primitives::*;
+use crate::render_gl::SendUniforms;
+
+//maps the world onto the minimap, scaled to fit `bounds` and centered in the panel
+#[derive(Clone, PartialEq, Debug)]
+pub struct MinimapView {
+    pub bounds: Rect,
+    pub panel: Rect,
+    scale: f32,
+    offset: Point,
+}
+
+impl MinimapView {
+    pub fn new(bounds: &Rect, panel: &Rect) -> Self {
+        let size = bounds.size();
+        let scale = f32::min(panel.width() / f32::max(size.x, 1.), panel.height() / f32::max(size.y, 1.));
+        let center = bounds.center();
+        let offset = panel.center() - Point::new(center.x * scale, center.y * scale);
+        MinimapView { bounds: bounds.clone(), panel: panel.clone(), scale, offset }
+    }
+    //the shapes' bounds together with the view at the page origin, so an empty page still has a map
+    pub fn fit(shapes: &[(&Shape, f32)], panel: &Rect, vp: &Point) -> Self {
+        let mut pts = vec![Point::origin(), *vp];
+        pts.extend(shapes.iter().flat_map(|(s, _)| MinimapView::outline(s, vp)));
+        MinimapView::new(&Rect::bounding_box(&pts), panel)
+    }
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+    pub fn to_panel(&self, pt: &Point) -> Point {
+        Point::new(pt.x * self.scale, pt.y * self.scale) + self.offset
+    }
+    pub fn to_world(&self, pt: &Point) -> Point {
+        (*pt - self.offset) / self.scale
+    }
+    //corners of a shape's rect, or the points of a line
+    fn outline(shape: &Shape, vp: &Point) -> Vec<Point> {
+        match shape {
+            Shape::Polygon(poly) => poly.rect.verts(vp),
+            Shape::Line(line) => line.points()
+        }
+    }
+    //triangles of x, y, r, g, b in window pixels. shapes are drawn as their rects and lines as thin quads,
+    //faded into `background` by the opacity of their layer
+    pub fn verts(&self, shapes: &[(&Shape, f32)], background: &glm::Vec4, vp: &Point) -> Vec<f32> {
+        let mut v = Vec::new();
+        for (shape, opacity) in shapes {
+            let (color, fill) = match shape {
+                Shape::Polygon(poly) if poly.fill => (poly.color, true),
+                Shape::Polygon(poly) => (poly.stroke_color, false),
+                Shape::Line(line) => (line.color, false)
+            };
+            let alpha = color[3] * opacity;
+            let color = color * alpha + background * (1. - alpha);
+            let pts: Vec<Point> = MinimapView::outline(shape, vp).iter().map(|p| self.to_panel(p)).collect();
+            let mut quad = |q: [Point; 4]| {
+                for i in [0, 1, 2, 0, 2, 3].iter() {
+                    v.extend_from_slice(&[q[*i].x, q[*i].y, color[0], color[1], color[2]]);
+                }
+            };
+            match shape {
+                Shape::Polygon(_) if fill => quad([pts[0], pts[1], pts[2], pts[3]]),
+                //unfilled shapes are outlined, closed back on their first corner
+                _ => {
+                    let closed = if let Shape::Polygon(_) = shape { pts.len() } else { pts.len() - 1 };
+                    for i in 0..closed {
+                        let (p1, p2) = (pts[i], pts[(i + 1) % pts.len()]);
+                        let dir = p2 - p1;
+                        let len = dir.mag();
+                        if len == 0. {
+                            continue;
+                        }
+                        let n = Point::new(-dir.y, dir.x) / (2. * len / Minimap::LINE_WIDTH);
+                        quad([p1 - n, p2 - n, p2 + n, p1 + n]);
+                    }
+                }
+            }
+        }
+        v
+    }
+}
+
+//an overview of the page in the bottom right corner. its triangles are only rebuilt
+//when the draw list's generation changes, the view rect over them is drawn every frame
+pub struct Minimap {
+    pub open: bool,
+    viewport: Point,
+    bottom: f32,
+    vao: Cell<GLuint>,
+    vbo: Cell<GLuint>,
+    cache: RefCell<Option<(u64, MinimapView, usize)>>,    // Generation, view and number of verts
+}
+
+impl Minimap {
+    const WIDTH: f32 = 200.;
+    const HEIGHT: f32 = 150.;
+    const PADDING: f32 = 6.;
+    pub const LINE_WIDTH: f32 = 1.5;
+    const BACKGROUND: (u8, u8, u8) = (245, 245, 245);
+
+    //`bottom` is the y the panel sits on
+    pub fn new(viewport: &Point, bottom: f32) -> Self {
+        Minimap { open: false, viewport: *viewport, bottom, vao: Cell::new(0), vbo: Cell::new(0), cache: RefCell::new(None) }
+    }
+    fn panel_rect(&self) -> Rect {
+        Rect::new(Point::new(self.viewport.x - Minimap::WIDTH, self.bottom - Minimap::HEIGHT), Point::new(self.viewport.x, self.bottom))
+    }
+    fn map_rect(&self) -> Rect {
+        let r = self.panel_rect();
+        let pad = Point::new(Minimap::PADDING, Minimap::PADDING);
+        Rect::new(r.c1 + pad, r.c2 - pad)
+    }
+    pub fn get_hover(&self, p: &Point) -> bool {
+        self.open && self.panel_rect().contains(p)
+    }
+    //the world point under a point in the panel, once the map has been drawn
+    pub fn to_world(&self, p: &Point) -> Option<Point> {
+        self.cache.borrow().as_ref().map(|(_, view, _)| view.to_world(p))
+    }
+    unsafe fn upload(&self, verts: &[f32]) {
+        if self.vao.get() == 0 {
+            let (mut vao, mut vbo) = (0, 0);
+            gl::GenBuffers(1, &mut vbo);
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            let stride = (5 * std::mem::size_of::<f32>()) as GLint;
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, stride, (2 * std::mem::size_of::<f32>()) as *const GLvoid);
+            gl::EnableVertexAttribArray(1);
+            gl::BindVertexArray(0);
+            self.vao.set(vao);
+            self.vbo.set(vbo);
+        }
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.get());
+        gl::BufferData(gl::ARRAY_BUFFER,
+            std::mem::size_of_val(verts) as GLsizeiptr,
+            verts.as_ptr() as *const GLvoid,
+            gl::DYNAMIC_DRAW
+        );
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    }
+    //`shapes` is only called when `generation` isn't the one the map was built for
+    pub fn draw<'a, F: FnOnce() -> Vec<(&'a Shape, f32)>>(&self, generation: u64, shapes: F, camera: &Point, draw_ctx: &DrawCtx) {
+        let vp = &draw_ctx.viewport;
+        if self.cache.borrow().as_ref().is_none_or(|(g, _, _)| *g != generation) {
+            let shapes = shapes();
+            let view = MinimapView::fit(&shapes, &self.map_rect(), vp);
+            let (r, g, b) = Minimap::BACKGROUND;
+            let verts = view.verts(&shapes, &rgb_to_f32(r, g, b), vp);
+            unsafe { self.upload(&verts); }
+            self.cache.replace(Some((generation, view, verts.len() / 5)));
+        }
+        let (r, g, b) = Minimap::BACKGROUND;
+        self.panel_rect().builder().color(r, g, b).stroke(120, 120, 120).line_width(1.).get().draw(draw_ctx);
+        let cache = self.cache.borrow();
+        let (view, nverts) = match cache.as_ref() {
+            Some((_, view, nverts)) => (view, *nverts),
+            None => return
+        };
+        let prog = draw_ctx.program(&PrimType::HexColor);
+        prog.set_used();
+        let trans = RectTransform { projection: glm::ortho(0., vp.x, vp.y, 0., -1., 1.), model: glm::identity() };
+        unsafe {
+            gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            trans.send_uniforms(prog.id()).unwrap();
+            gl::BindVertexArray(self.vao.get());
+            gl::DrawArrays(gl::TRIANGLES, 0, nverts as i32);
+        }
+        //the view rect is cut to the panel when the camera is off past the shapes
+        let (c1, c2) = (view.to_panel(camera), view.to_panel(&(*camera + *vp)));
+        let r = self.panel_rect();
+        let c1 = Point::new(f32::max(c1.x, r.c1.x), f32::max(c1.y, r.c1.y));
+        let c2 = Point::new(f32::min(c2.x, r.c2.x), f32::min(c2.y, r.c2.y));
+        if c1.x < c2.x && c1.y < c2.y {
+            Rect::new(c1, c2).builder().fill(false).stroke(220, 50, 50).line_width(1.).get().draw(draw_ctx);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+extern crate nalgebra_glm;
+
+use crate::primitives::*;
+use crate::render_text::RenderText;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TabHover {
+    Strip,
+    Tab(usize),
+    Add,
+}
+
+//a tab for each page along the bottom of the window
+pub struct PageTabs {
+    pub edit_text: String,
+    viewport: Point,
+}
+
+impl PageTabs {
+    pub const HEIGHT: f32 = 24.;
+    const TAB_WIDTH: f32 = 120.;
+    const TEXT_SCALE: f32 = 0.6;
+
+    pub fn new(viewport: &Point) -> Self {
+        PageTabs { edit_text: String::new(), viewport: *viewport }
+    }
+    fn strip_rect(&self) -> Rect {
+        Rect::new(Point::new(0., self.viewport.y - PageTabs::HEIGHT), self.viewport)
+    }
+    fn tab_rect(&self, i: usize) -> Rect {
+        let c1 = self.strip_rect().c1 + Point::new(i as f32 * PageTabs::TAB_WIDTH, 0.);
+        Rect::new(c1, c1 + Point::new(PageTabs::TAB_WIDTH - 2., PageTabs::HEIGHT))
+    }
+    fn add_rect(&self, npages: usize) -> Rect {
+        let c1 = self.tab_rect(npages).c1;
+        Rect::new(c1 + Point::new(3., 3.), c1 + Point::new(PageTabs::HEIGHT - 3., PageTabs::HEIGHT - 3.))
+    }
+    pub fn get_hover(&self, p: &Point, npages: usize) -> Option<TabHover> {
+        if !self.strip_rect().contains(p) {
+            return None;
+        }
+        if self.add_rect(npages).contains(p) {
+            return Some(TabHover::Add);
+        }
+        (0..npages).find(|i| self.tab_rect(*i).contains(p)).map(TabHover::Tab).or(Some(TabHover::Strip))
+    }
+    pub fn begin_rename(&mut self, name: &str) {
+        self.edit_text = name.to_string();
+    }
+    pub fn push_char(&mut self, ch: char) {
+        self.edit_text.push(ch);
+    }
+    pub fn pop_char(&mut self) {
+        self.edit_text.pop();
+    }
+    //the new name, pages can't be left without one
+    pub fn finish_rename(&mut self) -> Option<String> {
+        let name = self.edit_text.trim().to_string();
+        self.edit_text.clear();
+        Some(name).filter(|name| !name.is_empty())
+    }
+    fn draw_label(&self, text: &str, r: &Rect, rt: &RenderText, draw_ctx: &DrawCtx) {
+        let r = Rect::new(r.c1 + Point::new(6., 0.), r.c2);
+        rt.draw_label(text, &r, &rgb_to_f32(0, 0, 0), PageTabs::TEXT_SCALE, draw_ctx);
+    }
+    pub fn draw(&self, names: &[&str], current: usize, renaming: Option<usize>, rt: &RenderText, draw_ctx: &DrawCtx) {
+        self.strip_rect().builder().color(200, 200, 200).get().draw(draw_ctx);
+        for (i, name) in names.iter().enumerate() {
+            let color = if i == current { (255, 255, 255) } else { (230, 230, 230) };
+            let r = self.tab_rect(i);
+            r.builder().color(color.0, color.1, color.2).get().draw(draw_ctx);
+            let name = if renaming == Some(i) { format!("{}_", self.edit_text) } else { name.to_string() };
+            self.draw_label(&name, &r, rt, draw_ctx);
+        }
+        let add = self.add_rect(names.len());
+        add.builder().color(255, 255, 255).stroke(0, 0, 0).line_width(1.).get().draw(draw_ctx);
+        self.draw_label("+", &Rect::new(add.c1 - Point::new(1., 0.), add.c2), rt, draw_ctx);
+    }
+}
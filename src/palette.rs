@@ -0,0 +1,156 @@
+extern crate nalgebra_glm;
+
+use std::time::SystemTime;
+use crate::primitives::*;
+use crate::render_text::RenderText;
+use crate::textedit::{TextBox, TextCursorDirection, TextInput};
+use crate::commands::{CommandId, Keymap};
+
+//subsequence match of the query against the text, higher is better
+//consecutive matches and matches at the start of a word score extra
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut ti = 0;
+    let mut last_match: Option<usize> = None;
+    for qc in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let idx = (ti..text.len()).find(|i| text[*i] == qc)?;
+        score += 1;
+        if last_match.is_some_and(|last| last + 1 == idx) {
+            score += 5;
+        }
+        if idx == 0 || !text[idx - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (idx - ti) as i32 / 4;
+        last_match = Some(idx);
+        ti = idx + 1;
+    }
+    Some(score)
+}
+
+pub struct CommandPalette {
+    query: TextBox,
+    matches: Vec<CommandId>,
+    selected: usize,
+    recent: Vec<CommandId>,
+    open_time: SystemTime,
+    viewport: Point,
+}
+
+impl CommandPalette {
+    const WIDTH: f32 = 420.;
+    const ROW_HEIGHT: f32 = 24.;
+    const MAX_ROWS: usize = 10;
+    const MAX_RECENT: usize = 8;
+    const TEXT_SCALE: f32 = 0.6;
+
+    pub fn new(viewport: &Point) -> Self {
+        let mut palette = CommandPalette {
+            query: TextBox::new(),
+            matches: Vec::new(),
+            selected: 0,
+            recent: Vec::new(),
+            open_time: SystemTime::now(),
+            viewport: *viewport,
+        };
+        palette.update_matches();
+        palette
+    }
+    pub fn open(&mut self) {
+        self.query.clear();
+        self.query.set_text_scale(CommandPalette::TEXT_SCALE);
+        self.open_time = SystemTime::now();
+        self.update_matches();
+    }
+    fn field_rect(&self) -> Rect {
+        let c1 = Point::new((self.viewport.x - CommandPalette::WIDTH) / 2., self.viewport.y / 12. + 10.);
+        Rect::new(c1, c1 + Point::new(CommandPalette::WIDTH, CommandPalette::ROW_HEIGHT))
+    }
+    fn row_rect(&self, row: usize) -> Rect {
+        let c1 = self.field_rect().c1 + Point::new(0., (row + 1) as f32 * CommandPalette::ROW_HEIGHT);
+        Rect::new(c1, c1 + Point::new(CommandPalette::WIDTH, CommandPalette::ROW_HEIGHT))
+    }
+    fn text_rect(&self) -> RotateRect {
+        let r = self.field_rect();
+        RotateRect::new(r.c1 + Point::new(4., 0.), r.size() - Point::new(8., 0.), Radians(0.))
+    }
+    //recently used commands come first, the rest are ranked by fuzzy score
+    fn update_matches(&mut self) {
+        let query = self.query.text();
+        let mut scored: Vec<(usize, i32, CommandId)> = CommandId::ALL.iter()
+            .filter(|cmd| **cmd != CommandId::CommandPalette)
+            .filter_map(|cmd| {
+                let score = fuzzy_score(&query, cmd.name()).or(fuzzy_score(&query, cmd.id()))?;
+                let recent = self.recent.iter().position(|r| r == cmd).unwrap_or(self.recent.len());
+                Some((recent, score, *cmd))
+            }).collect();
+        scored.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        self.matches = scored.into_iter().map(|(_, _, cmd)| cmd).collect();
+        self.selected = 0;
+    }
+    //the query is typed from text input events, what the IME is composing is shown in it until it's committed
+    pub fn input_text(&mut self, input: TextInput) {
+        match input {
+            TextInput::Commit(text) => {
+                self.query.commit_text(&text);
+                self.update_matches();
+            }
+            TextInput::Compose(comp) => self.query.set_composition(comp)
+        }
+    }
+    pub fn composing(&self) -> bool {
+        self.query.composition().is_some()
+    }
+    pub fn pop_char(&mut self) {
+        self.query.delete_char();
+        self.update_matches();
+    }
+    pub fn move_cursor(&mut self, dir: TextCursorDirection, rt: &RenderText) {
+        match dir {
+            TextCursorDirection::Up => self.selected = self.selected.saturating_sub(1),
+            TextCursorDirection::Down => {
+                if self.selected + 1 < self.matches.len() {
+                    self.selected += 1;
+                }
+            }
+            _ => {
+                let r = self.text_rect();
+                self.query.move_cursor(dir, false, &r, rt);
+            }
+        }
+    }
+    //the command to run when enter is pressed, it's moved to the front of the recent list
+    pub fn accept(&mut self) -> Option<CommandId> {
+        let cmd = *self.matches.get(self.selected)?;
+        self.recent.retain(|r| *r != cmd);
+        self.recent.insert(0, cmd);
+        self.recent.truncate(CommandPalette::MAX_RECENT);
+        Some(cmd)
+    }
+    pub fn draw(&self, keymap: &Keymap, rt: &RenderText, draw_ctx: &DrawCtx) {
+        let nrows = std::cmp::min(self.matches.len(), CommandPalette::MAX_ROWS);
+        let first = (self.selected + 1).saturating_sub(CommandPalette::MAX_ROWS);
+        let field = self.field_rect();
+        let panel = Rect::new(field.c1, self.row_rect(nrows).c1 + Point::new(CommandPalette::WIDTH, 0.));
+        panel.builder().color(230, 230, 230).get().draw(draw_ctx);
+        field.builder().color(255, 255, 255).get().draw(draw_ctx);
+        self.query.draw(&self.text_rect(), Some(self.open_time), rt, draw_ctx);
+        let black = rgb_to_f32(0, 0, 0);
+        let gray = rgb_to_f32(90, 90, 90);
+        for (row, cmd) in self.matches.iter().skip(first).take(nrows).enumerate() {
+            let r = self.row_rect(row);
+            if first + row == self.selected {
+                r.builder().color(180, 200, 250).get().draw(draw_ctx);
+            }
+            let label = Rect::new(r.c1 + Point::new(4., 0.), r.c2);
+            rt.draw_label(cmd.name(), &label, &black, CommandPalette::TEXT_SCALE, draw_ctx);
+            let binding = keymap.bindings_for(*cmd).iter().map(|k| k.display()).collect::<Vec<String>>().join(", ");
+            if !binding.is_empty() {
+                let width = rt.measure(&binding, CommandPalette::TEXT_SCALE).x;
+                let binding_rect = Rect::new(Point::new(r.c2.x - width - 6., r.c1.y), r.c2);
+                rt.draw_label(&binding, &binding_rect, &gray, CommandPalette::TEXT_SCALE, draw_ctx);
+            }
+        }
+    }
+}
@@ -0,0 +1,378 @@
+extern crate nalgebra_glm;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use nalgebra_glm as glm;
+use crate::primitives::*;
+use crate::render_text::PlacedText;
+use crate::truetype::TrueType;
+
+//numbers are written with at most 4 decimals
+fn num(n: f32) -> String {
+    let s = format!("{:.4}", n);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" { "0".to_string() } else { s.to_string() }
+}
+
+//a font embedded in the document, with the glyphs drawn with it and the text each was shaped from
+struct PdfFont {
+    name: String,
+    data: &'static [u8],
+    slant: f32,
+    glyphs: BTreeMap<u16, String>,
+}
+
+//how a face's glyphs are drawn with a document font, styles the font file lacks are synthesized like on screen
+#[derive(Copy, Clone, Debug)]
+pub struct PdfFace {
+    pub font: usize,
+    pub slant: f32,
+    pub bold: bool,
+}
+
+//a page's content stream and the resources it uses
+pub struct PdfPage {
+    bounds: Rect,                           // Part of the canvas on the page, in pixels
+    content: String,
+    alphas: Vec<(&'static str, f32)>,       // Graphics states, each setting the fill or stroke alpha
+    glyphs: BTreeMap<usize, BTreeMap<u16, String>>,
+}
+
+impl PdfPage {
+    pub const MARGIN: f32 = 20.;
+    //canvas pixels are 1/96 inch
+    pub const PT_PER_PX: f32 = 0.75;
+
+    //the page fits `bounds` and a margin. content is drawn in canvas pixels with y down, like on screen
+    pub fn new(bounds: &Rect) -> Self {
+        let margin = Point::new(PdfPage::MARGIN, PdfPage::MARGIN);
+        let bounds = Rect::new(bounds.c1 - margin, bounds.c2 + margin);
+        let mut page = PdfPage { bounds, content: String::new(), alphas: Vec::new(), glyphs: BTreeMap::new() };
+        let (s, c1, height) = (PdfPage::PT_PER_PX, page.bounds.c1, page.size().y);
+        page.op(&format!("{} 0 0 {} {} {} cm", num(s), num(-s), num(-c1.x * s), num(height + c1.y * s)));
+        page
+    }
+    //in points
+    pub fn size(&self) -> Point {
+        Point::new(self.bounds.width() * PdfPage::PT_PER_PX, self.bounds.height() * PdfPage::PT_PER_PX)
+    }
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+    fn op(&mut self, op: &str) {
+        self.content.push_str(op);
+        self.content.push('\n');
+    }
+    pub fn save(&mut self) {
+        self.op("q");
+    }
+    pub fn restore(&mut self) {
+        self.op("Q");
+    }
+    //the 2d part of a model matrix is applied to everything drawn after it
+    pub fn transform(&mut self, m: &glm::Mat4) {
+        self.op(&format!("{} {} {} {} {} {} cm", num(m[(0, 0)]), num(m[(1, 0)]), num(m[(0, 1)]), num(m[(1, 1)]), num(m[(0, 3)]), num(m[(1, 3)])));
+    }
+    //`key` is ca for fills and CA for strokes
+    fn alpha(&mut self, key: &'static str, alpha: f32) {
+        let alpha = (alpha * 1000.).round() / 1000.;
+        let i = match self.alphas.iter().position(|a| *a == (key, alpha)) {
+            Some(i) => i,
+            None => {
+                self.alphas.push((key, alpha));
+                self.alphas.len() - 1
+            }
+        };
+        self.op(&format!("/GS{} gs", i));
+    }
+    //`opacity` is the layer's, it multiplies the color's alpha
+    pub fn fill_color(&mut self, color: &glm::Vec4, opacity: f32) {
+        self.op(&format!("{} {} {} rg", num(color.x), num(color.y), num(color.z)));
+        self.alpha("ca", color.w * opacity);
+    }
+    pub fn stroke_color(&mut self, color: &glm::Vec4, opacity: f32) {
+        self.op(&format!("{} {} {} RG", num(color.x), num(color.y), num(color.z)));
+        self.alpha("CA", color.w * opacity);
+    }
+    pub fn line_width(&mut self, width: f32) {
+        self.op(&format!("{} w", num(width)));
+    }
+    pub fn move_to(&mut self, p: &Point) {
+        self.op(&format!("{} {} m", num(p.x), num(p.y)));
+    }
+    pub fn line_to(&mut self, p: &Point) {
+        self.op(&format!("{} {} l", num(p.x), num(p.y)));
+    }
+    pub fn curve_to(&mut self, c1: &Point, c2: &Point, p: &Point) {
+        self.op(&format!("{} {} {} {} {} {} c", num(c1.x), num(c1.y), num(c2.x), num(c2.y), num(p.x), num(p.y)));
+    }
+    pub fn close(&mut self) {
+        self.op("h");
+    }
+    pub fn polyline(&mut self, pts: &[Point]) {
+        if let Some((first, rest)) = pts.split_first() {
+            self.move_to(first);
+            rest.iter().for_each(|p| self.line_to(p));
+        }
+    }
+    pub fn polygon(&mut self, pts: &[Point]) {
+        self.polyline(pts);
+        self.close();
+    }
+    pub fn fill(&mut self) {
+        self.op("f");
+    }
+    pub fn stroke(&mut self) {
+        self.op("S");
+    }
+    //a glyph in the fill color with its baseline starting at `pen`, `text` is what it reads as when selected
+    pub fn glyph(&mut self, face: &PdfFace, gid: u16, text: &str, pen: &Point, size: f32) {
+        //bold is the outline stroked as well as filled, as thick as FreeType's embolden
+        if face.bold {
+            self.op(&format!("2 Tr {} w", num(size / 24.)));
+        }
+        else {
+            self.op("0 Tr");
+        }
+        //glyphs are drawn flipped back upright in the y down space
+        self.op(&format!("BT /F{} 1 Tf {} 0 {} {} {} {} Tm <{:04X}> Tj ET",
+            face.font, num(size), num(face.slant * size), num(-size), num(pen.x), num(pen.y), gid));
+        let glyph_text = self.glyphs.entry(face.font).or_default().entry(gid).or_default();
+        if glyph_text.is_empty() {
+            *glyph_text = text.to_string();
+        }
+    }
+}
+
+//four bezier arcs around the ellipse filling the unit square
+fn ellipse(page: &mut PdfPage, to_canvas: &dyn Fn(&Point) -> Point) {
+    //distance of the control points from the ends of a quarter of a unit circle
+    const KAPPA: f32 = 0.5523;
+    let at = |x: f32, y: f32| to_canvas(&Point::new(0.5 + 0.5 * x, 0.5 + 0.5 * y));
+    let dirs = [(1., 0.), (0., 1.), (-1., 0.), (0., -1.)];
+    page.move_to(&at(1., 0.));
+    for i in 0..4 {
+        let ((ax, ay), (bx, by)) = (dirs[i], dirs[(i + 1) % 4]);
+        page.curve_to(&at(ax + KAPPA * bx, ay + KAPPA * by), &at(bx + KAPPA * ax, by + KAPPA * ay), &at(bx, by));
+    }
+    page.close();
+}
+
+fn draw_polygon(page: &mut PdfPage, poly: &DrawPolygon, opacity: f32, vp: &Point) {
+    let trans = poly.rect.transform(vp);
+    let to_canvas = |p: &Point| trans.model_to_pixel(&p.to_vec4());
+    let outline = |page: &mut PdfPage| match poly.prim {
+        PrimType::Circle | PrimType::Ring => ellipse(page, &to_canvas),
+        prim => page.polygon(&prim.outline().iter().map(&to_canvas).collect::<Vec<Point>>())
+    };
+    if poly.fill {
+        page.fill_color(&poly.color, opacity);
+        outline(page);
+        page.fill();
+    }
+    //unfilled shapes always get at least a hairline outline
+    if !poly.fill || poly.line_width > 0. {
+        page.stroke_color(&poly.stroke_color, opacity);
+        page.line_width(f32::max(poly.line_width, 1.));
+        outline(page);
+        page.polyline(&poly.prim.detail().iter().map(&to_canvas).collect::<Vec<Point>>());
+        page.stroke();
+    }
+}
+
+//a shape filled and stroked like it's drawn on screen, a polygon's outline is taken to the canvas by its rect's model matrix
+pub fn draw_shape(page: &mut PdfPage, shape: &Shape, opacity: f32, vp: &Point) {
+    match shape {
+        Shape::Polygon(poly) => draw_polygon(page, poly, opacity, vp),
+        Shape::Line(line) => {
+            page.stroke_color(&line.color, opacity);
+            page.line_width(line.line_width);
+            page.polyline(&line.points());
+            page.stroke();
+            for marker in line.markers() {
+                draw_polygon(page, &marker, opacity, vp);
+            }
+        }
+    }
+}
+
+//text placed in `rect` is drawn in the rect's space, turned with it. `faces` has the document font of each face used
+pub fn draw_text(page: &mut PdfPage, text: &PlacedText, faces: &HashMap<usize, PdfFace>, rect: &RotateRect, opacity: f32, vp: &Point) {
+    if (text.glyphs.is_empty() && text.lines.is_empty()) || rect.size.x == 0. || rect.size.y == 0. {
+        return;
+    }
+    let model = glm::scale(&rect.transform(vp).model, &glm::vec3(1. / rect.size.x, 1. / rect.size.y, 1.));
+    page.save();
+    page.transform(&model);
+    let mut color = None;
+    for g in text.glyphs.iter() {
+        let face = match faces.get(&g.font) {
+            Some(face) => face,
+            None => continue
+        };
+        if color != Some(g.color) {
+            page.fill_color(&g.color, opacity);
+            page.stroke_color(&g.color, opacity);
+            color = Some(g.color);
+        }
+        page.glyph(face, g.id as u16, &g.text, &g.pen, g.size);
+    }
+    for (r, color) in text.lines.iter() {
+        page.fill_color(color, opacity);
+        page.polygon(&r.verts());
+        page.fill();
+    }
+    page.restore();
+}
+
+//six capital letters naming a subset, the same glyphs always get the same tag
+fn subset_tag(gids: &BTreeSet<u16>) -> String {
+    let hash = gids.iter().fold(0xcbf2_9ce4_8422_2325u64, |h, g| (h ^ *g as u64).wrapping_mul(0x100_0000_01b3));
+    (0..6).map(|i| (b'A' + ((hash >> (i * 5)) % 26) as u8) as char).collect()
+}
+
+//maps each glyph back to the text it was shaped from, so text can be copied and searched
+fn to_unicode(glyphs: &BTreeMap<u16, String>) -> String {
+    let mapped: Vec<String> = glyphs.iter().filter(|(_, text)| !text.is_empty()).map(|(gid, text)| {
+        let utf16: String = text.encode_utf16().map(|u| format!("{:04X}", u)).collect();
+        format!("<{:04X}> <{}>", gid, utf16)
+    }).collect();
+    let mut cmap = String::from("/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+        /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+        /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+        1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n");
+    //a bfchar block holds at most 100 mappings
+    for chunk in mapped.chunks(100) {
+        cmap.push_str(&format!("{} beginbfchar\n{}\nendbfchar\n", chunk.len(), chunk.join("\n")));
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap
+}
+
+//objects are numbered in the order they're written, the cross-reference table has the offset of each
+struct ObjWriter {
+    out: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl ObjWriter {
+    fn object(&mut self, body: &str) {
+        self.offsets.push(self.out.len());
+        self.out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", self.offsets.len(), body).as_bytes());
+    }
+    //`dict` is the stream's entries besides its length
+    fn stream(&mut self, dict: &str, data: &[u8]) {
+        self.offsets.push(self.out.len());
+        self.out.extend_from_slice(format!("{} 0 obj\n<< /Length {}{} >>\nstream\n", self.offsets.len(), data.len(), dict).as_bytes());
+        self.out.extend_from_slice(data);
+        self.out.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+}
+
+//a PDF of the diagram's pages. streams aren't compressed, there's no deflate in the dependencies
+pub struct PdfDocument {
+    fonts: Vec<PdfFont>,
+    pages: Vec<PdfPage>,
+}
+
+impl PdfDocument {
+    pub const PATH: &'static str = "diagram.pdf";
+
+    pub fn new() -> Self {
+        PdfDocument { fonts: Vec::new(), pages: Vec::new() }
+    }
+    //faces loaded from the same file share its font, unless one is slanted to synthesize its italic
+    pub fn add_font(&mut self, data: &'static [u8], family: &str, slant: f32) -> Result<usize, String> {
+        if let Some(i) = self.fonts.iter().position(|f| std::ptr::eq(f.data, data) && f.slant == slant) {
+            return Ok(i);
+        }
+        TrueType::parse(data).map_err(|e| format!("Could not embed font {}: {}", family, e))?;
+        let name: String = family.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        let name = if name.is_empty() { "Font".to_string() } else { name };
+        let name = if slant != 0. { name + "-Oblique" } else { name };
+        self.fonts.push(PdfFont { name, data, slant, glyphs: BTreeMap::new() });
+        Ok(self.fonts.len() - 1)
+    }
+    pub fn add_page(&mut self, page: PdfPage) {
+        for (font, glyphs) in page.glyphs.iter() {
+            for (gid, text) in glyphs.iter() {
+                let glyph_text = self.fonts[*font].glyphs.entry(*gid).or_default();
+                if glyph_text.is_empty() {
+                    *glyph_text = text.clone();
+                }
+            }
+        }
+        self.pages.push(page);
+    }
+    //the catalog and page tree, each page and its content, then five objects for each font
+    pub fn write(&self) -> Result<Vec<u8>, String> {
+        let mut w = ObjWriter { out: b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n".to_vec(), offsets: Vec::new() };
+        let page_obj = |i: usize| 3 + 2 * i;
+        let font_obj = |i: usize| 3 + 2 * self.pages.len() + 5 * i;
+        w.object("<< /Type /Catalog /Pages 2 0 R >>");
+        let kids: Vec<String> = (0..self.pages.len()).map(|i| format!("{} 0 R", page_obj(i))).collect();
+        w.object(&format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), self.pages.len()));
+        for (i, page) in self.pages.iter().enumerate() {
+            let fonts: Vec<String> = page.glyphs.keys().map(|f| format!("/F{} {} 0 R", f, font_obj(*f))).collect();
+            let alphas: Vec<String> = page.alphas.iter().enumerate()
+                .map(|(n, (key, alpha))| format!("/GS{} << /{} {} >>", n, key, num(*alpha))).collect();
+            let size = page.size();
+            w.object(&format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << {} >> /ExtGState << {} >> >> /Contents {} 0 R >>",
+                num(size.x), num(size.y), fonts.join(" "), alphas.join(" "), page_obj(i) + 1));
+            w.stream("", page.content.as_bytes());
+        }
+        for (i, font) in self.fonts.iter().enumerate() {
+            self.write_font(&mut w, font, font_obj(i))?;
+        }
+        let xref = w.out.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", w.offsets.len() + 1);
+        for offset in w.offsets.iter() {
+            trailer.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        trailer.push_str(&format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", w.offsets.len() + 1, xref));
+        w.out.extend_from_slice(trailer.as_bytes());
+        Ok(w.out)
+    }
+    //glyphs are shown by glyph ID. fonts with TrueType outlines are subset to the glyphs used, others are embedded whole
+    fn write_font(&self, w: &mut ObjWriter, font: &PdfFont, first: usize) -> Result<(), String> {
+        let tt = TrueType::parse(font.data)?;
+        let gids: BTreeSet<u16> = font.glyphs.keys().cloned().collect();
+        let subset = if tt.has_glyf() { Some(tt.subset(&gids)?) } else { None };
+        let base_font = match subset {
+            Some(_) => format!("{}+{}", subset_tag(&gids), font.name),
+            None => font.name.clone()
+        };
+        let em = |v: i32| v * 1000 / tt.units_per_em as i32;
+        let widths: Vec<String> = gids.iter().map(|gid| format!("{} [{}]", gid, em(tt.advance(*gid) as i32))).collect();
+        let (cid_type, cid_to_gid) = if subset.is_some() { ("CIDFontType2", " /CIDToGIDMap /Identity") } else { ("CIDFontType0", "") };
+        let bbox: Vec<String> = tt.bbox.iter().map(|v| em(*v as i32).to_string()).collect();
+        let (ascent, descent) = (em(tt.ascent as i32), em(tt.descent as i32));
+        let file_key = if subset.is_some() { "FontFile2" } else { "FontFile3" };
+        //a slanted font leans by its slant, right is a negative angle
+        let italic_angle = if font.slant != 0. { -font.slant.atan().to_degrees() } else { tt.italic_angle };
+        //glyphs are picked by ID so the font is symbolic to viewers, and italic when it leans
+        let flags = if italic_angle != 0. { 4 | 64 } else { 4 };
+
+        w.object(&format!("<< /Type /Font /Subtype /Type0 /BaseFont /{} /Encoding /Identity-H /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>",
+            base_font, first + 1, first + 4));
+        w.object(&format!("<< /Type /Font /Subtype /{} /BaseFont /{} /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> /FontDescriptor {} 0 R{} /W [{}] >>",
+            cid_type, base_font, first + 2, cid_to_gid, widths.join(" ")));
+        w.object(&format!("<< /Type /FontDescriptor /FontName /{} /Flags {} /FontBBox [{}] /ItalicAngle {} /Ascent {} /Descent {} /CapHeight {} /StemV 80 /{} {} 0 R >>",
+            base_font, flags, bbox.join(" "), num(italic_angle), ascent, descent, ascent, file_key, first + 3));
+        match subset {
+            Some(data) => w.stream(&format!(" /Length1 {}", data.len()), &data),
+            None => w.stream(" /Subtype /OpenType", font.data)
+        }
+        w.stream("", to_unicode(&font.glyphs).as_bytes());
+        Ok(())
+    }
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let data = self.write()?;
+        std::fs::write(path, data).map_err(|e| format!("Could not write {}: {}", path, e))
+    }
+}
+
+impl Default for PdfDocument {
+    fn default() -> Self {
+        PdfDocument::new()
+    }
+}
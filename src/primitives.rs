@@ -9,12 +9,15 @@ use std::ffi::CString;
 use std::f32::{self, consts::PI};
 use PrimType as PT;
 use crate::render_gl::{Shader, Program, SendUniforms, SendUniform};
-use crate::hexcolor::HexColor;
+use crate::hexcolor::{HexColor, hsv_to_rgb};
+use crate::shape_kind::{self, ShapeKindId};
+use crate::geometry::{self, HIT_TOLERANCE};
 use sem_graph_derive::SendUniforms;
 use macro_attr::{macro_attr, macro_attr_impl};
 use newtype_derive::*;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{RefCell, Cell};
+use std::ops::Range;
 
 
 type PrimMap = HashMap<PrimType, GLuint>;
@@ -22,7 +25,9 @@ type ProgMap = HashMap<PrimType, Rc<Program>>;
 
 pub fn prim_map() -> PrimMap {
     let mut m = HashMap::new();
-    for prim in &[PT::Triangle, PT::Circle, PT::Rect, PT::Ring, PT::Line, PT::HexColor] {
+    for prim in &[PT::Triangle, PT::Circle, PT::Rect, PT::Ring, PT::Line, PT::HexColor, PT::ColorWheel,
+                  PT::RoundRect(PT::DEFAULT_RADIUS), PT::Diamond, PT::Polygon(6), PT::Parallelogram, PT::Cylinder,
+                  PT::Star(5)] {
         m.insert(*prim, prim.buffer_data());
     }
     m
@@ -64,11 +69,13 @@ pub fn prog_map() -> ProgMap {
     let shapecolor_prog = Rc::new(Program::from_shaders(shaders.as_ref()).unwrap());
 
     let mut m = HashMap::new();
-    for prim in &[PrimType::Triangle, PT::Circle, PT::Rect, PT::Ring] {
+    //parameterized prims that aren't in the map are drawn with the shape program too, see DrawCtx::program
+    for prim in &[PrimType::Triangle, PT::Circle, PT::Rect, PT::Ring, PT::Diamond, PT::Parallelogram, PT::Cylinder] {
         m.insert(*prim, Rc::clone(&shape_prog));
     }
     m.insert(PT::Line, line_prog);
-    m.insert(PT::HexColor, shapecolor_prog);
+    m.insert(PT::HexColor, Rc::clone(&shapecolor_prog));
+    m.insert(PT::ColorWheel, shapecolor_prog);
 
     m
 }
@@ -81,11 +88,53 @@ pub enum PrimType {
     Rect,
     Line,
     HexColor,
+    ColorWheel,
+    RoundRect(u8),  // Corner radius in percent of the width and height
+    Diamond,
+    Polygon(u8),    // Number of sides
+    Parallelogram,
+    Cylinder,
+    Star(u8),       // Number of points
+    Custom(ShapeKindId),
 }
 
 const NCIRCLE_VERTS: usize = 30;
+const NCORNER_VERTS: usize = 8;
+//horizontal offset of the slanted sides of a parallelogram
+const PARALLELOGRAM_SKEW: f32 = 0.25;
+//half the height of the ellipses at the ends of a cylinder
+const CYLINDER_CAP: f32 = 0.1;
+//inner radius of a star relative to its outer radius
+const STAR_INNER: f32 = 0.4;
+
+//filled as a triangle fan around the center, so the outline is the rest of the verts closed back on the first
+fn fan(outline: Vec<Point>) -> Vec<f32> {
+    let mut v = vec![0.5, 0.5];
+    v.extend(outline.iter().chain(outline.first()).flat_map(|p| vec![p.x, p.y]));
+    v
+}
+
+//points evenly around a circle with every other point at `inner` of the radius, stretched to fill the unit square
+fn radial(n: usize, inner: f32) -> Vec<Point> {
+    let start = if n % 2 == 0 { PI / n as f32 - PI / 2. } else { -PI / 2. };
+    let pts: Vec<Point> = (0..n).map(|i| {
+        let r = if i % 2 == 1 { inner } else { 1. };
+        let a = start + 2. * PI * i as f32 / n as f32;
+        Point::new(r * f32::cos(a), r * f32::sin(a))
+    }).collect();
+    let bb = Rect::bounding_box(&pts);
+    pts.iter().map(|p| Point::new((p.x - bb.c1.x) / bb.width(), (p.y - bb.c1.y) / bb.height())).collect()
+}
+
+fn arc(center: Point, rad: Point, angles: Range<f32>, n: usize) -> impl Iterator<Item=Point> {
+    (0..n).map(move |i| {
+        let a = angles.start + (angles.end - angles.start) * i as f32 / (n - 1) as f32;
+        center + Point::new(rad.x * f32::cos(a), rad.y * f32::sin(a))
+    })
+}
 
 impl PrimType {
+    pub const DEFAULT_RADIUS: u8 = 20;
     fn verts(&self) -> Vec<f32> {
         match self {
             PT::Triangle => { //isosceles
@@ -129,17 +178,93 @@ impl PrimType {
                     colors[i][0] / 255., colors[i][1] / 255., colors[i][2] / 255.]
                 ).flatten().collect()
             }
+            PT::ColorWheel => { //white center fading out to fully saturated hues
+                let n = NCIRCLE_VERTS as f32;
+                let mut v = vec![0.5, 0.5, 1., 1., 1.];
+                v.extend(PT::Ring.verts().chunks(2).enumerate().map(|(i, c)| {
+                    let (r, g, b) = hsv_to_rgb(360. * i as f32 / (n-1.), 1., 1.);
+                    vec![c[0], c[1], r, g, b]
+                }).flatten());
+                v
+            }
+            PT::RoundRect(_) | PT::Diamond | PT::Polygon(_) | PT::Parallelogram | PT::Star(_) => fan(self.outline()),
+            PT::Custom(id) => {
+                //the triangles, then the outline closed back on its first corner
+                shape_kind::get(*id).map(|kind| {
+                    let outline = kind.outline();
+                    kind.tessellate().iter().chain(outline.iter()).chain(outline.first())
+                        .flat_map(|p| vec![p.x, p.y]).collect()
+                }).unwrap_or_default()
+            }
+            PT::Cylinder => {
+                //the silhouette, then the front of the top ellipse so a single line strip outlines it
+                let mut pts = self.outline();
+                pts.push(pts[0]);
+                pts.extend(self.detail());
+                let mut v = vec![0.5, 0.5];
+                v.extend(pts.iter().flat_map(|p| vec![p.x, p.y]));
+                v
+            }
+        }
+    }
+    //corners in order around the shape, the edge hit tests measure the distance to
+    pub fn outline(&self) -> Vec<Point> {
+        match self {
+            PT::Triangle | PT::HexColor => PT::Triangle.verts().chunks(2).map(|c| Point::new(c[0], c[1])).collect(),
+            PT::Rect => PT::Rect.verts().chunks(2).map(|c| Point::new(c[0], c[1])).collect(),
+            PT::Circle | PT::Ring | PT::ColorWheel => {
+                PT::Ring.verts().chunks(2).take(NCIRCLE_VERTS - 1).map(|c| Point::new(c[0], c[1])).collect()
+            }
+            PT::Cylinder => {
+                let rad = Point::new(0.5, CYLINDER_CAP);
+                arc(Point::new(0.5, CYLINDER_CAP), rad, PI..2.*PI, NCIRCLE_VERTS / 2)
+                    .chain(arc(Point::new(0.5, 1. - CYLINDER_CAP), rad, 0. ..PI, NCIRCLE_VERTS / 2)).collect()
+            }
+            PT::Custom(id) => shape_kind::get(*id).map(|kind| kind.outline()).unwrap_or_default(),
+            PT::RoundRect(pct) => {
+                let r = f32::min(*pct as f32, 50.) / 100.;
+                let corners = [(1. - r, 1. - r, 0.), (r, 1. - r, PI / 2.), (r, r, PI), (1. - r, r, 3. * PI / 2.)];
+                corners.iter().flat_map(|(x, y, a)|
+                    arc(Point::new(*x, *y), Point::new(r, r), *a..*a + PI / 2., NCORNER_VERTS)
+                ).collect()
+            }
+            PT::Diamond => vec![Point::new(0.5, 0.), Point::new(1., 0.5), Point::new(0.5, 1.), Point::new(0., 0.5)],
+            PT::Polygon(n) => radial(usize::max(*n as usize, 3), 1.),
+            PT::Parallelogram => vec![
+                Point::new(PARALLELOGRAM_SKEW, 0.), Point::new(1., 0.),
+                Point::new(1. - PARALLELOGRAM_SKEW, 1.), Point::new(0., 1.)
+            ],
+            PT::Star(n) => radial(2 * usize::max(*n as usize, 3), STAR_INNER),
+            _ => Vec::new()
+        }
+    }
+    //lines stroked inside the outline, the front of a cylinder's top
+    pub fn detail(&self) -> Vec<Point> {
+        match self {
+            PT::Cylinder => arc(Point::new(0.5, CYLINDER_CAP), Point::new(0.5, CYLINDER_CAP), PI..0., NCIRCLE_VERTS / 2).collect(),
+            _ => Vec::new()
+        }
+    }
+    //how the fill or the outline of a shape is drawn, and the verts it's drawn from
+    pub fn draw_range(&self, outline: bool) -> (GLenum, Range<usize>) {
+        match self {
+            PT::RoundRect(_) | PT::Diamond | PT::Polygon(_) | PT::Parallelogram | PT::Cylinder | PT::Star(_) if outline => (gl::LINE_STRIP, 1..self.size()),
+            PT::Custom(id) => {
+                let ntris = shape_kind::get(*id).map_or(0, |kind| kind.tessellate().len());
+                if outline { (gl::LINE_STRIP, ntris..self.size()) } else { (gl::TRIANGLES, 0..ntris) }
+            }
+            _ => (self.mode(), 0..self.size())
         }
     }
     fn buffer_data(&self) -> GLuint {
         unsafe {
             match self {
-                PT::HexColor => { HexColor::buffer_verts(&self.verts().as_slice()) }
+                PT::HexColor | PT::ColorWheel => { HexColor::buffer_verts(&self.verts().as_slice()) }
                 _ => buffer_verts(&self.verts().as_slice()) 
             }
         }
     }
-    fn mode(&self) -> GLenum {
+    pub fn mode(&self) -> GLenum {
         match self {
             PT::Triangle => gl::TRIANGLES,
             PT::Rect => gl::QUADS,
@@ -147,6 +272,9 @@ impl PrimType {
             PT::Ring => gl::LINE_STRIP, 
             PT::Line => gl::POINTS,
             PT::HexColor=> gl::TRIANGLES,
+            PT::ColorWheel => gl::TRIANGLE_FAN,
+            PT::RoundRect(_) | PT::Diamond | PT::Polygon(_) | PT::Parallelogram | PT::Cylinder | PT::Star(_) => gl::TRIANGLE_FAN,
+            PT::Custom(_) => gl::TRIANGLES,
         }
     }
     pub fn size(&self) -> usize {
@@ -156,7 +284,11 @@ impl PrimType {
             PT::Circle => NCIRCLE_VERTS + 1, 
             PT::Ring => NCIRCLE_VERTS, 
             PT::Line => 1,
-            PT::HexColor => 3 
+            PT::HexColor => 3,
+            PT::ColorWheel => NCIRCLE_VERTS + 1,
+            PT::Cylinder => 3 * (NCIRCLE_VERTS / 2) + 2,
+            PT::Custom(_) => self.verts().len() / 2,
+            prim => prim.outline().len() + 2,
         }
     }
     fn in_bounds(&self, p: &Point) -> bool {
@@ -164,7 +296,7 @@ impl PrimType {
             PT::Triangle => {
                 p.x >= 0.0 && p.x <= 1.0 && p.y >= f32::abs(p.x - 0.5) && p.y <= 1.0
             }
-            PT::Circle | PT::Ring | PT::HexColor => {
+            PT::Circle | PT::Ring | PT::HexColor | PT::ColorWheel => {
                 (*p - Point{x: 0.5, y: 0.5}).mag() <= 0.5
             }
             PT::Rect => {
@@ -173,6 +305,84 @@ impl PrimType {
             PT::Line => {
                 false
             }
+            PT::RoundRect(pct) => {
+                let r = f32::min(*pct as f32, 50.) / 100.;
+                //distance past the inner rect the corners are centered on
+                let dx = f32::max(f32::abs(p.x - 0.5) - (0.5 - r), 0.);
+                let dy = f32::max(f32::abs(p.y - 0.5) - (0.5 - r), 0.);
+                PT::Rect.in_bounds(p) && dx * dx + dy * dy <= r * r
+            }
+            PT::Diamond => {
+                f32::abs(p.x - 0.5) + f32::abs(p.y - 0.5) <= 0.5
+            }
+            PT::Polygon(_) | PT::Parallelogram | PT::Star(_) => {
+                geometry::in_polygon(&self.outline(), p)
+            }
+            PT::Cylinder => {
+                //between the top of the top ellipse and the bottom of the bottom one
+                let dy = CYLINDER_CAP * f32::sqrt(f32::max(1. - (2. * p.x - 1.).powi(2), 0.));
+                p.x >= 0.0 && p.x <= 1.0 && p.y >= CYLINDER_CAP - dy && p.y <= 1. - CYLINDER_CAP + dy
+            }
+            PT::Custom(id) => {
+                shape_kind::get(*id).is_some_and(|kind| kind.in_bounds(p))
+            }
+        }
+    }
+    //stable name a shape's prim is saved as, custom kinds go by the name they were registered with
+    pub fn name(&self) -> String {
+        match self {
+            PT::Triangle => "triangle".to_string(),
+            PT::Circle => "circle".to_string(),
+            PT::Ring => "ring".to_string(),
+            PT::Rect => "rect".to_string(),
+            PT::Line => "line".to_string(),
+            PT::HexColor => "hex_color".to_string(),
+            PT::ColorWheel => "color_wheel".to_string(),
+            PT::RoundRect(r) => format!("round_rect({})", r),
+            PT::Diamond => "diamond".to_string(),
+            PT::Polygon(n) => format!("polygon({})", n),
+            PT::Parallelogram => "parallelogram".to_string(),
+            PT::Cylinder => "cylinder".to_string(),
+            PT::Star(n) => format!("star({})", n),
+            PT::Custom(id) => shape_kind::get(*id).map_or(format!("custom({})", id), |kind| kind.name().to_string()),
+        }
+    }
+    pub fn from_name(name: &str) -> Option<PrimType> {
+        //parameterized prims are written as name(param)
+        let (base, param) = match (name.find('('), name.strip_suffix(')')) {
+            (Some(i), Some(rest)) => (&name[..i], rest[i + 1..].parse::<u8>().ok()),
+            _ => (name, None)
+        };
+        match (base, param) {
+            ("triangle", None) => Some(PT::Triangle),
+            ("circle", None) => Some(PT::Circle),
+            ("ring", None) => Some(PT::Ring),
+            ("rect", None) => Some(PT::Rect),
+            ("line", None) => Some(PT::Line),
+            ("hex_color", None) => Some(PT::HexColor),
+            ("color_wheel", None) => Some(PT::ColorWheel),
+            ("round_rect", Some(r)) => Some(PT::RoundRect(r)),
+            ("diamond", None) => Some(PT::Diamond),
+            ("polygon", Some(n)) => Some(PT::Polygon(n)),
+            ("parallelogram", None) => Some(PT::Parallelogram),
+            ("cylinder", None) => Some(PT::Cylinder),
+            ("star", Some(n)) => Some(PT::Star(n)),
+            _ => shape_kind::find(name).map(PT::Custom)
+        }
+    }
+    //sides of a polygon, points of a star
+    pub fn sides(&self) -> Option<u8> {
+        match self {
+            PT::Polygon(n) | PT::Star(n) => Some(*n),
+            _ => None
+        }
+    }
+    pub fn with_sides(&self, n: u8) -> PrimType {
+        let n = u8::max(u8::min(n, 24), 3);
+        match self {
+            PT::Polygon(_) => PT::Polygon(n),
+            PT::Star(_) => PT::Star(n),
+            prim => *prim
         }
     }
 }
@@ -322,6 +532,12 @@ impl From<Degrees> for Radians {
     }
 }
 
+impl From<Radians> for Degrees {
+    fn from(rad: Radians) -> Self {
+        Degrees(rad.0 * 180. / PI)
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub struct RotateRect {
     pub offset: Point,
@@ -369,7 +585,7 @@ impl RotateRect {
         v
     }
     pub fn in_bounds(&self, p: &Point, vp: &Point) -> bool {
-        PrimType::Rect.in_bounds(&self.transform(vp).pixel_to_model(p).into())
+        geometry::polygon_hit(&self.verts(vp), p, HIT_TOLERANCE)
     }
     pub fn builder(&self) -> ShapeBuilder {
         ShapeBuilder { p: self.to_poly(), ..ShapeBuilder::new() }
@@ -435,12 +651,86 @@ impl Line {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LineMarker {
+    NoMarker,
+    Arrow,
+    Dot,
+}
+
+impl LineMarker {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineMarker::NoMarker => "none",
+            LineMarker::Arrow => "arrow",
+            LineMarker::Dot => "dot",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<LineMarker> {
+        match name {
+            "none" => Some(LineMarker::NoMarker),
+            "arrow" => Some(LineMarker::Arrow),
+            "dot" => Some(LineMarker::Dot),
+            _ => None
+        }
+    }
+}
+
+//how the path between a line's end points is drawn
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LineRoute {
+    Straight,       // Straight segments through the waypoints
+    Orthogonal,     // Horizontal and vertical segments around other shapes
+}
+
+impl LineRoute {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineRoute::Straight => "straight",
+            LineRoute::Orthogonal => "orthogonal",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<LineRoute> {
+        match name {
+            "straight" => Some(LineRoute::Straight),
+            "orthogonal" => Some(LineRoute::Orthogonal),
+            _ => None
+        }
+    }
+    pub fn next(&self) -> LineRoute {
+        match self {
+            LineRoute::Straight => LineRoute::Orthogonal,
+            LineRoute::Orthogonal => LineRoute::Straight,
+        }
+    }
+}
+
+//where a line's label sits, it moves along with the line's end points
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LabelPlacement {
+    pub t: f32,                 // Fraction of the way from p1 to p2
+    pub offset: f32,            // Distance from the line, positive is to the left of p1 to p2
+    pub follow_line: bool,      // Rotated to the line's direction, otherwise kept horizontal
+}
+
+impl Default for LabelPlacement {
+    fn default() -> Self {
+        LabelPlacement { t: 0.5, offset: 0., follow_line: false }
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct DrawLine {
     pub p1: Point, 
     pub p2: Point,
     pub line_width: f32,
-    pub color: glm::Vec4
+    pub color: glm::Vec4,
+    pub start_marker: LineMarker,
+    pub end_marker: LineMarker,
+    pub label: LabelPlacement,
+    pub route: LineRoute,
+    pub waypoints: Vec<Point>,      // Points pinned by the user that the path goes through
+    pub bends: Vec<Point>,          // Corners of the orthogonal route found between the ends
 }
 
 impl DrawLine {
@@ -456,21 +746,107 @@ impl DrawLine {
     pub fn max_y(&mut self) -> &mut f32 {
         if self.p1.y > self.p2.y { &mut self.p1.y } else { &mut self.p2.y }
     }
-    pub fn draw(&self, ctx: &DrawCtx) {
+    //the end points with the corners of the path between them
+    pub fn points(&self) -> Vec<Point> {
+        let inner = match self.route {
+            LineRoute::Straight => &self.waypoints,
+            LineRoute::Orthogonal => &self.bends,
+        };
+        std::iter::once(self.p1).chain(inner.iter().cloned()).chain(std::iter::once(self.p2)).collect()
+    }
+    pub fn drag(&mut self, off: &Point) {
+        self.p1 += *off;
+        self.p2 += *off;
+        self.waypoints.iter_mut().chain(self.bends.iter_mut()).for_each(|p| *p += *off);
+    }
+    fn draw_segment(&self, p1: Point, p2: Point, ctx: &DrawCtx) {
         let ptype = &PrimType::Line;
         ctx.prog_map[ptype].set_used();
-        let trans = LineTransform::new(self, &ctx.viewport);
+        let trans = LineTransform::new(&p1, &p2, &ctx.viewport);
         let prog_id = ctx.prog_map[&ptype].id();
-        let vao = ctx.prim_map[&ptype];
+        let vao = ctx.vao(ptype);
         let line_width = self.line_width; 
         unsafe {
             trans.send_uniforms(prog_id).unwrap();
             self.color.send_uniform(prog_id, "color").unwrap();
+            ctx.opacity.get().send_uniform(prog_id, "opacity").unwrap();
             gl::LineWidth(line_width as GLfloat);
             gl::BindVertexArray(vao);
             gl::DrawArrays(gl::POINTS, 0, 1);
         }
     }
+    pub fn draw(&self, ctx: &DrawCtx) {
+        let pts = self.points();
+        for seg in pts.windows(2) {
+            self.draw_segment(seg[0], seg[1], ctx);
+        }
+        for marker in self.markers() {
+            marker.draw(ctx);
+        }
+    }
+    //arrow heads and dots at the ends, filled with the line's color
+    pub fn markers(&self) -> Vec<DrawPolygon> {
+        let pts = self.points();
+        let n = pts.len();
+        let ends = [(&self.start_marker, &pts[0], &pts[1]), (&self.end_marker, &pts[n - 1], &pts[n - 2])];
+        ends.iter().filter_map(|(marker, tip, from)| self.marker(marker, tip, from)).collect()
+    }
+    fn marker(&self, marker: &LineMarker, tip: &Point, from: &Point) -> Option<DrawPolygon> {
+        let len = tip.dist(from);
+        if len == 0. {
+            return None;
+        }
+        let dir = (*tip - *from) / len;
+        let size = 4. * self.line_width + 6.;
+        let rect = match marker {
+            LineMarker::NoMarker => return None,
+            LineMarker::Arrow => {
+                //the triangle's apex points up in model space, turn it to face along the line
+                let size = Point::new(size, size);
+                let center = *tip - dir * size / 2.;
+                RotateRect::new(center - size / 2., size, Radians(dir.x.atan2(-dir.y)))
+            }
+            LineMarker::Dot => RotateRect::new(*tip - Point::new(size, size) / 2., Point::new(size, size), Radians(0.))
+        };
+        let prim = if let LineMarker::Arrow = marker { PT::Triangle } else { PT::Circle };
+        Some(DrawPolygon { prim, rect, color: self.color, stroke_color: self.color, ..DrawPolygon::default() })
+    }
+    //rect of a label of `size` centered on its anchor, a label rotated along the line is kept upright
+    pub fn label_rect(&self, size: &Point) -> RotateRect {
+        //the anchor is `t` of the way along the whole path, on the segment it falls on
+        let pts = self.points();
+        let total: f32 = pts.windows(2).map(|s| s[0].dist(&s[1])).sum();
+        let mut along = total * self.label.t;
+        let (mut a, mut b) = (pts[0], pts[1]);
+        for seg in pts.windows(2) {
+            let len = seg[0].dist(&seg[1]);
+            a = seg[0];
+            b = seg[1];
+            if along <= len {
+                break;
+            }
+            along -= len;
+        }
+        let dir = b - a;
+        let len = a.dist(&b);
+        let (unit, normal) = if len > 0. {
+            (dir / len, Point::new(dir.y / len, -dir.x / len))
+        } else {
+            (Point::new(1., 0.), Point::new(0., -1.))
+        };
+        let along = f32::min(along, len);
+        let anchor = a + Point::new(unit.x * along, unit.y * along) + Point::new(normal.x * self.label.offset, normal.y * self.label.offset);
+        let mut angle = if self.label.follow_line && len > 0. { dir.y.atan2(dir.x) } else { 0. };
+        if angle > PI / 2. {
+            angle -= PI;
+        }
+        else if angle < -PI / 2. {
+            angle += PI;
+        }
+        let mut rect = RotateRect::new(anchor - *size / 2., *size, Radians(0.));
+        rect.set_radians(Radians(angle));
+        rect
+    }
 }
 
 impl Default for DrawLine {
@@ -479,7 +855,13 @@ impl Default for DrawLine {
             p1: Point::origin(),
             p2: Point::origin(),
             line_width: 3.,
-            color: glm::vec4(0., 0., 0., 1.)
+            color: glm::vec4(0., 0., 0., 1.),
+            start_marker: LineMarker::NoMarker,
+            end_marker: LineMarker::NoMarker,
+            label: LabelPlacement::default(),
+            route: LineRoute::Straight,
+            waypoints: Vec::new(),
+            bends: Vec::new(),
         }
     }
 }
@@ -489,7 +871,9 @@ pub struct DrawPolygon {
     pub prim: PrimType,
     pub fill: bool,
     pub rect: RotateRect,
-    pub color: glm::Vec4
+    pub color: glm::Vec4,
+    pub stroke_color: glm::Vec4,
+    pub line_width: f32
 }
 
 impl Default for DrawPolygon {
@@ -498,7 +882,9 @@ impl Default for DrawPolygon {
             prim: PT::Triangle,
             rect: RotateRect::default(),
             fill: true,
-            color: glm::vec4(0., 0., 0., 1.)
+            color: glm::vec4(0., 0., 0., 1.),
+            stroke_color: glm::vec4(0., 0., 0., 1.),
+            line_width: 0.
         }
     }
 }
@@ -517,19 +903,44 @@ impl DrawPolygon {
         }).collect();
         v
     }
-    pub fn draw(&self, ctx: &DrawCtx) {
-        let ptype = &self.prim;
-        ctx.prog_map[ptype].set_used();
+    //the prim's outline in pixels
+    pub fn outline(&self, vp: &Point) -> Vec<Point> {
+        let trans = self.rect.transform(vp);
+        self.prim.outline().iter().map(|p| trans.model_to_pixel(&p.to_vec4())).collect()
+    }
+    pub fn set_fill(&mut self, fill: bool) {
+        self.fill = fill;
+        self.prim = match self.prim {
+            PT::Circle if !fill => PT::Ring,
+            PT::Ring if fill => PT::Circle,
+            prim => prim
+        };
+    }
+    fn draw_prim(&self, ptype: &PrimType, poly_mode: GLuint, color: &glm::Vec4, ctx: &DrawCtx) {
+        let prog = ctx.program(ptype);
+        prog.set_used();
         let trans = self.rect.transform(&ctx.viewport);
-        let prog_id = ctx.prog_map[ptype].id();
-        let vao = ctx.prim_map[ptype];
-        let poly_mode: GLuint = if self.fill { gl::FILL } else { gl::LINE };
+        let prog_id = prog.id();
+        let vao = ctx.vao(ptype);
+        let (mode, range) = ptype.draw_range(poly_mode == gl::LINE);
         unsafe {
             gl::PolygonMode(gl::FRONT_AND_BACK, poly_mode); 
             trans.send_uniforms(prog_id).unwrap();
-            self.color.send_uniform(prog_id, "color").unwrap();
+            color.send_uniform(prog_id, "color").unwrap();
+            ctx.opacity.get().send_uniform(prog_id, "opacity").unwrap();
             gl::BindVertexArray(vao);
-            gl::DrawArrays(ptype.mode(), 0, ptype.size() as i32);
+            gl::DrawArrays(mode, range.start as i32, range.len() as i32);
+        }
+    }
+    pub fn draw(&self, ctx: &DrawCtx) {
+        if self.fill {
+            self.draw_prim(&self.prim, gl::FILL, &self.color, ctx);
+        }
+        //unfilled shapes always get at least a hairline outline
+        if !self.fill || self.line_width > 0. {
+            let outline = if self.prim == PT::Circle { PT::Ring } else { self.prim };
+            unsafe { gl::LineWidth(f32::max(self.line_width, 1.) as GLfloat); }
+            self.draw_prim(&outline, gl::LINE, &self.stroke_color, ctx);
         }
     }
 }
@@ -598,10 +1009,10 @@ struct LineTransform {
 }
 
 impl LineTransform {
-    fn new(l: &DrawLine, vp: &Point) -> Self {
+    fn new(p1: &Point, p2: &Point, vp: &Point) -> Self {
         LineTransform {
-            point1: l.p1.to_vec2(),
-            point2: l.p2.to_vec2(),
+            point1: p1.to_vec2(),
+            point2: p2.to_vec2(),
             projection: glm::ortho(0., vp.x, vp.y, 0., -1., 1.)
         }
     }
@@ -611,7 +1022,7 @@ pub trait InBounds {
     fn in_bounds(&self, p: &Point, vp: &Point) -> bool;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Rect {
     //upper left corner, lower right corner
     pub c1: Point, pub c2: Point
@@ -679,6 +1090,11 @@ impl Rect {
     pub fn bot_center(&self) -> Point {
         Point {x: (self.min_x() + self.max_x()) / 2., y: *self.max_y() }
     }
+    //exactly inside. ui rects are hit tested with this too, only canvas shapes get the hit tolerance
+    pub fn contains(&self, p: &Point) -> bool {
+        p.x >= f32::min(self.c1.x, self.c2.x) && p.x <= f32::max(self.c1.x, self.c2.x)
+            && p.y >= f32::min(self.c1.y, self.c2.y) && p.y <= f32::max(self.c1.y, self.c2.y)
+    }
     pub fn min_x(&self) -> &f32 {
         &self.c1.x
     }
@@ -695,11 +1111,7 @@ impl Rect {
 
 impl InBounds for Rect {
     fn in_bounds(&self, p: &Point, _: &Point) -> bool {
-        let min_x = if self.c1.x < self.c2.x { &self.c1.x } else { &self.c2.x };
-        let max_x = if self.c1.x < self.c2.x { &self.c2.x } else { &self.c1.x };
-        let min_y = if self.c1.y < self.c2.y { &self.c1.y } else { &self.c2.y };
-        let max_y = if self.c1.y < self.c2.y { &self.c2.y } else { &self.c1.y };
-        p.x >= *min_x && p.x <= *max_x && p.y >= *min_y && p.y <= *max_y
+        self.contains(p)
     }
 }
 
@@ -707,27 +1119,13 @@ impl InBounds for DrawPolygon {
     fn in_bounds(&self, p: &Point, vp: &Point) -> bool {
         let trans = self.rect.transform(vp);
         self.prim.in_bounds(&trans.pixel_to_model(p).into())
+            || geometry::dist_to_polygon_edge(p, &self.outline(vp)) <= self.line_width / 2. + HIT_TOLERANCE
     }
 }
 
 impl InBounds for DrawLine {
-     fn in_bounds(&self, p: &Point, vp: &Point) -> bool {
-        let mut c1 = self.p1;
-        let mut c2 = self.p2;
-        if c1.x == c2.x {
-            c1.x -= self.line_width / 2.;
-            c2.x += self.line_width / 2.;
-        }
-        if c2.y == c2.y {
-            c1.y -= self.line_width / 2.;
-            c2.y += self.line_width / 2.;
-        }
-        if !(Rect {c1, c2}).in_bounds(p, vp) {
-            return false
-        }
-        let l = Line::from_pts(self.p1, self.p2);
-        let dst = l.dist_to_pt(p);
-        dst <= self.line_width
+    fn in_bounds(&self, p: &Point, _: &Point) -> bool {
+        geometry::dist_to_polyline(p, &self.points()) <= self.line_width / 2. + HIT_TOLERANCE
     }
 }
 
@@ -743,9 +1141,7 @@ impl Shape {
             Shape::Polygon(ref draw_poly) => {
                 draw_poly.verts(vp)
             }
-            Shape::Line(draw_line) => {
-                vec![draw_line.p1, draw_line.p2]
-            }
+            Shape::Line(draw_line) => draw_line.points()
         }
     }
     pub fn rect(&self) -> RotateRect {
@@ -779,14 +1175,28 @@ impl InBounds for Shape {
 }
 
 pub struct DrawCtx {
-    pub prim_map: PrimMap,
+    pub prim_map: RefCell<PrimMap>,
     pub prog_map: ProgMap,
     pub viewport: Point,
+    pub opacity: Cell<f32>,     // Alpha multiplier of the layer being drawn
 }
 
 impl DrawCtx {
     pub fn new(viewport: &Point) -> DrawCtx {
-        DrawCtx { prim_map: prim_map(), prog_map: prog_map(), viewport: *viewport }
+        DrawCtx { prim_map: RefCell::new(prim_map()), prog_map: prog_map(), viewport: *viewport, opacity: Cell::new(1.) }
+    }
+    //verts of prims with parameters are buffered the first time they're drawn
+    pub fn vao(&self, ptype: &PrimType) -> GLuint {
+        *self.prim_map.borrow_mut().entry(*ptype).or_insert_with(|| ptype.buffer_data())
+    }
+    pub fn program(&self, ptype: &PrimType) -> &Rc<Program> {
+        self.prog_map.get(ptype).unwrap_or(&self.prog_map[&PT::Rect])
+    }
+    //the canvas is drawn moved by the camera, the panels over it aren't
+    pub fn set_camera(&self, camera: &Point) {
+        unsafe {
+            gl::Viewport(-camera.x as i32, camera.y as i32, self.viewport.x as i32, self.viewport.y as i32);
+        }
     }
 }
 
@@ -809,10 +1219,20 @@ impl ShapeBuilder {
     }
     pub fn color(mut self, r: u8, g: u8, b: u8) -> Self {
         self.p.color = rgb_to_f32(r,g,b);
+        self.p.stroke_color = self.p.color;
+        self
+    }
+    pub fn stroke(mut self, r: u8, g: u8, b: u8) -> Self {
+        self.p.stroke_color = rgb_to_f32(r,g,b);
+        self
+    }
+    pub fn line_width(mut self, width: f32) -> Self {
+        self.p.line_width = width;
         self
     }
     pub fn alpha(mut self, a: f32) -> Self {
         self.p.color[3] = a;
+        self.p.stroke_color[3] = a;
         self
     }
     pub fn circle(mut self, rad: u32) -> Self {
@@ -840,21 +1260,47 @@ impl ShapeBuilder {
         self.p.rect.size = Point::new(base as f32, height as f32);
         self
     }
+    //radius in percent of the width and height
+    pub fn round_rect(mut self, width: u32, height: u32, radius: u8) -> Self {
+        self.p.prim = PT::RoundRect(radius);
+        self.p.rect.size = Point::new(width as f32, height as f32);
+        self
+    }
+    pub fn diamond(mut self, width: u32, height: u32) -> Self {
+        self.p.prim = PT::Diamond;
+        self.p.rect.size = Point::new(width as f32, height as f32);
+        self
+    }
+    pub fn polygon(mut self, sides: u8, width: u32, height: u32) -> Self {
+        self.p.prim = PT::Polygon(sides).with_sides(sides);
+        self.p.rect.size = Point::new(width as f32, height as f32);
+        self
+    }
+    pub fn hexagon(self, width: u32, height: u32) -> Self {
+        self.polygon(6, width, height)
+    }
+    pub fn parallelogram(mut self, width: u32, height: u32) -> Self {
+        self.p.prim = PT::Parallelogram;
+        self.p.rect.size = Point::new(width as f32, height as f32);
+        self
+    }
+    pub fn cylinder(mut self, width: u32, height: u32) -> Self {
+        self.p.prim = PT::Cylinder;
+        self.p.rect.size = Point::new(width as f32, height as f32);
+        self
+    }
+    pub fn star(mut self, points: u8, width: u32, height: u32) -> Self {
+        self.p.prim = PT::Star(points).with_sides(points);
+        self.p.rect.size = Point::new(width as f32, height as f32);
+        self
+    }
     pub fn fill(mut self, fill: bool) -> Self {
         self.p.fill = fill;
         self
     }
     pub fn get(mut self) -> Shape { 
-        if let PT::Circle = self.p.prim {
-            if !self.p.fill {
-                self.p.prim = PT::Ring
-            }
-        }
-        if let PT::Ring = self.p.prim {
-            if self.p.fill {
-                self.p.prim = PT::Circle
-            }
-        }
+        let fill = self.p.fill;
+        self.p.set_fill(fill);
         Shape::Polygon(self.p)
     }
 }
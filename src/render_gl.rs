@@ -147,6 +147,12 @@ unsafe fn get_uniform_location(prog_id: GLuint, name: &str) -> Result<i32, Strin
     return Ok(loc)
 }
 
+impl SendUniform for f32 {
+    unsafe fn uniform(&self, loc: GLint) {
+        gl::Uniform1f(loc, *self); 
+    }
+}
+
 impl SendUniform for glm::Vec2 {
     unsafe fn uniform(&self, loc: GLint) {
         gl::Uniform2fv(loc, 1, self.as_ptr()); 
@@ -3,16 +3,23 @@ extern crate gl;
 extern crate nalgebra_glm;
 extern crate sem_graph_derive;
 
-use std::collections::HashMap;
+use std::cell::RefCell;
 use std::error::Error;
 use nalgebra_glm as glm;
-use freetype::library::Library;
+use std::rc::Rc;
+use std::ops::Range;
+use freetype::face::LoadFlag;
+use freetype::render_mode::RenderMode;
 use std::ffi::CString;
 use gl::types::*;
 
 use crate::render_gl::{Program, Shader, SendUniform, SendUniforms};
-use crate::primitives::{Point, rgb_to_f32, DrawCtx, Rect, RotateRect} ;
+use crate::primitives::{Point, rgb_to_f32, DrawCtx, Rect, RotateRect, Radians} ;
 use sem_graph_derive::SendUniforms;
+use crate::glyph_atlas::{GlyphAtlas, Glyph, GlyphBitmap, GlyphKey, distance_field};
+use crate::shaping::{LineCache, ShapedLine};
+use crate::fonts::{FontManager, FontConfig, FontStyle};
+use crate::text_spans::{TextAttrs, slice_runs};
 
 fn buffer_char_data() -> (GLuint, GLuint) {
     let mut vao: GLuint = 0;
@@ -22,12 +29,12 @@ fn buffer_char_data() -> (GLuint, GLuint) {
         gl::GenBuffers(1, &mut vbo);
         gl::BindVertexArray(vao);
         gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(gl::ARRAY_BUFFER, 
-            (std::mem::size_of::<f32>() * 4 * 4) as GLsizeiptr,
-            std::ptr::null(), gl::DYNAMIC_DRAW);
+        let stride = (8 * std::mem::size_of::<f32>()) as GLint;
         gl::EnableVertexAttribArray(0);
-        gl::VertexAttribPointer(0, 4, gl::FLOAT, gl::FALSE,
-            (4 * std::mem::size_of::<f32>()) as GLint, std::ptr::null());
+        gl::VertexAttribPointer(0, 4, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+        gl::EnableVertexAttribArray(1);
+        gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, stride,
+            (4 * std::mem::size_of::<f32>()) as *const GLvoid);
         gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         gl::BindVertexArray(0);
     }
@@ -44,27 +51,14 @@ fn get_char_program() -> Result<Program, String> {
     Program::from_shaders(&[vert_shader, frag_shader])
 }
 
-struct Character {
-    texture: GLuint,
-    size: glm::TVec2<i32>,
-    bearing: glm::TVec2<i32>,    // Offset from baseline to left/top of glyph
-    advance: GLint 
-}
-
 #[derive(SendUniforms, PartialEq, Clone)]
 pub struct TextUniforms {
-    text_color: glm::Vec3,
     model: glm::Mat4,
     projection: glm::Mat4
 }
 
 impl TextUniforms {
-    pub fn new(text_color: &glm::Vec4, r: &RotateRect, off: &Point, vp: &Point) -> Self {
-        let pct = Point::new(off.x / r.size.x, off.y / r.size.y);
-        let r2 = Rect::new(pct, Point::new(1.,1.));
-        let mut r = r.clone();
-        r.resize(&r2, vp);
-
+    pub fn new(r: &RotateRect, vp: &Point) -> Self {
         let projection = glm::ortho(0., vp.x, vp.y, 0., -1., 1.);
         let mut model = glm::translate(&glm::identity(), &r.offset.to_vec3());
 
@@ -72,15 +66,68 @@ impl TextUniforms {
         model = glm::rotate(&model, r.rot.0, &glm::vec3(0., 0., 1.));
         model = glm::translate(&model, &(-r.size / 2.).to_vec3());
 
-        let text_color = glm::vec4_to_vec3(text_color);
-        TextUniforms {text_color, model, projection}
+        TextUniforms {model, projection}
+    }
+}
+
+//outline and drop shadow drawn behind the glyphs, sizes are in screen pixels
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct TextEffects {
+    pub outline_color: glm::Vec4,
+    pub outline_width: f32,
+    pub shadow_color: glm::Vec4,
+    pub shadow_offset: Point,
+}
+
+impl Default for TextEffects {
+    fn default() -> Self {
+        TextEffects {
+            outline_color: glm::vec4(0., 0., 0., 0.),
+            outline_width: 0.,
+            shadow_color: glm::vec4(0., 0., 0., 0.),
+            shadow_offset: Point::new(1.5, 1.5),
+        }
+    }
+}
+
+#[derive(SendUniforms)]
+struct EffectUniforms {
+    outline_color: glm::Vec4,
+    outline_width: f32,
+    shadow_color: glm::Vec4,
+    shadow_offset: glm::Vec2,
+}
+
+impl EffectUniforms {
+    //distances in the glyph textures are measured in raster pixels, spread over 0..1 by the SDF spread
+    fn new(effects: &TextEffects, scale: f32) -> Self {
+        let texels = FontManager::RASTER_SCALE as f32 / scale;
+        let spread = RenderText::SDF_SPREAD as f32;
+        //shadows are sampled this far from the glyph, past its distance field into the empty space around it.
+        //a texel short of the atlas padding so filtering doesn't reach the next glyph
+        let max_offset = f32::min(spread / 2., (GlyphAtlas::PADDING - 1) as f32);
+        let offset = Point::new(
+            (effects.shadow_offset.x * texels).clamp(-max_offset, max_offset),
+            (effects.shadow_offset.y * texels).clamp(-max_offset, max_offset));
+        EffectUniforms {
+            outline_color: effects.outline_color,
+            outline_width: (effects.outline_width * texels / (2. * spread)).clamp(0., 0.45),
+            shadow_color: effects.shadow_color,
+            shadow_offset: glm::vec2(offset.x, offset.y) / GlyphAtlas::SIZE as f32,
+        }
     }
 }
 
+//runs of chars drawn with the same attributes, lengths are in chars
+pub type AttrRun = (usize, TextAttrs);
+
 pub struct TextParams<'a> {
     pub text: &'a str,
-    pub color: glm::Vec3,
+    pub color: glm::Vec4,           // Color of runs without one of their own
     pub scale: f32,
+    pub effects: TextEffects,
+    pub runs: &'a [AttrRun],
+    pub placements: &'a [LinePlacement],
     pub trans: &'a TextUniforms,
 }
 
@@ -89,140 +136,404 @@ impl<'a> TextParams<'a> {
     pub fn new(text: &'a str, trans: &'a TextUniforms) -> Self {
         TextParams {
             text,
-            color: glm::vec3(0.,0.,0.),
+            color: glm::vec4(0., 0., 0., 1.),
             scale: 1.0,
+            effects: TextEffects::default(),
+            runs: &[],
+            placements: &[],
             trans
         }
     }
+    pub fn effects(mut self, effects: &TextEffects) -> Self {
+        self.effects = *effects;
+        self
+    }
     pub fn color(mut self, r: u8, g: u8, b: u8) -> Self {
-        self.color = glm::vec4_to_vec3(&rgb_to_f32(r, g, b));
+        self.color = rgb_to_f32(r, g, b);
+        self
+    }
+    pub fn runs(mut self, runs: &'a [AttrRun]) -> Self {
+        self.runs = runs;
         self
     }
     pub fn scale(mut self, scale: f32) -> Self {
         self.scale = scale;
         self
     }
+    pub fn placements(mut self, placements: &'a [LinePlacement]) -> Self {
+        self.placements = placements;
+        self
+    }
+}
+
+//where a line of the text is drawn, lines without a placement are stacked below the one before
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LinePlacement {
+    pub x: f32,
+    pub y: f32,                     // Top of the line box
+    pub word_spacing: f32,          // Extra space after each space between words
+}
+
+struct LineSegment {
+    shaped: Rc<ShapedLine>,
+    start: usize,                   // Index in the line of the segment's first char
+    x: f32,
+    scale: f32,
+    attrs: TextAttrs,
+}
+
+//a line split into segments of chars with the same attributes, laid out left to right
+#[derive(Default)]
+pub struct LineLayout {
+    segments: Vec<LineSegment>,
+    pub width: f32,
+    pub height: f32,
+    x: f32,
+    word_spacing: f32,
+    spaces: Vec<usize>,             // Indices of the spaces between words, stretched when justified
+    nchars: usize,
+    text_end: usize,                // Index of the char after the last one that isn't a space
+}
+
+impl LineLayout {
+    //offset of the line and the stretched spaces, applied to every caret position
+    pub fn align(&mut self, x: f32, word_spacing: f32) {
+        self.x = x;
+        self.word_spacing = word_spacing;
+    }
+    pub fn placement(&self, y: f32) -> LinePlacement {
+        LinePlacement { x: self.x, y, word_spacing: self.word_spacing }
+    }
+    //width without the spaces the line ends with
+    pub fn trimmed_width(&self) -> f32 {
+        if self.text_end == self.nchars { self.width } else { self.shaped_x(self.text_end) }
+    }
+    pub fn word_gaps(&self) -> usize {
+        self.spaces.len()
+    }
+    fn offset(&self, char_idx: usize) -> f32 {
+        self.x + self.word_spacing * self.spaces.iter().take_while(|i| **i < char_idx).count() as f32
+    }
+    fn shaped_x(&self, char_idx: usize) -> f32 {
+        let seg = match self.segments.iter().rev().find(|s| s.start <= char_idx) {
+            Some(seg) => seg,
+            None => return 0.
+        };
+        let text = seg.shaped.text();
+        let idx = text.char_indices().nth(char_idx - seg.start).map_or(text.len(), |(i, _)| i);
+        seg.x + seg.shaped.cursor_x(idx) * seg.scale
+    }
+    //x offset of the caret placed before char `char_idx`
+    pub fn cursor_x(&self, char_idx: usize) -> f32 {
+        self.shaped_x(char_idx) + self.offset(char_idx)
+    }
+    //char index of the caret position closest to `x`
+    pub fn hit_test(&self, x: f32) -> usize {
+        if self.word_spacing != 0. {
+            return (0..=self.nchars).min_by(|a, b| {
+                (self.cursor_x(*a) - x).abs().partial_cmp(&(self.cursor_x(*b) - x).abs()).unwrap()
+            }).unwrap_or(0);
+        }
+        let x = x - self.x;
+        let seg = self.segments.iter().find(|s| x < s.x + s.shaped.width * s.scale).or(self.segments.last());
+        match seg {
+            Some(seg) => {
+                let idx = seg.shaped.hit_test((x - seg.x) / seg.scale);
+                seg.start + seg.shaped.text()[..idx].chars().count()
+            }
+            None => 0
+        }
+    }
+}
+
+//a glyph at its place in the text's space, for writers that draw text themselves
+pub struct PlacedGlyph {
+    pub font: usize,
+    pub id: u32,
+    pub pen: Point,                 // Left end of the glyph's baseline
+    pub scale: f32,
+    pub size: f32,                  // Em size in pixels
+    pub color: glm::Vec4,
+    pub text: String,               // Chars the glyph was shaped from, empty for the rest of a cluster's glyphs
+}
+
+#[derive(Default)]
+pub struct PlacedText {
+    pub glyphs: Vec<PlacedGlyph>,
+    pub lines: Vec<(Rect, glm::Vec4)>,  // Underlines and strikethroughs
 }
 
 pub struct RenderText {
-    char_map: HashMap<GLchar, Character>,
+    fonts: FontManager,
+    lines: LineCache,
+    atlas: RefCell<GlyphAtlas>,
     vao: GLuint,
     vbo: GLuint,
     prog: Program,
 }
 
 impl RenderText {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
+    //glyph ID used for characters no font in the fallback chain has a glyph for
+    const FALLBACK_GLYPH: u32 = u32::MAX;
+    //solid block that underlines and strikethroughs are drawn with
+    const SOLID_GLYPH: u32 = u32::MAX - 1;
+    //padding around each glyph's distance field in raster pixels, limits outline width and shadow offset
+    const SDF_SPREAD: i32 = 8;
+
+    pub fn new(config: &FontConfig) -> Result<Self, Box<dyn Error>> {
         unsafe { gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1); }
-        let lib = Library::init()?;
-        let face = lib.new_face("fonts/arial.ttf", 0).map_err(|e| format!("Could not load font face: {:?}", e))?;
-        let mut char_map = HashMap::new();
-        face.set_char_size(12 * 64, 0, 50, 0).unwrap();
-        for c in 0..=127 {
-            face.load_char(c as usize, freetype::face::LoadFlag::RENDER).map_err(|e| format!("Could not load char {:?} {:?}", c, e))?;
-            face.set_pixel_sizes(0, 24)?;
+        let fonts = FontManager::new(config)?;
+        let (vao, vbo) = buffer_char_data();
+        let prog = get_char_program()?;
+        Ok(RenderText { fonts, lines: LineCache::new(), atlas: RefCell::new(GlyphAtlas::new()), vao, vbo, prog })
+    }
+    //hollow box drawn in place of missing glyphs
+    fn fallback_bitmap(&self) -> (Vec<u8>, i32, i32) {
+        let raster_size = (self.fonts.pixel_size() * FontManager::RASTER_SCALE) as i32;
+        let border = FontManager::RASTER_SCALE as i32;
+        let (w, h) = (raster_size / 2, raster_size * 2 / 3);
+        let buffer = (0..h).flat_map(|y| (0..w).map(move |x|
+            if x < border || y < border || x >= w - border || y >= h - border { 255 } else { 0 })).collect();
+        (buffer, w, h)
+    }
+    //glyphs are stored as distance fields, metrics are scaled back down to the pixel size
+    fn insert_glyph(&self, key: GlyphKey, bitmap: &GlyphBitmap, bearing: (i32, i32), advance: f32) -> Glyph {
+        let (field, w, h) = distance_field(bitmap, RenderText::SDF_SPREAD);
+        let sdf = GlyphBitmap { width: w, rows: h, pitch: w, buffer: &field };
+        let raster_scale = FontManager::RASTER_SCALE as f32;
+        let metrics = Glyph {
+            size: glm::vec2(bitmap.width as f32, bitmap.rows as f32) / raster_scale,
+            bearing: glm::vec2(bearing.0 as f32, bearing.1 as f32) / raster_scale,
+            advance: advance / raster_scale,
+            padding: RenderText::SDF_SPREAD as f32 / raster_scale,
+            uv: glm::vec4(0., 0., 0., 0.)
+        };
+        self.atlas.borrow_mut().insert(key, &sdf, metrics)
+    }
+    fn rasterize(&self, font: usize, id: u32) -> Glyph {
+        if id == RenderText::SOLID_GLYPH {
+            let size = 4 * RenderText::SDF_SPREAD;
+            let buffer = vec![255; (size * size) as usize];
+            let bitmap = GlyphBitmap { width: size, rows: size, pitch: size, buffer: &buffer };
+            return self.insert_glyph((0, id), &bitmap, (0, size), size as f32);
+        }
+        let font_face = self.fonts.face(font);
+        let face = &font_face.ft;
+        if id != RenderText::FALLBACK_GLYPH && face.load_glyph(id, LoadFlag::DEFAULT).is_ok() {
+            if font_face.embolden {
+                //thickened by 1/24 of the em like FreeType's own slot emboldening, the advance grows with it
+                unsafe {
+                    let slot = face.raw().glyph;
+                    if (*slot).format == freetype::ffi::FT_GLYPH_FORMAT_OUTLINE {
+                        let strength = (*face.raw().size).metrics.y_ppem as freetype::ffi::FT_Pos * 64 / 24;
+                        freetype::ffi::FT_Outline_Embolden(&mut (*slot).outline, strength);
+                        (*slot).advance.x += strength;
+                    }
+                }
+            }
             let glyph = face.glyph();
-            let bitmap = glyph.bitmap();
-            let mut texture: GLuint = 0;
-            unsafe {
-                gl::GenTextures(1, &mut texture) ;
-                gl::BindTexture(gl::TEXTURE_2D, texture);
-                gl::TexImage2D(
-                    gl::TEXTURE_2D,
-                    0,
-                    gl::RED as GLint,
-                    bitmap.width(),
-                    bitmap.rows(),
-                    0,
-                    gl::RED,
-                    gl::UNSIGNED_BYTE,
-                    bitmap.buffer().as_ptr() as *const GLvoid
-                );
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
-                let size = glm::vec2(bitmap.width(), bitmap.rows());
-                let bearing = glm::vec2(glyph.bitmap_left(), glyph.bitmap_top());
-                let advance = glyph.advance().x as GLint; 
-                char_map.insert(c, 
-                    Character { texture, size, bearing, advance });
+            if glyph.render_glyph(RenderMode::Normal).is_err() {
+                return self.rasterize(font, RenderText::FALLBACK_GLYPH);
             }
+            let bitmap = glyph.bitmap();
+            let bearing = (glyph.bitmap_left(), glyph.bitmap_top());
+            let advance = (glyph.advance().x >> 6) as f32;
+            let buffer = bitmap.buffer();
+            let bitmap = GlyphBitmap { width: bitmap.width(), rows: bitmap.rows(), pitch: bitmap.pitch().abs(), buffer };
+            return self.insert_glyph((font, id), &bitmap, bearing, advance);
         }
-        unsafe { gl::BindTexture(gl::TEXTURE_2D, 0); }
-        let (vao, vbo) = buffer_char_data();
-        let prog = get_char_program()?;
-        Ok(RenderText { char_map, vao, vbo, prog })
+        let (buffer, w, h) = self.fallback_bitmap();
+        let bitmap = GlyphBitmap { width: w, rows: h, pitch: w, buffer: &buffer };
+        let border = FontManager::RASTER_SCALE as i32;
+        self.insert_glyph((0, RenderText::FALLBACK_GLYPH), &bitmap, (border, h), (w + 2 * border) as f32)
     }
-    pub fn draw(&self, params: &TextParams, _: &DrawCtx) {
-        self.prog.set_used();
-        let (text, scale, trans) = (params.text, params.scale, params.trans);
-        trans.send_uniforms(self.prog.id()).unwrap();
-        unsafe {
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindVertexArray(self.vao);
+    fn glyph_by_id(&self, font: usize, id: u32) -> Glyph {
+        let key = if id == 0 { (0, RenderText::FALLBACK_GLYPH) } else { (font, id) };
+        if let Some(glyph) = self.atlas.borrow().get(key) {
+            return glyph;
         }
-        let mut char_pt = Point::origin();
-        for c in text.bytes() {
-            if c == '\n' as u8 {
-                char_pt.x = 0.;
-                char_pt.y += self.line_height(scale);
+        self.rasterize(key.0, key.1)
+    }
+    fn glyph(&self, ch: char, style: FontStyle) -> Glyph {
+        let font = self.fonts.font_for_char(style, ch);
+        self.glyph_by_id(font, self.fonts.face(font).ft.get_char_index(ch as usize))
+    }
+    //`runs` are the attributes of the line's chars, a run for its newline sets the height of an empty line
+    pub fn layout_line(&self, line: &str, runs: &[AttrRun], scale: f32) -> LineLayout {
+        let height = runs.iter().map(|(_, attrs)| self.line_height(scale * attrs.size)).fold(None, |h: Option<f32>, lh| {
+            Some(h.map_or(lh, |h| f32::max(h, lh)))
+        }).unwrap_or_else(|| self.line_height(scale));
+        let bounds: Vec<usize> = line.char_indices().map(|(i, _)| i).chain(std::iter::once(line.len())).collect();
+        let nchars = bounds.len() - 1;
+        let chars: Vec<char> = line.chars().collect();
+        let text_start = chars.iter().position(|c| !c.is_whitespace()).unwrap_or(nchars);
+        let text_end = chars.iter().rposition(|c| !c.is_whitespace()).map_or(0, |i| i + 1);
+        let spaces = (text_start..text_end).filter(|i| chars[*i].is_whitespace()).collect();
+        let mut segments = Vec::new();
+        let (mut start, mut x) = (0, 0.);
+        //chars past the end of the runs get the default attributes
+        for (n, attrs) in runs.iter().cloned().chain(std::iter::once((nchars, TextAttrs::default()))) {
+            let end = std::cmp::min(start + n, nchars);
+            if start == end {
                 continue;
             }
-            let ch = &self.char_map[&(c as GLchar)];
-            let offset = Point::new(
-                ch.bearing.x as f32 * scale,
-                (ch.size.y - ch.bearing.y) as f32 * scale);
-            let size = Point::new(scale, scale) * ch.size.into();
-            let orig = char_pt + offset;
-            let verts = [
-                [orig.x, orig.y - size.y, 0.0, 0.0],
-                [orig.x, orig.y, 0.0, 1.0],
-                [orig.x + size.x, orig.y, 1.0, 1.0],
-                [orig.x + size.x, orig.y - size.y, 1.0, 0.0]
-            ];
-            unsafe { 
-                gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
-                gl::BindTexture(gl::TEXTURE_2D, ch.texture);
-                gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
-                gl::BufferSubData(gl::ARRAY_BUFFER, 0, 
-                    (std::mem::size_of::<f32>() * 4 * 4) as GLsizeiptr, verts.as_ptr() as *const GLvoid);
-                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-                gl::DrawArrays(gl::QUADS, 0, 4);
+            let shaped = self.lines.get(&line[bounds[start]..bounds[end]], &self.fonts, attrs.font_style());
+            let seg_scale = scale * attrs.size;
+            let width = shaped.width * seg_scale;
+            segments.push(LineSegment { shaped, start, x, scale: seg_scale, attrs });
+            x += width;
+            start = end;
+        }
+        LineLayout { segments, width: x, height, x: 0., word_spacing: 0., spaces, nchars, text_end }
+    }
+    //every line's baseline is at the bottom of its line box
+    pub fn place_glyphs(&self, params: &TextParams) -> PlacedText {
+        let (text, runs, color, scale) = (params.text, params.runs, &params.color, params.scale);
+        let mut placed = PlacedText::default();
+        let (mut y, mut pos) = (0., 0);
+        for (n, line) in text.split('\n').enumerate() {
+            let nchars = line.chars().count();
+            let mut layout = self.layout_line(line, &slice_runs(runs, pos..pos + nchars + 1), scale);
+            if let Some(placement) = params.placements.get(n) {
+                layout.align(placement.x, placement.word_spacing);
+                y = placement.y;
+            }
+            y += layout.height;
+            for seg in layout.segments.iter() {
+                let seg_color = seg.attrs.color.unwrap_or(*color);
+                let shaped = &seg.shaped;
+                let text = shaped.text();
+                for (i, g) in shaped.glyphs.iter().enumerate() {
+                    let offset = layout.offset(seg.start + text[..g.cluster].chars().count());
+                    let first = i == 0 || shaped.glyphs[i - 1].cluster != g.cluster;
+                    placed.glyphs.push(PlacedGlyph {
+                        font: g.font,
+                        id: g.id,
+                        pen: Point::new(seg.x + offset + (g.x + g.x_offset) * seg.scale, y - g.y_offset * seg.scale),
+                        scale: seg.scale,
+                        size: self.fonts.pixel_size() as f32 * seg.scale,
+                        color: seg_color,
+                        text: if first { text[g.cluster..shaped.cluster_end(g.cluster)].to_string() } else { String::new() }
+                    });
+                }
+                let x1 = seg.x + layout.offset(seg.start);
+                let x2 = seg.x + shaped.width * seg.scale + layout.offset(seg.start + text.chars().count());
+                placed.lines.extend(self.decorations(seg, x1..x2, y).into_iter().map(|r| (r, seg_color)));
             }
-            char_pt.x += (ch.advance >> 6) as f32 * scale;
+            pos += nchars + 1;
+        }
+        placed
+    }
+    //two triangles per glyph, each vertex is x, y, u, v, r, g, b, a
+    fn glyph_quads(&self, params: &TextParams) -> Vec<f32> {
+        let placed = self.place_glyphs(params);
+        let mut verts = Vec::with_capacity((placed.glyphs.len() + placed.lines.len()) * 6 * 8);
+        for g in placed.glyphs.iter() {
+            let ch = self.glyph_by_id(g.font, g.id);
+            self.push_quad(&mut verts, &ch, &g.pen, g.scale, &g.color);
+        }
+        //lines are the middle of the solid glyph stretched over the segment
+        let solid = self.glyph_by_id(0, RenderText::SOLID_GLYPH);
+        let (u, v) = ((solid.uv.x + solid.uv.z) / 2., (solid.uv.y + solid.uv.w) / 2.);
+        let uv = glm::vec4(u, v, u, v);
+        for (r, color) in placed.lines.iter() {
+            RenderText::push_vertices(&mut verts, &r.c1, &r.c2, &uv, color);
         }
+        verts
+    }
+    fn push_vertices(verts: &mut Vec<f32>, c1: &Point, c2: &Point, uv: &glm::Vec4, color: &glm::Vec4) {
+        let (u0, v0, u1, v1) = (uv.x, uv.y, uv.z, uv.w);
+        let (r, g, b, a) = (color.x, color.y, color.z, color.w);
+        verts.extend_from_slice(&[
+            c1.x, c1.y, u0, v0, r, g, b, a,
+            c1.x, c2.y, u0, v1, r, g, b, a,
+            c2.x, c2.y, u1, v1, r, g, b, a,
+            c1.x, c1.y, u0, v0, r, g, b, a,
+            c2.x, c2.y, u1, v1, r, g, b, a,
+            c2.x, c1.y, u1, v0, r, g, b, a,
+        ]);
+    }
+    fn push_quad(&self, verts: &mut Vec<f32>, ch: &Glyph, pen: &Point, scale: f32, color: &glm::Vec4) {
+        let offset = Point::new((ch.bearing.x - ch.padding) * scale, (ch.size.y - ch.bearing.y + ch.padding) * scale);
+        let size = Point::new((ch.size.x + 2. * ch.padding) * scale, (ch.size.y + 2. * ch.padding) * scale);
+        let orig = *pen + offset;
+        let c1 = Point::new(orig.x, orig.y - size.y);
+        let c2 = Point::new(orig.x + size.x, orig.y);
+        RenderText::push_vertices(verts, &c1, &c2, &ch.uv, color);
+    }
+    //underline and strikethrough rects over the segment
+    fn decorations(&self, seg: &LineSegment, xs: Range<f32>, baseline: f32) -> Vec<Rect> {
+        let px = self.fonts.pixel_size() as f32 * seg.scale;
+        let thickness = f32::max(px / 16., 1.);
+        let mut lines = Vec::new();
+        if seg.attrs.underline {
+            lines.push(baseline + px * 0.1);
+        }
+        if seg.attrs.strikethrough {
+            lines.push(baseline - px * 0.3);
+        }
+        lines.into_iter().map(|y| Rect::new(Point::new(xs.start, y), Point::new(xs.end, y + thickness))).collect()
+    }
+    pub fn draw(&self, params: &TextParams, draw_ctx: &DrawCtx) {
+        let (scale, trans) = (params.scale, params.trans);
+        let generation = self.atlas.borrow().generation();
+        let mut verts = self.glyph_quads(params);
+        //the atlas filled up part way through, earlier glyphs have to be looked up again
+        if self.atlas.borrow().generation() != generation {
+            verts = self.glyph_quads(params);
+        }
+        if verts.is_empty() {
+            return;
+        }
+        self.prog.set_used();
+        trans.send_uniforms(self.prog.id()).unwrap();
+        EffectUniforms::new(&params.effects, scale).send_uniforms(self.prog.id()).unwrap();
+        draw_ctx.opacity.get().send_uniform(self.prog.id(), "opacity").unwrap();
         unsafe {
+            gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.atlas.borrow().texture());
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(gl::ARRAY_BUFFER,
+                (std::mem::size_of::<f32>() * verts.len()) as GLsizeiptr,
+                verts.as_ptr() as *const GLvoid, gl::DYNAMIC_DRAW);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::DrawArrays(gl::TRIANGLES, 0, (verts.len() / 8) as GLint);
             gl::BindVertexArray(0);
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
     }
+    pub fn draw_label(&self, text: &str, r: &Rect, color: &glm::Vec4, scale: f32, draw_ctx: &DrawCtx) {
+        let rect = RotateRect::new(r.c1, r.size(), Radians(0.));
+        let trans = TextUniforms::new(&rect, &draw_ctx.viewport);
+        self.draw(&TextParams { color: *color, ..TextParams::new(text, &trans).scale(scale) }, draw_ctx);
+    }
+    pub fn fonts(&self) -> &FontManager {
+        &self.fonts
+    }
     pub fn has_char(&self, ch: char) -> bool {
-        self.char_map.contains_key(&(ch as GLchar))
+        let font = self.fonts.font_for_char(FontStyle::Regular, ch);
+        self.fonts.face(font).has_char(ch)
     }
     pub fn line_height(&self, scale: f32) -> f32 {
-        self.char_map[&('a' as i8)].size.y as f32 * scale * 1.4
+        self.glyph('a', FontStyle::Regular).size.y * scale * 1.4
     }
-    pub fn char_size(&self, ch: char, scale: f32) -> Point {
-        self.char_map.get(&(ch as GLchar)).map(|ch| Point::new(scale * ch.size.x as f32, scale * ch.size.y as f32))
-            .unwrap_or(Point::origin())
+    pub fn char_size(&self, ch: char, style: FontStyle, scale: f32) -> Point {
+        let glyph = self.glyph(ch, style);
+        Point::new(scale * glyph.size.x, scale * glyph.size.y)
     }
-    pub fn char_size_w_advance(&self, ch: char, scale: f32) -> Point {
-        self.char_map.get(&(ch as GLchar)).map(|ch| Point::new(scale * (ch.advance >> 6) as f32, scale * ch.size.y as f32))
-            .unwrap_or(Point::origin())
+    pub fn char_size_w_advance(&self, ch: char, style: FontStyle, scale: f32) -> Point {
+        let glyph = self.glyph(ch, style);
+        Point::new(scale * glyph.advance, scale * glyph.size.y)
     }
     pub fn measure(&self, text: &str, scale: f32) -> Point {
         if text.is_empty() {
             return Point::origin();
         } 
         let lh = self.line_height(scale);
-        text.bytes().fold(Point::new(0., lh), |size, c| {
-            let ch = &self.char_map[&(c as GLchar)];
-            size + match c as char {
-                '\n' => Point::new(0., lh),
-                _ => Point::new(scale * (ch.advance >> 6) as f32, 0.)
-            } 
-        }) 
-    } 
+        text.split('\n').fold(Point::new(0., 0.), |size, line| {
+            Point::new(f32::max(size.x, self.layout_line(line, &[], scale).width), size.y + lh)
+        })
+    }
 }
-
@@ -0,0 +1,191 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use crate::primitives::{Point, Rect};
+
+//clearance kept between a route and the nodes it goes around
+const MARGIN: f32 = 12.;
+//a bend costs as much as this many pixels of length, so routes with fewer bends win
+const BEND_COST: f32 = 40.;
+const MAX_PASSES: usize = 4;
+
+//everything an orthogonal route depends on, a route is only searched for again when this changes
+#[derive(Clone, PartialEq, Debug)]
+pub struct RouteQuery {
+    pub start: Point,
+    pub end: Point,
+    pub waypoints: Vec<Point>,      // Pinned points the route goes through in order
+    pub obstacles: Vec<Rect>,       // Bounding boxes of the nodes near the route
+}
+
+impl RouteQuery {
+    //only nodes within reach of the ends, the waypoints and the previous route are kept as obstacles,
+    //a node moving elsewhere on the canvas doesn't change the query
+    pub fn new(start: Point, end: Point, waypoints: &[Point], nodes: &[Rect], prev: &[Point]) -> Self {
+        let mut pts = vec![start, end];
+        pts.extend_from_slice(waypoints);
+        pts.extend_from_slice(prev);
+        let obstacles = nodes_near(nodes, &pts);
+        RouteQuery { start, end, waypoints: waypoints.to_vec(), obstacles }
+    }
+    //bend points of the route, without its ends. when the route leaves the area its obstacles came from
+    //it's searched for again with the nodes around the new route
+    pub fn route(&mut self, nodes: &[Rect]) -> Vec<Point> {
+        let mut bends = self.search();
+        for _ in 0..MAX_PASSES {
+            let mut pts = vec![self.start, self.end];
+            pts.extend_from_slice(&self.waypoints);
+            pts.extend_from_slice(&bends);
+            let obstacles = nodes_near(nodes, &pts);
+            if obstacles == self.obstacles {
+                break;
+            }
+            self.obstacles = obstacles;
+            bends = self.search();
+        }
+        bends
+    }
+    fn search(&self) -> Vec<Point> {
+        let stops: Vec<Point> = std::iter::once(self.start)
+            .chain(self.waypoints.iter().cloned())
+            .chain(std::iter::once(self.end)).collect();
+        let mut path = vec![self.start];
+        for leg in stops.windows(2) {
+            path.extend(route_leg(leg[0], leg[1], &self.obstacles).into_iter().skip(1));
+        }
+        let path = simplify(&path);
+        path[1..path.len() - 1].to_vec()
+    }
+}
+
+fn inflate(r: &Rect, by: f32) -> Rect {
+    Rect::new(r.c1 - Point::new(by, by), r.c2 + Point::new(by, by))
+}
+
+fn strictly_inside(r: &Rect, p: &Point) -> bool {
+    p.x > r.c1.x && p.x < r.c2.x && p.y > r.c1.y && p.y < r.c2.y
+}
+
+fn nodes_near(nodes: &[Rect], pts: &[Point]) -> Vec<Rect> {
+    let area = inflate(&Rect::bounding_box(pts), 4. * MARGIN);
+    nodes.iter().filter(|r| r.c1.x < area.c2.x && r.c2.x > area.c1.x && r.c1.y < area.c2.y && r.c2.y > area.c1.y)
+        .cloned().collect()
+}
+
+//drops points that don't turn the path. a point the path doubles back from, like a pinned waypoint
+//off to the side, is a turn and is kept
+fn simplify(path: &[Point]) -> Vec<Point> {
+    let mut out: Vec<Point> = Vec::with_capacity(path.len());
+    for p in path {
+        if out.last() == Some(p) {
+            continue;
+        }
+        if out.len() >= 2 {
+            let (a, b) = (out[out.len() - 2], out[out.len() - 1]);
+            let between = (b.x - a.x) * (p.x - b.x) >= 0. && (b.y - a.y) * (p.y - b.y) >= 0.;
+            if between && ((a.x == b.x && b.x == p.x) || (a.y == b.y && b.y == p.y)) {
+                out.pop();
+            }
+        }
+        out.push(*p);
+    }
+    if out.len() == 1 {
+        out.push(out[0]);
+    }
+    out
+}
+
+#[derive(Copy, Clone, PartialEq)]
+struct Visit {
+    cost: f32,
+    estimate: f32,
+    node: (usize, usize),
+    dir: Option<usize>,
+}
+
+impl Eq for Visit {}
+
+//reversed so the binary heap pops the cheapest visit first
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.partial_cmp(&self.estimate).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+//a grid node and the direction it was reached in, turning costs a bend
+type GridState = ((usize, usize), Option<usize>);
+
+//A* over the grid made by the edges of the inflated obstacles and the ends, moving along grid lines.
+//the path from `a` to `b` including both, an L shaped path if they can't be connected
+fn route_leg(a: Point, b: Point, obstacles: &[Rect]) -> Vec<Point> {
+    //a node an end is inside of can't be avoided
+    let obs: Vec<Rect> = obstacles.iter().map(|r| inflate(r, MARGIN))
+        .filter(|r| !strictly_inside(r, &a) && !strictly_inside(r, &b)).collect();
+    let axis = |f: &dyn Fn(&Point) -> f32| {
+        let mut v: Vec<f32> = vec![f(&a), f(&b)];
+        v.extend(obs.iter().flat_map(|r| vec![f(&r.c1), f(&r.c2)]));
+        v.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        v.dedup();
+        v
+    };
+    let (xs, ys) = (axis(&|p| p.x), axis(&|p| p.y));
+    let pt = |(i, j): (usize, usize)| Point::new(xs[i], ys[j]);
+    let index = |v: &[f32], x: f32| v.iter().position(|c| *c == x).unwrap();
+    let (start, goal) = ((index(&xs, a.x), index(&ys, a.y)), (index(&xs, b.x), index(&ys, b.y)));
+    let blocked = |p: Point| obs.iter().any(|r| strictly_inside(r, &p));
+    let estimate = |n: (usize, usize)| (xs[n.0] - b.x).abs() + (ys[n.1] - b.y).abs();
+
+    let mut best: HashMap<GridState, f32> = HashMap::new();
+    let mut came_from: HashMap<GridState, GridState> = HashMap::new();
+    let mut heap = BinaryHeap::new();
+    heap.push(Visit { cost: 0., estimate: estimate(start), node: start, dir: None });
+    best.insert((start, None), 0.);
+    let mut found = None;
+    while let Some(Visit { cost, node, dir, .. }) = heap.pop() {
+        if node == goal {
+            found = Some((node, dir));
+            break;
+        }
+        if best.get(&(node, dir)).is_some_and(|c| cost > *c) {
+            continue;
+        }
+        //right, left, down, up
+        let steps = [(1i64, 0i64), (-1, 0), (0, 1), (0, -1)];
+        for (d, (dx, dy)) in steps.iter().enumerate() {
+            let (ni, nj) = (node.0 as i64 + dx, node.1 as i64 + dy);
+            if ni < 0 || nj < 0 || ni >= xs.len() as i64 || nj >= ys.len() as i64 {
+                continue;
+            }
+            let next = (ni as usize, nj as usize);
+            let (p, q) = (pt(node), pt(next));
+            //grid lines run along every obstacle edge, so a step is blocked exactly when its middle is
+            if blocked(q) || blocked((p + q) / 2.) {
+                continue;
+            }
+            let bend = if dir.is_some_and(|prev| prev != d) { BEND_COST } else { 0. };
+            let next_cost = cost + p.dist(&q) + bend;
+            if best.get(&(next, Some(d))).is_none_or(|c| next_cost < *c) {
+                best.insert((next, Some(d)), next_cost);
+                came_from.insert((next, Some(d)), (node, dir));
+                heap.push(Visit { cost: next_cost, estimate: next_cost + estimate(next), node: next, dir: Some(d) });
+            }
+        }
+    }
+    match found {
+        Some(mut state) => {
+            let mut path = vec![pt(state.0)];
+            while let Some(prev) = came_from.get(&state) {
+                path.push(pt(prev.0));
+                state = *prev;
+            }
+            path.reverse();
+            path
+        }
+        None => vec![a, Point::new(b.x, a.y), b]
+    }
+}
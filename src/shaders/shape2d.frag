@@ -2,8 +2,9 @@
 layout(location = 0) out vec4 shapeColor;
 
 uniform vec4 color;
+uniform float opacity;
 
 void main()
 {
-    shapeColor = color;
+    shapeColor = vec4(color.rgb, color.a * opacity);
 }
\ No newline at end of file
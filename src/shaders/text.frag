@@ -1,13 +1,32 @@
 #version 330 core
 in vec2 TexCoords;
+in vec4 GlyphColor;
 out vec4 color;
 
 uniform sampler2D text;
-uniform vec3 text_color;
+uniform vec4 outline_color;
+uniform float outline_width;
+uniform vec4 shadow_color;
+uniform vec2 shadow_offset;
+uniform float opacity;
+
+// Glyphs are signed distance fields, 0.5 is on the outline
+float coverage(float dist, float edge)
+{
+    float w = fwidth(dist);
+    return smoothstep(edge - w, edge + w, dist);
+}
 
 void main()
 {
-    vec4 sampled = vec4(1.0, 1.0, 1.0, texture(text, TexCoords).r);
-    vec3 my_color = text_color;
-    color = vec4(text_color, 1.0) * sampled;
-}
\ No newline at end of file
+    float dist = texture(text, TexCoords).r;
+    float fill = coverage(dist, 0.5) * GlyphColor.a;
+    float outline = coverage(dist, 0.5 - outline_width) * outline_color.a;
+    float shadow = coverage(texture(text, TexCoords - shadow_offset).r, 0.5 - outline_width) * shadow_color.a;
+
+    // Composite premultiplied glyph over outline over shadow
+    vec4 result = vec4(GlyphColor.rgb * fill, fill);
+    result += vec4(outline_color.rgb * outline, outline) * (1.0 - result.a);
+    result += vec4(shadow_color.rgb * shadow, shadow) * (1.0 - result.a);
+    color = result.a > 0.0 ? vec4(result.rgb / result.a, result.a * opacity) : vec4(0.0);
+}
@@ -1,7 +1,9 @@
 #version 330 core
 
 layout (location = 0) in vec4 vertex;
+layout (location = 1) in vec4 color;
 out vec2 TexCoords;
+out vec4 GlyphColor;
 
 uniform mat4 model;
 uniform mat4 projection;
@@ -10,4 +12,5 @@ void main()
 {
     gl_Position = projection * model * vec4(vertex.xy, 0.0, 1.0);
     TexCoords = vertex.zw;
+    GlyphColor = color;
 }
\ No newline at end of file
@@ -0,0 +1,64 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use crate::primitives::{Point, PrimType};
+use crate::geometry::in_polygon;
+
+pub type ShapeKindId = u16;
+
+//a node shape defined outside of PrimType. everything is in unit space, (0, 0) to (1, 1) stretched over the shape's rect
+pub trait ShapeKind {
+    //stable name shapes of this kind are saved under and looked up by
+    fn name(&self) -> &str;
+    //corners in order around the shape
+    fn outline(&self) -> Vec<Point>;
+    //triangles filling the shape, three points each. a fan around the center works for any outline the center can see all of
+    fn tessellate(&self) -> Vec<Point> {
+        let outline = self.outline();
+        let center = Point::new(0.5, 0.5);
+        (0..outline.len()).flat_map(|i| vec![center, outline[i], outline[(i + 1) % outline.len()]]).collect()
+    }
+    fn in_bounds(&self, p: &Point) -> bool {
+        in_polygon(&self.outline(), p)
+    }
+    //size in pixels of a shape placed without dragging it out
+    fn default_size(&self) -> Point {
+        Point::new(30., 30.)
+    }
+    //outline drawn on the shape bar instead of the shape itself
+    fn icon(&self) -> Option<Vec<Point>> {
+        None
+    }
+}
+
+thread_local! {
+    //shapes are only ever drawn and hit tested on the GL thread
+    static REGISTRY: RefCell<Vec<Rc<dyn ShapeKind>>> = RefCell::new(Vec::new());
+}
+
+//the prim to draw shapes of the kind with, kinds can't be replaced once shapes may be using them
+pub fn register(kind: Rc<dyn ShapeKind>) -> Result<PrimType, String> {
+    if find(kind.name()).is_some() {
+        return Err(format!("Shape kind {:?} is already registered", kind.name()));
+    }
+    //parentheses are kept for the parameters of built in prims
+    if PrimType::from_name(kind.name()).is_some() || kind.name().contains('(') {
+        return Err(format!("Shape kind name {:?} is reserved for a built in shape", kind.name()));
+    }
+    REGISTRY.with(|r| {
+        let mut r = r.borrow_mut();
+        r.push(kind);
+        Ok(PrimType::Custom((r.len() - 1) as ShapeKindId))
+    })
+}
+
+pub fn get(id: ShapeKindId) -> Option<Rc<dyn ShapeKind>> {
+    REGISTRY.with(|r| r.borrow().get(id as usize).cloned())
+}
+
+pub fn find(name: &str) -> Option<ShapeKindId> {
+    REGISTRY.with(|r| r.borrow().iter().position(|k| k.name() == name).map(|i| i as ShapeKindId))
+}
+
+pub fn count() -> usize {
+    REGISTRY.with(|r| r.borrow().len())
+}
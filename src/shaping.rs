@@ -0,0 +1,171 @@
+extern crate rustybuzz;
+extern crate unicode_bidi;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+use unicode_bidi::BidiInfo;
+use crate::fonts::{FontManager, FontStyle};
+
+#[derive(Clone, Copy, Debug)]
+pub struct ShapedGlyph {
+    pub id: u32,
+    pub font: usize,        // Face the glyph ID belongs to
+    pub cluster: usize,     // Byte index of the first char the glyph was shaped from
+    pub x: f32,             // Pen position in pixels at scale 1
+    pub x_advance: f32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+    pub rtl: bool,
+}
+
+//glyphs of one line of text, in visual order
+#[derive(Debug)]
+pub struct ShapedLine {
+    pub glyphs: Vec<ShapedGlyph>,
+    pub width: f32,
+    text: String,
+}
+
+impl ShapedLine {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    //byte index of the first char after the cluster starting at `cluster`
+    pub fn cluster_end(&self, cluster: usize) -> usize {
+        self.glyphs.iter().map(|g| g.cluster).filter(|c| *c > cluster).min().unwrap_or(self.text.len())
+    }
+    //x of the caret placed before the char at byte index `idx`
+    pub fn cursor_x(&self, idx: usize) -> f32 {
+        let glyph = self.glyphs.iter().filter(|g| g.cluster <= idx).max_by_key(|g| g.cluster);
+        let glyph = match glyph {
+            Some(glyph) => glyph,
+            None => return 0.
+        };
+        let end = self.cluster_end(glyph.cluster);
+        //split ligatures evenly between the chars they were made from
+        let nchars = std::cmp::max(self.text[glyph.cluster..end].chars().count(), 1);
+        let pct = self.text[glyph.cluster..std::cmp::min(idx, end)].chars().count() as f32 / nchars as f32;
+        if glyph.rtl { glyph.x + glyph.x_advance * (1. - pct) } else { glyph.x + glyph.x_advance * pct }
+    }
+    //byte index of the caret position closest to `x`
+    pub fn hit_test(&self, x: f32) -> usize {
+        let glyph = self.glyphs.iter().find(|g| x < g.x + g.x_advance).or(self.glyphs.last());
+        match glyph {
+            Some(glyph) => {
+                let before = (x - glyph.x) < glyph.x_advance / 2.;
+                if before != glyph.rtl { glyph.cluster } else { self.cluster_end(glyph.cluster) }
+            }
+            None => 0
+        }
+    }
+}
+
+pub struct Shaper {
+    face: rustybuzz::Face<'static>,
+    px_per_unit: f32,
+}
+
+impl Shaper {
+    pub fn new(font_data: &'static [u8], pixel_size: u32) -> Option<Self> {
+        let face = rustybuzz::Face::from_slice(font_data, 0)?;
+        let px_per_unit = pixel_size as f32 / face.units_per_em() as f32;
+        Some(Shaper { face, px_per_unit })
+    }
+    //shapes a run of one font and direction, glyphs are returned in visual order
+    fn shape_run(&self, text: &str, rtl: bool) -> rustybuzz::GlyphBuffer {
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        buffer.set_direction(if rtl { rustybuzz::Direction::RightToLeft } else { rustybuzz::Direction::LeftToRight });
+        rustybuzz::shape(&self.face, &[], buffer)
+    }
+}
+
+//splits a bidi run into runs of chars covered by the same face of the fallback chain,
+//whitespace and combining marks stay with the run before them
+fn font_runs(text: &str, start: usize, fonts: &FontManager, style: FontStyle) -> Vec<(usize, Range<usize>)> {
+    let mut runs: Vec<(usize, Range<usize>)> = Vec::new();
+    for (i, ch) in text.char_indices() {
+        let idx = start + i;
+        let end = idx + ch.len_utf8();
+        let font = match runs.last() {
+            Some((font, _)) if ch.is_whitespace() || fonts.face(*font).has_char(ch) => *font,
+            _ => fonts.font_for_char(style, ch)
+        };
+        match runs.last_mut() {
+            Some((last_font, range)) if *last_font == font => range.end = end,
+            _ => runs.push((font, idx..end))
+        }
+    }
+    runs
+}
+
+//splits the line into bidi runs in visual order and shapes each one with the fonts covering it
+pub fn shape_line(line: &str, fonts: &FontManager, style: FontStyle) -> ShapedLine {
+    let mut glyphs = Vec::new();
+    let mut pen = 0.;
+    if !line.is_empty() {
+        let bidi = BidiInfo::new(line, None);
+        let para = &bidi.paragraphs[0];
+        let (levels, runs) = bidi.visual_runs(para, para.range.clone());
+        for run in runs {
+            let rtl = levels[run.start].is_rtl();
+            let mut font_runs = font_runs(&line[run.clone()], run.start, fonts, style);
+            if rtl {
+                font_runs.reverse();
+            }
+            for (font, range) in font_runs {
+                let shaper = &fonts.face(font).shaper;
+                let shaped = shaper.shape_run(&line[range.clone()], rtl);
+                for (info, pos) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+                    let x_advance = pos.x_advance as f32 * shaper.px_per_unit;
+                    glyphs.push(ShapedGlyph {
+                        id: info.glyph_id,
+                        font,
+                        cluster: range.start + info.cluster as usize,
+                        x: pen,
+                        x_advance,
+                        x_offset: pos.x_offset as f32 * shaper.px_per_unit,
+                        y_offset: pos.y_offset as f32 * shaper.px_per_unit,
+                        rtl
+                    });
+                    pen += x_advance;
+                }
+            }
+        }
+    }
+    ShapedLine { glyphs, width: pen, text: line.to_string() }
+}
+
+pub struct LineCache {
+    lines: RefCell<HashMap<(String, FontStyle), Rc<ShapedLine>>>,
+}
+
+impl LineCache {
+    const MAX_CACHED_LINES: usize = 1024;
+
+    pub fn new() -> Self {
+        LineCache { lines: RefCell::new(HashMap::new()) }
+    }
+    pub fn get(&self, line: &str, fonts: &FontManager, style: FontStyle) -> Rc<ShapedLine> {
+        let key = (line.to_string(), style);
+        if let Some(shaped) = self.lines.borrow().get(&key) {
+            return shaped.clone();
+        }
+        let shaped = Rc::new(shape_line(line, fonts, style));
+        let mut lines = self.lines.borrow_mut();
+        if lines.len() > LineCache::MAX_CACHED_LINES {
+            lines.clear();
+        }
+        lines.insert(key, shaped.clone());
+        shaped
+    }
+}
+
+impl Default for LineCache {
+    fn default() -> Self {
+        LineCache::new()
+    }
+}
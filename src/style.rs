@@ -0,0 +1,238 @@
+extern crate nalgebra_glm;
+
+use nalgebra_glm as glm;
+use std::collections::BTreeMap;
+use crate::primitives::{Shape, LineMarker, LineRoute, rgb_to_f32};
+use crate::textedit::{TextBox, TextAlign, VerticalAlign, TextFit, TextOverflow};
+use crate::hexcolor::{color_to_hex, hex_to_color};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Style {
+    pub fill_color: glm::Vec4,
+    pub stroke_color: glm::Vec4,
+    pub stroke_width: f32,
+    pub fill: bool,
+    pub text_color: glm::Vec4,
+    pub text_scale: f32,
+    pub text_outline_color: glm::Vec4,
+    pub text_outline_width: f32,
+    pub text_shadow_color: glm::Vec4,
+    pub text_align: TextAlign,
+    pub text_valign: VerticalAlign,
+    pub text_padding: f32,
+    pub text_fit: TextFit,
+    pub text_overflow: TextOverflow,
+    pub start_marker: LineMarker,
+    pub end_marker: LineMarker,
+    pub route: LineRoute,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fill_color: rgb_to_f32(255, 0, 0),
+            stroke_color: rgb_to_f32(255, 0, 0),
+            stroke_width: 0.,
+            fill: true,
+            text_color: rgb_to_f32(0, 0, 0),
+            text_scale: 0.7,
+            text_outline_color: glm::vec4(0., 0., 0., 0.),
+            text_outline_width: 0.,
+            text_shadow_color: glm::vec4(0., 0., 0., 0.),
+            text_align: TextAlign::Left,
+            text_valign: VerticalAlign::Top,
+            text_padding: 0.,
+            text_fit: TextFit::Fixed,
+            text_overflow: TextOverflow::Clip,
+            start_marker: LineMarker::NoMarker,
+            end_marker: LineMarker::NoMarker,
+            route: LineRoute::Straight,
+        }
+    }
+}
+
+impl Style {
+    pub fn apply(&self, shape: &mut Shape) {
+        match shape {
+            Shape::Polygon(ref mut poly) => {
+                poly.color = self.fill_color;
+                poly.stroke_color = self.stroke_color;
+                poly.line_width = self.stroke_width;
+                poly.set_fill(self.fill);
+            }
+            Shape::Line(ref mut line) => {
+                line.color = self.stroke_color;
+                line.line_width = f32::max(self.stroke_width, 1.);
+                line.start_marker = self.start_marker;
+                line.end_marker = self.end_marker;
+                line.route = self.route;
+            }
+        }
+    }
+    pub fn apply_text(&self, tb: &mut TextBox) {
+        tb.text_color = self.text_color;
+        tb.effects.outline_color = self.text_outline_color;
+        tb.effects.outline_width = self.text_outline_width;
+        tb.effects.shadow_color = self.text_shadow_color;
+        tb.set_text_scale(self.text_scale);
+        tb.set_align(self.text_align);
+        tb.set_valign(self.text_valign);
+        tb.set_padding(self.text_padding);
+        tb.set_fit(self.text_fit);
+        tb.set_overflow(self.text_overflow);
+    }
+    //copy the look of a shape back into the style
+    pub fn update_from(&mut self, shape: &Shape, tb: Option<&TextBox>) {
+        match shape {
+            Shape::Polygon(ref poly) => {
+                self.fill_color = poly.color;
+                self.stroke_color = poly.stroke_color;
+                self.stroke_width = poly.line_width;
+                self.fill = poly.fill;
+            }
+            Shape::Line(ref line) => {
+                self.stroke_color = line.color;
+                self.stroke_width = line.line_width;
+                self.start_marker = line.start_marker;
+                self.end_marker = line.end_marker;
+                self.route = line.route;
+            }
+        }
+        if let Some(tb) = tb {
+            self.text_color = tb.text_color;
+            self.text_scale = tb.text_scale();
+            self.text_outline_color = tb.effects.outline_color;
+            self.text_outline_width = tb.effects.outline_width;
+            self.text_shadow_color = tb.effects.shadow_color;
+            self.text_align = tb.align();
+            self.text_valign = tb.valign();
+            self.text_padding = tb.padding();
+            self.text_fit = tb.fit();
+            self.text_overflow = tb.overflow();
+        }
+    }
+    fn set_field(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let color = || hex_to_color(value).ok_or(format!("Invalid color {:?}", value));
+        let num = || value.parse::<f32>().map_err(|_| format!("Invalid number {:?}", value));
+        let marker = || LineMarker::from_name(value).ok_or(format!("Invalid line marker {:?}", value));
+        match key {
+            "fill_color" => self.fill_color = color()?,
+            "stroke_color" => self.stroke_color = color()?,
+            "stroke_width" => self.stroke_width = num()?,
+            "fill" => self.fill = value.parse::<bool>().map_err(|_| format!("Invalid bool {:?}", value))?,
+            "text_color" => self.text_color = color()?,
+            "text_scale" => self.text_scale = num()?,
+            "text_outline_color" => self.text_outline_color = color()?,
+            "text_outline_width" => self.text_outline_width = num()?,
+            "text_shadow_color" => self.text_shadow_color = color()?,
+            "text_align" => self.text_align = TextAlign::from_name(value).ok_or(format!("Invalid text alignment {:?}", value))?,
+            "text_valign" => self.text_valign = VerticalAlign::from_name(value).ok_or(format!("Invalid vertical alignment {:?}", value))?,
+            "text_padding" => self.text_padding = num()?,
+            "text_fit" => self.text_fit = TextFit::from_name(value).ok_or(format!("Invalid text fit {:?}", value))?,
+            "text_overflow" => self.text_overflow = TextOverflow::from_name(value).ok_or(format!("Invalid text overflow {:?}", value))?,
+            "start_marker" => self.start_marker = marker()?,
+            "end_marker" => self.end_marker = marker()?,
+            "route" => self.route = LineRoute::from_name(value).ok_or(format!("Invalid line route {:?}", value))?,
+            _ => return Err(format!("Unknown style property {:?}", key))
+        }
+        Ok(())
+    }
+    fn write(&self, out: &mut String) {
+        out.push_str(&format!("fill_color = {}\n", color_to_hex(&self.fill_color)));
+        out.push_str(&format!("stroke_color = {}\n", color_to_hex(&self.stroke_color)));
+        out.push_str(&format!("stroke_width = {}\n", self.stroke_width));
+        out.push_str(&format!("fill = {}\n", self.fill));
+        out.push_str(&format!("text_color = {}\n", color_to_hex(&self.text_color)));
+        out.push_str(&format!("text_scale = {}\n", self.text_scale));
+        out.push_str(&format!("text_outline_color = {}\n", color_to_hex(&self.text_outline_color)));
+        out.push_str(&format!("text_outline_width = {}\n", self.text_outline_width));
+        out.push_str(&format!("text_shadow_color = {}\n", color_to_hex(&self.text_shadow_color)));
+        out.push_str(&format!("text_align = {}\n", self.text_align.name()));
+        out.push_str(&format!("text_valign = {}\n", self.text_valign.name()));
+        out.push_str(&format!("text_padding = {}\n", self.text_padding));
+        out.push_str(&format!("text_fit = {}\n", self.text_fit.name()));
+        out.push_str(&format!("text_overflow = {}\n", self.text_overflow.name()));
+        out.push_str(&format!("start_marker = {}\n", self.start_marker.name()));
+        out.push_str(&format!("end_marker = {}\n", self.end_marker.name()));
+        out.push_str(&format!("route = {}\n", self.route.name()));
+    }
+}
+
+pub struct StyleSheet {
+    styles: BTreeMap<String, Style>,
+}
+
+impl StyleSheet {
+    pub const DEFAULT: &'static str = "default";
+    pub const TEXT: &'static str = "text";
+    pub const LINE: &'static str = "line";
+    pub const PATH: &'static str = "diagram.theme";
+
+    pub fn default_theme() -> Self {
+        StyleSheet::parse(include_str!("styles/default.theme")).unwrap()
+    }
+    //styles are ini-like sections, properties missing from a section are taken from [default]
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut styles: BTreeMap<String, Style> = BTreeMap::new();
+        let mut current: Option<String> = None;
+        for (n, line) in src.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                let name = line[1..line.len()-1].trim().to_string();
+                let base = styles.get(StyleSheet::DEFAULT).cloned().unwrap_or_default();
+                styles.insert(name.clone(), base);
+                current = Some(name);
+                continue;
+            }
+            let mut kv = line.splitn(2, '=');
+            let (key, value) = match (kv.next(), kv.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => return Err(format!("Line {}: expected `property = value`", n + 1))
+            };
+            let style = current.as_ref().and_then(|name| styles.get_mut(name))
+                .ok_or(format!("Line {}: property outside of a [style] section", n + 1))?;
+            style.set_field(key, value).map_err(|e| format!("Line {}: {}", n + 1, e))?;
+        }
+        styles.entry(StyleSheet::DEFAULT.to_string()).or_default();
+        Ok(StyleSheet { styles })
+    }
+    pub fn load(path: &str) -> Result<Self, String> {
+        let src = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        StyleSheet::parse(&src)
+    }
+    pub fn load_or_default(path: &str) -> Self {
+        if !std::path::Path::new(path).exists() {
+            return StyleSheet::default_theme();
+        }
+        StyleSheet::load(path).unwrap_or_else(|e| {
+            eprintln!("Error loading stylesheet, using default theme: {}", e);
+            StyleSheet::default_theme()
+        })
+    }
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut out = String::new();
+        for (name, style) in self.styles.iter() {
+            out.push_str(&format!("[{}]\n", name));
+            style.write(&mut out);
+            out.push('\n');
+        }
+        std::fs::write(path, out).map_err(|e| format!("Could not write {}: {}", path, e))
+    }
+    pub fn get(&self, name: &str) -> &Style {
+        self.styles.get(name).unwrap_or(&self.styles[StyleSheet::DEFAULT])
+    }
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Style> {
+        self.styles.get_mut(name)
+    }
+    pub fn names(&self) -> Vec<String> {
+        self.styles.keys().cloned().collect()
+    }
+    pub fn next_name(&self, name: &str) -> String {
+        let names = self.names();
+        let i = names.iter().position(|n| n == name).map_or(0, |i| (i + 1) % names.len());
+        names[i].clone()
+    }
+}
@@ -0,0 +1,185 @@
+extern crate nalgebra_glm;
+
+use std::collections::HashMap;
+use nalgebra_glm as glm;
+use crate::primitives::*;
+use crate::render_text::PlacedText;
+
+//numbers are written with at most 3 decimals
+fn num(n: f32) -> String {
+    let s = format!("{:.3}", n);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" { "0".to_string() } else { s.to_string() }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn hex(color: &glm::Vec4) -> String {
+    let c = |v: f32| (v.clamp(0., 1.) * 255.).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", c(color.x), c(color.y), c(color.z))
+}
+
+//glyphs are written as text in the face's family, a viewer without it falls back to its own
+#[derive(Clone, Debug)]
+pub struct SvgFace {
+    pub family: String,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+//one page of the diagram, written as its own SVG file
+pub struct SvgPage {
+    bounds: Rect,                   // Part of the canvas on the page, in pixels
+    body: String,
+    path: String,                   // Path data of the shape being drawn
+}
+
+impl SvgPage {
+    pub const MARGIN: f32 = 20.;
+
+    //the page fits `bounds` and a margin. content is drawn in canvas pixels, an SVG pixel is a canvas one
+    pub fn new(bounds: &Rect) -> Self {
+        let margin = Point::new(SvgPage::MARGIN, SvgPage::MARGIN);
+        SvgPage { bounds: Rect::new(bounds.c1 - margin, bounds.c2 + margin), body: String::new(), path: String::new() }
+    }
+    //the file each page goes to, numbered from 1 in page order
+    pub fn path(page: usize) -> String {
+        format!("diagram-{}.svg", page + 1)
+    }
+    pub fn size(&self) -> Point {
+        self.bounds.size()
+    }
+    fn element(&mut self, el: &str) {
+        self.body.push_str(el);
+        self.body.push('\n');
+    }
+    pub fn group(&mut self, m: &glm::Mat4) {
+        self.element(&format!("<g transform=\"matrix({} {} {} {} {} {})\">",
+            num(m[(0, 0)]), num(m[(1, 0)]), num(m[(0, 1)]), num(m[(1, 1)]), num(m[(0, 3)]), num(m[(1, 3)])));
+    }
+    pub fn end_group(&mut self) {
+        self.element("</g>");
+    }
+    pub fn move_to(&mut self, p: &Point) {
+        self.path.push_str(&format!("M{} {} ", num(p.x), num(p.y)));
+    }
+    pub fn line_to(&mut self, p: &Point) {
+        self.path.push_str(&format!("L{} {} ", num(p.x), num(p.y)));
+    }
+    pub fn curve_to(&mut self, c1: &Point, c2: &Point, p: &Point) {
+        self.path.push_str(&format!("C{} {} {} {} {} {} ", num(c1.x), num(c1.y), num(c2.x), num(c2.y), num(p.x), num(p.y)));
+    }
+    pub fn close(&mut self) {
+        self.path.push_str("Z ");
+    }
+    pub fn polyline(&mut self, pts: &[Point]) {
+        if let Some((first, rest)) = pts.split_first() {
+            self.move_to(first);
+            rest.iter().for_each(|p| self.line_to(p));
+        }
+    }
+    pub fn polygon(&mut self, pts: &[Point]) {
+        self.polyline(pts);
+        self.close();
+    }
+    //the path drawn since the last fill or stroke is filled. `opacity` is the layer's, it multiplies the color's alpha
+    pub fn fill(&mut self, color: &glm::Vec4, opacity: f32) {
+        let d = std::mem::take(&mut self.path);
+        self.element(&format!("<path d=\"{}\" fill=\"{}\" fill-opacity=\"{}\"/>", d.trim_end(), hex(color), num(color.w * opacity)));
+    }
+    pub fn stroke(&mut self, color: &glm::Vec4, opacity: f32, width: f32) {
+        let d = std::mem::take(&mut self.path);
+        self.element(&format!("<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"round\"/>",
+            d.trim_end(), hex(color), num(color.w * opacity), num(width)));
+    }
+    //`text` with its baseline starting at `pen`
+    pub fn text(&mut self, face: &SvgFace, text: &str, pen: &Point, size: f32, color: &glm::Vec4, opacity: f32) {
+        let weight = if face.bold { " font-weight=\"bold\"" } else { "" };
+        let style = if face.italic { " font-style=\"italic\"" } else { "" };
+        self.element(&format!("<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"{}\"{}{} fill=\"{}\" fill-opacity=\"{}\" xml:space=\"preserve\">{}</text>",
+            num(pen.x), num(pen.y), escape(&face.family), num(size), weight, style, hex(color), num(color.w * opacity), escape(text)));
+    }
+    pub fn write(&self) -> String {
+        let size = self.size();
+        let (c1, c2) = (self.bounds.c1, self.bounds.c2);
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str(&format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">\n",
+            num(size.x), num(size.y), num(c1.x), num(c1.y), num(c2.x - c1.x), num(c2.y - c1.y)));
+        out.push_str(&self.body);
+        out.push_str("</svg>\n");
+        out
+    }
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.write()).map_err(|e| format!("Could not write {}: {}", path, e))
+    }
+}
+
+//four bezier arcs around the ellipse filling the unit square
+fn ellipse(page: &mut SvgPage, to_canvas: &dyn Fn(&Point) -> Point) {
+    //distance of the control points from the ends of a quarter of a unit circle
+    const KAPPA: f32 = 0.5523;
+    let at = |x: f32, y: f32| to_canvas(&Point::new(0.5 + 0.5 * x, 0.5 + 0.5 * y));
+    let dirs = [(1., 0.), (0., 1.), (-1., 0.), (0., -1.)];
+    page.move_to(&at(1., 0.));
+    for i in 0..4 {
+        let ((ax, ay), (bx, by)) = (dirs[i], dirs[(i + 1) % 4]);
+        page.curve_to(&at(ax + KAPPA * bx, ay + KAPPA * by), &at(bx + KAPPA * ax, by + KAPPA * ay), &at(bx, by));
+    }
+    page.close();
+}
+
+fn draw_polygon(page: &mut SvgPage, poly: &DrawPolygon, opacity: f32, vp: &Point) {
+    let trans = poly.rect.transform(vp);
+    let to_canvas = |p: &Point| trans.model_to_pixel(&p.to_vec4());
+    let outline = |page: &mut SvgPage| match poly.prim {
+        PrimType::Circle | PrimType::Ring => ellipse(page, &to_canvas),
+        prim => page.polygon(&prim.outline().iter().map(&to_canvas).collect::<Vec<Point>>())
+    };
+    if poly.fill {
+        outline(page);
+        page.fill(&poly.color, opacity);
+    }
+    //unfilled shapes always get at least a hairline outline
+    if !poly.fill || poly.line_width > 0. {
+        outline(page);
+        page.polyline(&poly.prim.detail().iter().map(&to_canvas).collect::<Vec<Point>>());
+        page.stroke(&poly.stroke_color, opacity, f32::max(poly.line_width, 1.));
+    }
+}
+
+//a shape filled and stroked like it's drawn on screen, a polygon's outline is taken to the canvas by its rect's model matrix
+pub fn draw_shape(page: &mut SvgPage, shape: &Shape, opacity: f32, vp: &Point) {
+    match shape {
+        Shape::Polygon(poly) => draw_polygon(page, poly, opacity, vp),
+        Shape::Line(line) => {
+            page.polyline(&line.points());
+            page.stroke(&line.color, opacity, line.line_width);
+            for marker in line.markers() {
+                draw_polygon(page, &marker, opacity, vp);
+            }
+        }
+    }
+}
+
+//text placed in `rect` is drawn in the rect's space, turned with it. `faces` has the family and style of each face used
+pub fn draw_text(page: &mut SvgPage, text: &PlacedText, faces: &HashMap<usize, SvgFace>, rect: &RotateRect, opacity: f32, vp: &Point) {
+    if (text.glyphs.is_empty() && text.lines.is_empty()) || rect.size.x == 0. || rect.size.y == 0. {
+        return;
+    }
+    let model = glm::scale(&rect.transform(vp).model, &glm::vec3(1. / rect.size.x, 1. / rect.size.y, 1.));
+    page.group(&model);
+    //the rest of a cluster's glyphs have no text of their own
+    for g in text.glyphs.iter().filter(|g| !g.text.is_empty()) {
+        if let Some(face) = faces.get(&g.font) {
+            page.text(face, &g.text, &g.pen, g.size, &g.color, opacity);
+        }
+    }
+    for (r, color) in text.lines.iter() {
+        page.polygon(&r.verts());
+        page.fill(color, opacity);
+    }
+    page.end_group();
+}
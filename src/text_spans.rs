@@ -0,0 +1,192 @@
+extern crate nalgebra_glm;
+
+use std::ops::Range;
+use nalgebra_glm as glm;
+use crate::fonts::FontStyle;
+use crate::hexcolor::{color_to_hex, hex_to_color};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TextAttr {
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TextAttrs {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+    pub color: Option<glm::Vec4>,   // Uses the text box color when unset
+    pub size: f32,                  // Multiplier of the text box scale
+}
+
+impl Default for TextAttrs {
+    fn default() -> Self {
+        TextAttrs { bold: false, italic: false, underline: false, strikethrough: false, color: None, size: 1. }
+    }
+}
+
+impl TextAttrs {
+    pub const MIN_SIZE: f32 = 0.25;
+    pub const MAX_SIZE: f32 = 8.;
+
+    pub fn get(&self, attr: TextAttr) -> bool {
+        match attr {
+            TextAttr::Bold => self.bold,
+            TextAttr::Italic => self.italic,
+            TextAttr::Underline => self.underline,
+            TextAttr::Strikethrough => self.strikethrough,
+        }
+    }
+    pub fn set(&mut self, attr: TextAttr, on: bool) {
+        match attr {
+            TextAttr::Bold => self.bold = on,
+            TextAttr::Italic => self.italic = on,
+            TextAttr::Underline => self.underline = on,
+            TextAttr::Strikethrough => self.strikethrough = on,
+        }
+    }
+    pub fn font_style(&self) -> FontStyle {
+        FontStyle::new(self.bold, self.italic)
+    }
+    //space separated flags, eg. `bold underline color=#FF0000FF size=1.5`
+    pub fn write(&self) -> String {
+        let mut out = Vec::new();
+        if self.bold { out.push("bold".to_string()); }
+        if self.italic { out.push("italic".to_string()); }
+        if self.underline { out.push("underline".to_string()); }
+        if self.strikethrough { out.push("strikethrough".to_string()); }
+        if let Some(color) = self.color {
+            out.push(format!("color={}", color_to_hex(&color)));
+        }
+        if self.size != 1. {
+            out.push(format!("size={}", self.size));
+        }
+        out.join(" ")
+    }
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut attrs = TextAttrs::default();
+        for flag in src.split_whitespace() {
+            let mut kv = flag.splitn(2, '=');
+            match (kv.next().unwrap_or(""), kv.next()) {
+                ("bold", None) => attrs.bold = true,
+                ("italic", None) => attrs.italic = true,
+                ("underline", None) => attrs.underline = true,
+                ("strikethrough", None) => attrs.strikethrough = true,
+                ("color", Some(value)) => attrs.color = Some(hex_to_color(value).ok_or(format!("Invalid color {:?}", value))?),
+                ("size", Some(value)) => attrs.size = value.parse::<f32>().ok()
+                    .filter(|s| *s >= TextAttrs::MIN_SIZE && *s <= TextAttrs::MAX_SIZE)
+                    .ok_or(format!("Invalid size {:?}", value))?,
+                _ => return Err(format!("Unknown text attribute {:?}", flag))
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+//runs covering `range`, cut at its ends
+pub fn slice_runs(runs: &[(usize, TextAttrs)], range: Range<usize>) -> Vec<(usize, TextAttrs)> {
+    let mut pos = 0;
+    let mut out = Vec::new();
+    for (n, attrs) in runs.iter() {
+        let (start, end) = (std::cmp::max(pos, range.start), std::cmp::min(pos + n, range.end));
+        if start < end {
+            out.push((end - start, *attrs));
+        }
+        pos += n;
+    }
+    out
+}
+
+//run length list of the attributes of every char in a text, run lengths are in chars.
+//it has to be edited along with the text so the two stay the same length
+#[derive(Clone, Debug, Default)]
+pub struct SpanList {
+    runs: Vec<(usize, TextAttrs)>,
+}
+
+impl SpanList {
+    pub fn new() -> Self {
+        SpanList { runs: Vec::new() }
+    }
+    pub fn len(&self) -> usize {
+        self.runs.iter().map(|(n, _)| n).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn runs(&self) -> &[(usize, TextAttrs)] {
+        &self.runs
+    }
+    //attributes of the char at `idx`, or of the last char when past the end
+    pub fn attrs_at(&self, idx: usize) -> TextAttrs {
+        let mut pos = 0;
+        for (n, attrs) in self.runs.iter() {
+            pos += n;
+            if idx < pos {
+                return *attrs;
+            }
+        }
+        self.runs.last().map(|(_, attrs)| *attrs).unwrap_or_default()
+    }
+    pub fn slice(&self, range: Range<usize>) -> Vec<(usize, TextAttrs)> {
+        slice_runs(&self.runs, range)
+    }
+    //attributes of each char in `range`
+    pub fn char_attrs(&self, range: Range<usize>) -> impl Iterator<Item = TextAttrs> {
+        self.slice(range).into_iter().flat_map(|(n, attrs)| std::iter::repeat_n(attrs, n))
+    }
+    pub fn all(&self, range: Range<usize>, attr: TextAttr) -> bool {
+        self.slice(range).iter().all(|(_, attrs)| attrs.get(attr))
+    }
+    //makes sure a run starts at `idx` and returns its index
+    fn split(&mut self, idx: usize) -> usize {
+        let mut pos = 0;
+        for i in 0..self.runs.len() {
+            let (n, attrs) = self.runs[i];
+            if idx == pos {
+                return i;
+            }
+            if idx < pos + n {
+                self.runs[i].0 = idx - pos;
+                self.runs.insert(i + 1, (pos + n - idx, attrs));
+                return i + 1;
+            }
+            pos += n;
+        }
+        self.runs.len()
+    }
+    fn merge(&mut self) {
+        let mut runs: Vec<(usize, TextAttrs)> = Vec::with_capacity(self.runs.len());
+        for (n, attrs) in self.runs.drain(..).filter(|(n, _)| *n > 0) {
+            match runs.last_mut() {
+                Some((last_n, last_attrs)) if *last_attrs == attrs => *last_n += n,
+                _ => runs.push((n, attrs))
+            }
+        }
+        self.runs = runs;
+    }
+    pub fn insert(&mut self, idx: usize, n: usize, attrs: TextAttrs) {
+        let i = self.split(idx);
+        self.runs.insert(i, (n, attrs));
+        self.merge();
+    }
+    pub fn remove(&mut self, range: Range<usize>) {
+        let start = self.split(range.start);
+        let end = self.split(range.end);
+        self.runs.drain(start..end);
+        self.merge();
+    }
+    pub fn update<F: Fn(&mut TextAttrs)>(&mut self, range: Range<usize>, f: F) {
+        let start = self.split(range.start);
+        let end = self.split(range.end);
+        self.runs[start..end].iter_mut().for_each(|(_, attrs)| f(attrs));
+        self.merge();
+    }
+    pub fn clear(&mut self) {
+        self.runs.clear();
+    }
+}
@@ -1,173 +1,989 @@
 extern crate ropey;
 extern crate sdl2;
+extern crate nalgebra_glm;
 
+use nalgebra_glm as glm;
 use ropey::Rope;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
 use std::time::{SystemTime};
-use crate::primitives::{Point, RotateRect, DrawCtx, LineBuilder, TransformCache, Radians, rgb_to_f32};
-use crate::render_text::{RenderText, TextParams, TextUniforms};
+use crate::primitives::{Point, Rect, RotateRect, DrawCtx, LineBuilder, TransformCache, Radians, rgb_to_f32};
+use crate::render_text::{RenderText, TextParams, TextUniforms, TextEffects, LineLayout, LinePlacement, AttrRun, PlacedText};
+use crate::text_spans::{SpanList, TextAttr, TextAttrs};
+use crate::hexcolor::{color_to_hex, hex_to_color};
+use crate::commands::KeyChord;
+use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 
-#[derive(Debug)]
+//the selection is between the anchor and the cursor
+#[derive(Clone, Debug)]
 struct TextCursor {
-    char_idx: usize
+    char_idx: usize,
+    anchor: Option<usize>,
 }
 
 impl TextCursor {
     fn new() -> Self {
-        TextCursor { char_idx: 0 }
+        TextCursor { char_idx: 0, anchor: None }
     }
 }
 
 pub enum TextCursorDirection {
-    Up, Down, Left, Right
+    Up, Down, Left, Right,
+    WordLeft, WordRight,
+    LineStart, LineEnd,
+    TextStart, TextEnd,
+}
+
+//IME text still being composed, shown underlined at the cursor until it is committed
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Composition {
+    pub text: String,
+    pub cursor: usize,      // Char index of the IME cursor in the text
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum TextInput {
+    Commit(String),
+    Compose(Composition),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum EditKind {
+    Typing,
+    Deleting,
+    Other,
+}
+
+#[derive(Clone)]
+struct TextSnapshot {
+    rope: Rope,
+    spans: SpanList,
+    cursor: usize,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum CharClass {
+    Word,
+    Space,
+    Punct,
+}
+
+fn char_class(ch: char) -> CharClass {
+    if ch.is_alphanumeric() || ch == '_' { CharClass::Word }
+    else if ch.is_whitespace() { CharClass::Space }
+    else { CharClass::Punct }
+}
+
+//a line as drawn, a paragraph of the text or a part of it when it's wrapped.
+//a wrapped line keeps the spaces it ends with, the newline isn't part of a paragraph's last line
+struct VisualLine {
+    start: usize,
+    end: usize,
+    wrapped: bool,
+    layout: LineLayout,
+}
+
+impl VisualLine {
+    fn new(start: usize, end: usize, wrapped: bool) -> Self {
+        VisualLine { start, end, wrapped, layout: LineLayout::default() }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+impl TextAlign {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextAlign::Left => "left",
+            TextAlign::Center => "center",
+            TextAlign::Right => "right",
+            TextAlign::Justify => "justify",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<TextAlign> {
+        match name {
+            "left" => Some(TextAlign::Left),
+            "center" => Some(TextAlign::Center),
+            "right" => Some(TextAlign::Right),
+            "justify" => Some(TextAlign::Justify),
+            _ => None
+        }
+    }
+    pub fn next(&self) -> TextAlign {
+        match self {
+            TextAlign::Left => TextAlign::Center,
+            TextAlign::Center => TextAlign::Right,
+            TextAlign::Right => TextAlign::Justify,
+            TextAlign::Justify => TextAlign::Left,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum VerticalAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl VerticalAlign {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VerticalAlign::Top => "top",
+            VerticalAlign::Middle => "middle",
+            VerticalAlign::Bottom => "bottom",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<VerticalAlign> {
+        match name {
+            "top" => Some(VerticalAlign::Top),
+            "middle" => Some(VerticalAlign::Middle),
+            "bottom" => Some(VerticalAlign::Bottom),
+            _ => None
+        }
+    }
+    pub fn next(&self) -> VerticalAlign {
+        match self {
+            VerticalAlign::Top => VerticalAlign::Middle,
+            VerticalAlign::Middle => VerticalAlign::Bottom,
+            VerticalAlign::Bottom => VerticalAlign::Top,
+        }
+    }
+}
+
+//what gives way when the text doesn't fit the box
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TextFit {
+    Fixed,
+    GrowShape,      // The shape's height follows the text
+    ShrinkText,     // The text is drawn smaller than its scale until it fits
+}
+
+impl TextFit {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextFit::Fixed => "fixed",
+            TextFit::GrowShape => "grow",
+            TextFit::ShrinkText => "shrink",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<TextFit> {
+        match name {
+            "fixed" => Some(TextFit::Fixed),
+            "grow" => Some(TextFit::GrowShape),
+            "shrink" => Some(TextFit::ShrinkText),
+            _ => None
+        }
+    }
+    pub fn next(&self) -> TextFit {
+        match self {
+            TextFit::Fixed => TextFit::GrowShape,
+            TextFit::GrowShape => TextFit::ShrinkText,
+            TextFit::ShrinkText => TextFit::Fixed,
+        }
+    }
+}
+
+//what happens to lines that don't fit in the box
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TextOverflow {
+    Clip,
+    Scroll,         // Scrolled with the mouse wheel while editing, with a scrollbar
+    Ellipsis,       // The last line shown ends with an ellipsis when it isn't being edited
+}
+
+impl TextOverflow {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextOverflow::Clip => "clip",
+            TextOverflow::Scroll => "scroll",
+            TextOverflow::Ellipsis => "ellipsis",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<TextOverflow> {
+        match name {
+            "clip" => Some(TextOverflow::Clip),
+            "scroll" => Some(TextOverflow::Scroll),
+            "ellipsis" => Some(TextOverflow::Ellipsis),
+            _ => None
+        }
+    }
+    pub fn next(&self) -> TextOverflow {
+        match self {
+            TextOverflow::Clip => TextOverflow::Scroll,
+            TextOverflow::Scroll => TextOverflow::Ellipsis,
+            TextOverflow::Ellipsis => TextOverflow::Clip,
+        }
+    }
+}
+
+//the wrapped lines and the scale they were laid out at, which is smaller than the box's when shrunk to fit
+struct TextLayout {
+    scale: f32,
+    lines: Vec<VisualLine>,
 }
 
 //#[derive(Debug)]
+#[derive(Clone)]
 pub struct TextBox {
     text_rope: Rope,
+    spans: SpanList,
+    typing_attrs: Option<TextAttrs>,
     text_scale: f32,
-    top_line: usize,
+    pub text_color: glm::Vec4,
+    pub effects: TextEffects,
+    align: TextAlign,
+    valign: VerticalAlign,
+    padding: f32,
+    fit: TextFit,
+    overflow: TextOverflow,
+    top_line: usize,                // First visual line shown
+    follow_cursor: bool,            // The cursor moved or the text changed since the box was scrolled to the cursor
     cursor: TextCursor,
-    trans: TransformCache<(Point, Radians), TextUniforms>
+    composition: Option<Composition>,
+    undo_stack: Vec<TextSnapshot>,
+    redo_stack: Vec<TextSnapshot>,
+    last_edit: Option<EditKind>,
+    wrap_cache: RefCell<Option<(Point, Rc<TextLayout>)>>,    // Wrapped lines and the box size they fit
+    trans: TransformCache<(Point, Radians, Point), TextUniforms>
 }
 
 impl TextBox {
     pub fn new() -> Self {
         TextBox {
             text_rope: Rope::new(),
+            spans: SpanList::new(),
+            typing_attrs: None,
             top_line: 0,
             text_scale: 0.7,
+            text_color: rgb_to_f32(0, 0, 0),
+            effects: TextEffects::default(),
+            align: TextAlign::Left,
+            valign: VerticalAlign::Top,
+            padding: 0.,
+            fit: TextFit::Fixed,
+            overflow: TextOverflow::Clip,
+            follow_cursor: false,
             cursor: TextCursor::new(),
+            composition: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            last_edit: None,
+            wrap_cache: RefCell::new(None),
             trans: TransformCache::new()
         }
     }
-    pub fn trans(&self, r: &RotateRect, rt: &RenderText, color: &(u8, u8, u8), vp: &Point) -> TextUniforms {
-        let off = Point::new(0., rt.line_height(self.text_scale));
+    pub fn trans(&self, r: &RotateRect, vp: &Point) -> TextUniforms {
         self.trans.transform(
-            (r.offset, r.rot),
-            Box::new(move || TextUniforms::new(&rgb_to_f32(color.0, color.1, color.2), r, &off, vp))
+            (r.offset, r.rot, r.size),
+            Box::new(move || TextUniforms::new(r, vp))
         )
     }
-    pub fn get_params<'a>(&self, text: &'a str, color: &(u8, u8, u8), trans: &'a TextUniforms) 
+    pub fn text(&self) -> String {
+        self.text_rope.to_string()
+    }
+    pub fn clear(&mut self) {
+        self.text_rope = Rope::new();
+        self.spans.clear();
+        self.typing_attrs = None;
+        self.cursor = TextCursor::new();
+        self.composition = None;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.last_edit = None;
+        self.top_line = 0;
+        self.text_changed();
+    }
+    //the text and its attribute runs as `key = value` lines, the text is escaped to a single line
+    pub fn write(&self) -> String {
+        let mut out = String::new();
+        let text: String = self.text_rope.chars().map(|c| match c {
+            '\\' => "\\\\".to_string(),
+            '\n' => "\\n".to_string(),
+            c => c.to_string()
+        }).collect();
+        out.push_str(&format!("text = {}\n", text));
+        out.push_str(&format!("scale = {}\n", self.text_scale));
+        out.push_str(&format!("color = {}\n", color_to_hex(&self.text_color)));
+        out.push_str(&format!("align = {}\n", self.align.name()));
+        out.push_str(&format!("valign = {}\n", self.valign.name()));
+        out.push_str(&format!("padding = {}\n", self.padding));
+        out.push_str(&format!("fit = {}\n", self.fit.name()));
+        out.push_str(&format!("overflow = {}\n", self.overflow.name()));
+        for (n, attrs) in self.spans.runs() {
+            out.push_str(format!("span = {} {}", n, attrs.write()).trim_end());
+            out.push('\n');
+        }
+        out
+    }
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut tb = TextBox::new();
+        let mut text = String::new();
+        for (n, line) in src.lines().enumerate() {
+            if line.trim().is_empty() || line.trim_start().starts_with(';') {
+                continue;
+            }
+            let mut kv = line.splitn(2, '=');
+            let (key, value) = match (kv.next(), kv.next()) {
+                (Some(key), Some(value)) => (key.trim(), value),
+                _ => return Err(format!("Line {}: expected `key = value`", n + 1))
+            };
+            let err = |e: String| format!("Line {}: {}", n + 1, e);
+            match key {
+                //only the space after the `=` is dropped, the rest belongs to the text
+                "text" => text = unescape(value.strip_prefix(' ').unwrap_or(value)).map_err(err)?,
+                "scale" => tb.text_scale = value.trim().parse::<f32>().map_err(|_| err(format!("Invalid scale {:?}", value)))?,
+                "color" => tb.text_color = hex_to_color(value.trim()).ok_or_else(|| err(format!("Invalid color {:?}", value)))?,
+                "align" => tb.align = TextAlign::from_name(value.trim()).ok_or_else(|| err(format!("Invalid alignment {:?}", value)))?,
+                "valign" => tb.valign = VerticalAlign::from_name(value.trim()).ok_or_else(|| err(format!("Invalid vertical alignment {:?}", value)))?,
+                "padding" => tb.padding = value.trim().parse::<f32>().map_err(|_| err(format!("Invalid padding {:?}", value)))?,
+                "fit" => tb.fit = TextFit::from_name(value.trim()).ok_or_else(|| err(format!("Invalid fit {:?}", value)))?,
+                "overflow" => tb.overflow = TextOverflow::from_name(value.trim()).ok_or_else(|| err(format!("Invalid overflow {:?}", value)))?,
+                "span" => {
+                    let value = value.trim();
+                    let (len, attrs) = value.split_at(value.find(' ').unwrap_or(value.len()));
+                    let len = len.parse::<usize>().map_err(|_| err(format!("Invalid span length {:?}", len)))?;
+                    let attrs = TextAttrs::parse(attrs).map_err(err)?;
+                    let end = tb.spans.len();
+                    tb.spans.insert(end, len, attrs);
+                }
+                _ => return Err(err(format!("Unknown text box property {:?}", key)))
+            }
+        }
+        tb.text_rope = Rope::from_str(&text);
+        let nchars = tb.text_rope.len_chars();
+        if tb.spans.is_empty() && nchars > 0 {
+            tb.spans.insert(0, nchars, TextAttrs::default());
+        }
+        if tb.spans.len() != nchars {
+            return Err(format!("Spans cover {} chars but the text has {}", tb.spans.len(), nchars));
+        }
+        Ok(tb)
+    }
+    const MAX_UNDO: usize = 200;
+    const MIN_SCALE: f32 = 0.1;
+    const SCROLLBAR_WIDTH: f32 = 4.;
+    const CARET_WIDTH: f32 = 2.;
+
+    pub fn text_scale(&self) -> f32 {
+        self.text_scale
+    }
+    pub fn set_text_scale(&mut self, scale: f32) {
+        self.text_scale = scale;
+        self.text_changed();
+    }
+    pub fn align(&self) -> TextAlign {
+        self.align
+    }
+    pub fn set_align(&mut self, align: TextAlign) {
+        self.align = align;
+        self.text_changed();
+    }
+    pub fn valign(&self) -> VerticalAlign {
+        self.valign
+    }
+    pub fn set_valign(&mut self, valign: VerticalAlign) {
+        self.valign = valign;
+    }
+    pub fn padding(&self) -> f32 {
+        self.padding
+    }
+    pub fn set_padding(&mut self, padding: f32) {
+        self.padding = f32::max(padding, 0.);
+        self.text_changed();
+    }
+    pub fn fit(&self) -> TextFit {
+        self.fit
+    }
+    pub fn set_fit(&mut self, fit: TextFit) {
+        self.fit = fit;
+        self.text_changed();
+    }
+    pub fn overflow(&self) -> TextOverflow {
+        self.overflow
+    }
+    pub fn set_overflow(&mut self, overflow: TextOverflow) {
+        self.overflow = overflow;
+    }
+    pub fn get_params<'a>(&self, text: &'a str, trans: &'a TextUniforms) 
         -> TextParams<'a> 
     {
-        TextParams::new(text, trans).color(color.0, color.1, color.2).scale(self.text_scale)
+        TextParams { color: self.text_color, ..TextParams::new(text, trans).scale(self.text_scale).effects(&self.effects) }
+    }
+    //the wrapped lines are laid out again the next time they're needed
+    fn text_changed(&mut self) {
+        self.wrap_cache.replace(None);
+        self.follow_cursor = true;
+    }
+    //the rope and its spans are always edited together
+    fn insert_text_char(&mut self, idx: usize, ch: char, attrs: TextAttrs) {
+        self.text_rope.insert_char(idx, ch);
+        self.spans.insert(idx, 1, attrs);
+        self.text_changed();
+    }
+    fn remove_text(&mut self, range: Range<usize>) {
+        self.text_rope.remove(range.clone());
+        self.spans.remove(range);
+        self.text_changed();
+    }
+    //attributes new chars get, those of the char before the cursor unless they were toggled since
+    fn typing_attrs(&self) -> TextAttrs {
+        self.typing_attrs.unwrap_or_else(|| self.spans.attrs_at(self.cursor.char_idx.saturating_sub(1)))
+    }
+    fn snapshot(&self) -> TextSnapshot {
+        TextSnapshot { rope: self.text_rope.clone(), spans: self.spans.clone(), cursor: self.cursor.char_idx }
+    }
+    fn restore(&mut self, snapshot: TextSnapshot) {
+        self.text_rope = snapshot.rope;
+        self.spans = snapshot.spans;
+        self.cursor = TextCursor { char_idx: snapshot.cursor, anchor: None };
+        self.typing_attrs = None;
+        self.last_edit = None;
+        self.text_changed();
+    }
+    //saves the text before an edit, runs of typing or deleting are undone together
+    fn begin_edit(&mut self, kind: EditKind) {
+        if kind == EditKind::Other || self.last_edit != Some(kind) {
+            let snapshot = self.snapshot();
+            self.undo_stack.push(snapshot);
+            if self.undo_stack.len() > TextBox::MAX_UNDO {
+                self.undo_stack.remove(0);
+            }
+        }
+        self.redo_stack.clear();
+        self.last_edit = Some(kind);
+    }
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            let current = self.snapshot();
+            self.redo_stack.push(current);
+            self.restore(snapshot);
+        }
+    }
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.redo_stack.pop() {
+            let current = self.snapshot();
+            self.undo_stack.push(current);
+            self.restore(snapshot);
+        }
+    }
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let cursor = self.cursor.char_idx;
+        self.cursor.anchor.filter(|a| *a != cursor)
+            .map(|a| std::cmp::min(a, cursor)..std::cmp::max(a, cursor))
+    }
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection().map(|r| self.text_rope.slice(r).to_string())
+    }
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some(range) => {
+                self.remove_text(range.clone());
+                self.cursor = TextCursor { char_idx: range.start, anchor: None };
+                true
+            }
+            None => false
+        }
+    }
+    pub fn select_all(&mut self) {
+        self.cursor = TextCursor { char_idx: self.text_rope.len_chars(), anchor: Some(0) };
+        self.follow_cursor = true;
+    }
+    //selects the run of chars of the same class around `idx`
+    pub fn select_word(&mut self, idx: usize) {
+        let len = self.text_rope.len_chars();
+        let idx = std::cmp::min(idx, len);
+        let class = match (idx < len, idx > 0) {
+            (true, _) => char_class(self.text_rope.char(idx)),
+            (false, true) => char_class(self.text_rope.char(idx - 1)),
+            _ => return
+        };
+        let mut start = idx;
+        while start > 0 && char_class(self.text_rope.char(start - 1)) == class {
+            start -= 1;
+        }
+        let mut end = idx;
+        while end < len && char_class(self.text_rope.char(end)) == class {
+            end += 1;
+        }
+        self.cursor = TextCursor { char_idx: end, anchor: Some(start) };
+        self.last_edit = None;
+    }
+    //selects the whole paragraph, not just the wrapped line
+    pub fn select_line(&mut self, idx: usize) {
+        let line_idx = self.text_rope.char_to_line(std::cmp::min(idx, self.text_rope.len_chars()));
+        let start = self.text_rope.line_to_char(line_idx);
+        let end = start + self.text_rope.line(line_idx).chars().filter(|c| *c != '\n').count();
+        self.cursor = TextCursor { char_idx: end, anchor: Some(start) };
+        self.last_edit = None;
+    }
+    pub fn composition(&self) -> Option<&Composition> {
+        self.composition.as_ref()
+    }
+    //an empty composition means the IME cancelled or is about to commit
+    pub fn set_composition(&mut self, comp: Composition) {
+        let text: String = comp.text.chars().filter(|c| !c.is_control()).collect();
+        let cursor = std::cmp::min(comp.cursor, text.chars().count());
+        self.composition = if text.is_empty() { None } else { Some(Composition { text, cursor }) };
     }
-    pub fn insert_char(&mut self, ch: char, draw_rect: &RotateRect, rt: &RenderText) {
-        self.text_rope.insert_char(self.cursor.char_idx, ch);
+    pub fn commit_text(&mut self, text: &str) {
+        self.composition = None;
+        for ch in text.chars().filter(|c| !c.is_control()) {
+            self.insert_char(ch);
+        }
+    }
+    pub fn insert_char(&mut self, ch: char) {
+        self.begin_edit(if ch.is_whitespace() { EditKind::Other } else { EditKind::Typing });
+        self.replace_selection(ch);
+    }
+    //pasted text is undone in one step
+    pub fn insert_text(&mut self, text: &str) {
+        self.begin_edit(EditKind::Other);
+        for ch in text.chars().filter(|c| *c != '\r') {
+            self.replace_selection(ch);
+        }
+        self.last_edit = None;
+    }
+    //new chars take the attributes of the selection they replace
+    fn replace_selection(&mut self, ch: char) {
+        let attrs = match (self.typing_attrs, self.selection()) {
+            (None, Some(range)) => self.spans.attrs_at(range.start),
+            _ => self.typing_attrs()
+        };
+        self.typing_attrs = None;
+        self.delete_selection();
+        self.insert_text_char(self.cursor.char_idx, ch, attrs);
         self.cursor.char_idx += 1;
-        let cursor_line = self.text_rope.char_to_line(self.cursor.char_idx);
-        let line = self.text_rope.line(cursor_line);
-        let cursor_pos = self.cursor.char_idx - self.text_rope.line_to_char(cursor_line);
-        if cursor_pos == line.len_chars() &&
-            rt.measure(line.as_str().unwrap(), self.text_scale).x > draw_rect.size.x
-        {
-            if self.cursor.char_idx == self.text_rope.len_chars() {
-                self.text_rope.insert_char(self.cursor.char_idx-1, '\n');
-                self.cursor.char_idx += 1;
-            }
-        }
-        if self.cursor.char_idx < self.text_rope.len_chars() {
-            self.format_text(draw_rect, cursor_line, rt);
-        }
-        /*if self.text_rope.len_lines() as f32 * rt.line_height(self.text_scale) > draw_rect.size.y {
-            self.top_line += 1;
-        }*/
-    }
-    pub fn delete_char(&mut self, draw_rect: &RotateRect, rt: &RenderText) {
-        if self.text_rope.len_chars() == 0 {
+    }
+    pub fn delete_char(&mut self) {
+        if self.text_rope.len_chars() == 0 || (self.cursor.char_idx == 0 && self.selection().is_none()) {
             return;
         }
-        let cursor_line = self.text_rope.char_to_line(self.cursor.char_idx);
-        if cursor_line > 0 && self.cursor.char_idx == self.text_rope.line_to_char(cursor_line) {
+        self.begin_edit(EditKind::Deleting);
+        if !self.delete_selection() {
+            self.remove_text(self.cursor.char_idx - 1..self.cursor.char_idx);
             self.cursor.char_idx -= 1;
         }
-        //println!("Cursor char index: {:?}, line char index: {:?}", self.cursor.char_idx, self.text_rope.line_to_char(cursor_line));
-        self.text_rope.remove(self.cursor.char_idx-1..self.cursor.char_idx);
-        self.cursor.char_idx -= 1;
-        if self.text_rope.len_chars() > 0 && self.cursor.char_idx < self.text_rope.len_chars() - 1 {
-            self.format_text(draw_rect, cursor_line, rt);
+        self.typing_attrs = None;
+    }
+    pub fn delete_forward(&mut self) {
+        if self.cursor.char_idx >= self.text_rope.len_chars() && self.selection().is_none() {
+            return;
+        }
+        self.begin_edit(EditKind::Deleting);
+        if !self.delete_selection() {
+            self.remove_text(self.cursor.char_idx..self.cursor.char_idx + 1);
+        }
+        self.typing_attrs = None;
+    }
+    //visual lines of the text wrapped to the rect inside the padding, only laid out again after an edit or resize
+    fn wrapped(&self, draw_rect: &RotateRect, rt: &RenderText) -> Rc<TextLayout> {
+        if let Some((size, layout)) = &*self.wrap_cache.borrow() {
+            if *size == draw_rect.size {
+                return layout.clone();
+            }
+        }
+        let layout = Rc::new(self.layout_text(&self.text_rope, &self.spans, &draw_rect.size, rt));
+        self.wrap_cache.replace(Some((draw_rect.size, layout.clone())));
+        layout
+    }
+    //when shrinking, the largest scale the text fits the box at without breaking a word is searched for
+    fn layout_text(&self, rope: &Rope, spans: &SpanList, size: &Point, rt: &RenderText) -> TextLayout {
+        let inner = *size - Point::new(2. * self.padding, 2. * self.padding);
+        let wrap = |scale| wrap_text(rope, spans, inner.x, scale, self.padding, self.align, rt);
+        let fits = |lines: &[VisualLine]| {
+            text_height(lines) <= inner.y && lines.iter().all(|l| !l.wrapped || rope.char(l.end - 1).is_whitespace())
+        };
+        let lines = wrap(self.text_scale);
+        if self.fit != TextFit::ShrinkText || self.text_scale <= TextBox::MIN_SCALE || fits(&lines) {
+            return TextLayout { scale: self.text_scale, lines };
+        }
+        let (mut lo, mut hi) = (TextBox::MIN_SCALE, self.text_scale);
+        let mut best = wrap(lo);
+        for _ in 0..8 {
+            let mid = (lo + hi) / 2.;
+            let lines = wrap(mid);
+            if fits(&lines) {
+                lo = mid;
+                best = lines;
+            }
+            else {
+                hi = mid;
+            }
+        }
+        TextLayout { scale: lo, lines: best }
+    }
+    //size that fits the text and any composition without wrapping, for labels sized to their text
+    pub fn natural_size(&self, rt: &RenderText) -> Point {
+        let (rope, spans, _) = self.composed_text();
+        let lines = wrap_text(&rope, &spans, f32::INFINITY, self.text_scale, 0., TextAlign::Left, rt);
+        let width = lines.iter().map(|l| l.layout.width).fold(0., f32::max);
+        Point::new(width + 2. * self.padding + TextBox::CARET_WIDTH, text_height(&lines) + 2. * self.padding)
+    }
+    //height of the shape that fits all of the text, when the shape grows with it
+    pub fn fit_height(&self, draw_rect: &RotateRect, rt: &RenderText) -> Option<f32> {
+        if self.fit != TextFit::GrowShape {
+            return None;
+        }
+        let layout = self.wrapped(draw_rect, rt);
+        Some(text_height(&layout.lines) + 2. * self.padding)
+    }
+    fn inner_height(&self, draw_rect: &RotateRect) -> f32 {
+        draw_rect.size.y - 2. * self.padding
+    }
+    //the top line the text can be scrolled to, where the last line is at the bottom of the box
+    fn max_top_line(&self, lines: &[VisualLine], draw_rect: &RotateRect) -> usize {
+        let inner_height = self.inner_height(draw_rect);
+        let (mut top, mut height) = (lines.len(), 0.);
+        while top > 0 && height + lines[top - 1].layout.height <= inner_height {
+            height += lines[top - 1].layout.height;
+            top -= 1;
+        }
+        std::cmp::min(top, lines.len().saturating_sub(1))
+    }
+    //the top line kept in range after the text got shorter
+    fn first_line(&self, lines: &[VisualLine]) -> usize {
+        std::cmp::min(self.top_line, lines.len().saturating_sub(1))
+    }
+    //scrolls just far enough for the cursor's line to be shown, if it moved since the last time
+    pub fn scroll_to_cursor(&mut self, draw_rect: &RotateRect, rt: &RenderText) {
+        if !self.follow_cursor {
+            return;
+        }
+        self.follow_cursor = false;
+        let layout = self.wrapped(draw_rect, rt);
+        let lines = &layout.lines;
+        let line = TextBox::line_of(lines, self.cursor.char_idx);
+        let inner_height = self.inner_height(draw_rect);
+        let mut top = std::cmp::min(std::cmp::min(self.top_line, self.max_top_line(lines, draw_rect)), line);
+        while top < line && text_height(&lines[top..=line]) > inner_height {
+            top += 1;
+        }
+        self.top_line = top;
+    }
+    //scrolls by whole visual lines, only boxes that overflow by scrolling can be scrolled
+    pub fn scroll(&mut self, delta: i32, draw_rect: &RotateRect, rt: &RenderText) {
+        if self.overflow != TextOverflow::Scroll {
+            return;
+        }
+        let layout = self.wrapped(draw_rect, rt);
+        let max_top = self.max_top_line(&layout.lines, draw_rect) as i32;
+        self.top_line = std::cmp::max(0, std::cmp::min(self.top_line as i32 + delta, max_top)) as usize;
+    }
+    //boxes that aren't scrolled by the user go back to showing the start of the text
+    pub fn end_edit(&mut self) {
+        self.composition = None;
+        if self.overflow != TextOverflow::Scroll {
+            self.top_line = 0;
         }
     }
+    //index of the visual line the cursor at `idx` is drawn on, a wrap point belongs to the line it starts
+    fn line_of(lines: &[VisualLine], idx: usize) -> usize {
+        lines.iter().rposition(|l| l.start <= idx).unwrap_or(0)
+    }
+    //the last caret position on a line, before the space a wrapped line ends with
+    fn line_end(&self, line: &VisualLine) -> usize {
+        if line.wrapped && self.text_rope.char(line.end - 1).is_whitespace() { line.end - 1 } else { line.end }
+    }
+    fn hit_line(&self, line: &VisualLine, x: f32) -> usize {
+        std::cmp::min(line.start + line.layout.hit_test(x), self.line_end(line))
+    }
+    //point in the box's unrotated pixel space, with the origin at its top left
+    fn model_pt(&self, pt: &Point, rect: &RotateRect, vp: &Point) -> Point {
+        let model = rect.transform(vp).pixel_to_model(pt);
+        Point::new(model.x * rect.size.x, model.y * rect.size.y)
+    }
     pub fn hover_text(&self, pt: &Point, rect: &RotateRect, rt: &RenderText, vp: &Point) -> Option<usize> {
-        let pt2 = rect.transform(vp).pixel_to_model(pt) * rect.size.x;
-        let n_line = (pt2.y / rt.line_height(self.text_scale)) as i32;
-        if pt2.x < 0. || pt2.x > rect.size.x || 
-            n_line < 0 || n_line >= self.text_rope.len_lines() as i32 {
+        let pt2 = self.model_pt(pt, rect, vp);
+        if pt2.x < 0. || pt2.x > rect.size.x || pt2.y < 0. {
             return None;
         }
-        let line_idx = n_line as usize;
-        let start_char = self.text_rope.line_to_char(line_idx);
-        let end_char = self.text_rope.line_to_char(line_idx + 1);
-        //println!("Hover Text! Line index: {:?} Start char pos: {:?}, End char pos {:?}", line_idx, start_char, end_char);
-        let mut line_x = 0.;
-        (start_char+1..end_char)
-            .take_while(|i| { 
-                line_x += rt.char_size_w_advance(self.text_rope.char(i-1), self.text_scale).x; line_x <= pt2.x}).last()
-    } 
-    pub fn set_cursor_pos(&mut self, cursor_idx: usize) {
+        let layout = self.wrapped(rect, rt);
+        self.visible_lines(&layout.lines, rect).into_iter()
+            .find(|(line, y)| pt2.y >= *y && pt2.y < y + line.layout.height)
+            .map(|(line, _)| self.hit_line(line, pt2.x))
+    }
+    //char index closest to a point anywhere on screen, for extending a selection by dragging
+    pub fn nearest_text_pos(&self, pt: &Point, rect: &RotateRect, rt: &RenderText, vp: &Point) -> usize {
+        let pt2 = self.model_pt(pt, rect, vp);
+        let layout = self.wrapped(rect, rt);
+        let lines = &layout.lines;
+        let mut y = self.visible_lines(lines, rect).first().map_or(self.padding, |(_, y)| *y);
+        if pt2.y < y {
+            return lines.get(self.first_line(lines)).map_or(0, |l| l.start);
+        }
+        for line in lines.iter().skip(self.first_line(lines)) {
+            y += line.layout.height;
+            if pt2.y < y {
+                return self.hit_line(line, pt2.x);
+            }
+        }
+        self.text_rope.len_chars()
+    }
+    //visual lines from the top line that fit inside the padding, with the y of their top
+    fn visible_lines<'a>(&self, lines: &'a [VisualLine], draw_rect: &RotateRect) -> Vec<(&'a VisualLine, f32)> {
+        let inner_height = self.inner_height(draw_rect);
+        let mut visible = Vec::new();
+        let mut y = 0.;
+        for line in lines.iter().skip(self.first_line(lines)) {
+            if y + line.layout.height > inner_height {
+                break;
+            }
+            visible.push((line, y));
+            y += line.layout.height;
+        }
+        let top = self.padding + match self.valign {
+            VerticalAlign::Top => 0.,
+            VerticalAlign::Middle => (inner_height - y) / 2.,
+            VerticalAlign::Bottom => inner_height - y,
+        };
+        visible.into_iter().map(|(line, y)| (line, y + top)).collect()
+    }
+    pub fn all_text(&self) -> Range<usize> {
+        0..self.text_rope.len_chars()
+    }
+    //whether every char in the range has the attribute, without a range it's checked for the next typed chars
+    pub fn attr_on(&self, attr: TextAttr, range: Option<Range<usize>>) -> bool {
+        match range {
+            Some(range) if !range.is_empty() => self.spans.all(range, attr),
+            _ => self.typing_attrs().get(attr)
+        }
+    }
+    pub fn color_at(&self, range: Option<Range<usize>>) -> glm::Vec4 {
+        let attrs = match range {
+            Some(range) if !range.is_empty() => self.spans.attrs_at(range.start),
+            _ => self.typing_attrs()
+        };
+        attrs.color.unwrap_or(self.text_color)
+    }
+    //edits the attributes of a range of chars, without a range the next typed chars are changed
+    pub fn update_attrs<F: Fn(&mut TextAttrs)>(&mut self, range: Option<Range<usize>>, f: F) {
+        match range {
+            Some(range) if !range.is_empty() => {
+                self.begin_edit(EditKind::Other);
+                self.spans.update(range, f);
+                self.text_changed();
+            }
+            _ => {
+                let mut attrs = self.typing_attrs();
+                f(&mut attrs);
+                self.typing_attrs = Some(attrs);
+            }
+        }
+    }
+    //with `select` the selection is extended to the new position
+    fn set_anchor(&mut self, select: bool) {
+        if !select {
+            self.cursor.anchor = None;
+        }
+        else if self.cursor.anchor.is_none() {
+            self.cursor.anchor = Some(self.cursor.char_idx);
+        }
+        self.typing_attrs = None;
+        self.last_edit = None;
+        self.follow_cursor = true;
+    }
+    pub fn set_cursor_pos(&mut self, cursor_idx: usize, select: bool) {
+        self.set_anchor(select);
         self.cursor.char_idx = std::cmp::max(0, std::cmp::min(self.text_rope.len_chars(), cursor_idx));
     }
-    pub fn move_cursor(&mut self, dir: TextCursorDirection) {
-        let cursor_line = self.text_rope.char_to_line(self.cursor.char_idx);
-        let line = self.text_rope.line(cursor_line);
-        let cursor_pos = self.cursor.char_idx - self.text_rope.line_to_char(cursor_line);
-        //println!("Cursor line: {:?} Cursor Pos: {:?}", line, cursor_pos);
-        match dir {
-            TextCursorDirection::Left =>  { 
-                if self.cursor.char_idx > 0 {
-                    if cursor_pos == 0 {
-                        self.cursor.char_idx -= 1;
-                    }
-                    self.cursor.char_idx -= 1;
-                }
+    fn word_left(&self, mut idx: usize) -> usize {
+        while idx > 0 && char_class(self.text_rope.char(idx - 1)) == CharClass::Space {
+            idx -= 1;
+        }
+        if idx > 0 {
+            let class = char_class(self.text_rope.char(idx - 1));
+            while idx > 0 && char_class(self.text_rope.char(idx - 1)) == class {
+                idx -= 1;
             }
-            TextCursorDirection::Right =>  { 
-                if self.cursor.char_idx < self.text_rope.len_chars() {
-                    if cursor_pos == line.len_chars() {
-                        self.cursor.char_idx += 1;
-                    }
-                    self.cursor.char_idx += 1;
-                }
+        }
+        idx
+    }
+    fn word_right(&self, mut idx: usize) -> usize {
+        let len = self.text_rope.len_chars();
+        if idx < len {
+            let class = char_class(self.text_rope.char(idx));
+            while idx < len && char_class(self.text_rope.char(idx)) == class {
+                idx += 1;
             }
-            TextCursorDirection::Up =>  { 
-                if cursor_line > 0 {
-                    let prev_line_char = self.text_rope.line_to_char(cursor_line - 1);
-                    let prev_line = self.text_rope.line(cursor_line - 1);
-                    self.cursor.char_idx = std::cmp::min(prev_line_char + prev_line.len_chars(), prev_line_char + cursor_pos);
+        }
+        while idx < len && char_class(self.text_rope.char(idx)) == CharClass::Space {
+            idx += 1;
+        }
+        idx
+    }
+    //up, down and the line ends move through the wrapped lines the box shows
+    pub fn move_cursor(&mut self, dir: TextCursorDirection, select: bool, draw_rect: &RotateRect, rt: &RenderText) {
+        //without shift left and right collapse the selection to its ends
+        if let (false, Some(range)) = (select, self.selection()) {
+            match dir {
+                TextCursorDirection::Left => {
+                    self.set_cursor_pos(range.start, false);
+                    return;
                 }
-            }
-            TextCursorDirection::Down =>  { 
-                if cursor_line < self.text_rope.len_lines() - 1 {
-                    let next_line_char = self.text_rope.line_to_char(cursor_line + 1);
-                    let next_line = self.text_rope.line(cursor_line + 1);
-                    self.cursor.char_idx = std::cmp::min(next_line_char + next_line.len_chars() - 1, next_line_char + cursor_pos);
+                TextCursorDirection::Right => {
+                    self.set_cursor_pos(range.end, false);
+                    return;
                 }
+                _ => {}
             }
         }
+        self.set_anchor(select);
+        let layout = self.wrapped(draw_rect, rt);
+        let lines = &layout.lines;
+        let line_idx = TextBox::line_of(lines, self.cursor.char_idx);
+        let line = &lines[line_idx];
+        let cursor_x = line.layout.cursor_x(self.cursor.char_idx - line.start);
+        self.cursor.char_idx = match dir {
+            TextCursorDirection::WordLeft => self.word_left(self.cursor.char_idx),
+            TextCursorDirection::WordRight => self.word_right(self.cursor.char_idx),
+            TextCursorDirection::LineStart => line.start,
+            TextCursorDirection::LineEnd => self.line_end(line),
+            TextCursorDirection::TextStart => 0,
+            TextCursorDirection::TextEnd => self.text_rope.len_chars(),
+            TextCursorDirection::Left => self.cursor.char_idx.saturating_sub(1),
+            TextCursorDirection::Right => std::cmp::min(self.cursor.char_idx + 1, self.text_rope.len_chars()),
+            TextCursorDirection::Up if line_idx > 0 => self.hit_line(&lines[line_idx - 1], cursor_x),
+            TextCursorDirection::Down if line_idx + 1 < lines.len() => self.hit_line(&lines[line_idx + 1], cursor_x),
+            TextCursorDirection::Up | TextCursorDirection::Down => self.cursor.char_idx,
+        };
+    }
+    //one highlight rect per visible line the selection touches
+    fn draw_selection(&self, lines: &[(&VisualLine, f32)], draw_rect: &RotateRect, draw_ctx: &DrawCtx) {
+        let range = match self.selection() {
+            Some(range) => range,
+            None => return
+        };
+        for (line, y) in lines {
+            let (start, end) = (std::cmp::max(range.start, line.start), std::cmp::min(range.end, line.end));
+            if start > end || (start == end && range.end <= line.end) {
+                continue;
+            }
+            let x1 = line.layout.cursor_x(start - line.start);
+            //a selected newline is shown as a sliver past the end of the line
+            let x2 = line.layout.cursor_x(end - line.start) + if range.end > line.end && !line.wrapped { 4. } else { 0. };
+            let size = draw_rect.size;
+            let model_rect = Rect::new(
+                Point::new(f32::min(x1, x2) / size.x, y / size.y),
+                Point::new(f32::max(x1, x2) / size.x, (y + line.layout.height) / size.y));
+            let mut highlight = draw_rect.clone();
+            highlight.resize(&model_rect, &draw_ctx.viewport);
+            highlight.builder().color(180, 200, 250).get().draw(draw_ctx);
+        }
+    }
+    //the chars of a line that fit before an ellipsis at the right edge, followed by the ellipsis
+    fn ellipsized(&self, line: &VisualLine, scale: f32, draw_rect: &RotateRect, rt: &RenderText) -> (String, Vec<AttrRun>) {
+        let attrs = self.spans.attrs_at(std::cmp::max(line.start, line.end.saturating_sub(1)));
+        let width = rt.layout_line("\u{2026}", &[(1, attrs)], scale).width;
+        let right = draw_rect.size.x - self.padding;
+        let mut n = self.line_end(line) - line.start;
+        while n > 0 && line.layout.cursor_x(n) + width > right {
+            n -= 1;
+        }
+        let text = format!("{}\u{2026}", self.text_rope.slice(line.start..line.start + n));
+        let mut runs = self.spans.slice(line.start..line.start + n);
+        runs.push((1, attrs));
+        runs.push((1, attrs));
+        (text, runs)
+    }
+    //a thumb along the right edge showing which part of the text is in view
+    fn draw_scrollbar(&self, lines: &[VisualLine], visible: &[(&VisualLine, f32)], draw_rect: &RotateRect, draw_ctx: &DrawCtx) {
+        if visible.len() >= lines.len() {
+            return;
+        }
+        let total = text_height(lines);
+        let above = text_height(&lines[..self.first_line(lines)]);
+        let shown: f32 = visible.iter().map(|(line, _)| line.layout.height).sum();
+        let size = draw_rect.size;
+        let model_rect = Rect::new(
+            Point::new(1. - TextBox::SCROLLBAR_WIDTH / size.x, above / total),
+            Point::new(1., (above + shown) / total));
+        let mut thumb = draw_rect.clone();
+        thumb.resize(&model_rect, &draw_ctx.viewport);
+        thumb.builder().color(150, 150, 150).get().draw(draw_ctx);
+    }
+    //the text with the composition spliced in at the cursor, and the cursor position within it
+    fn composed_text(&self) -> (Rope, SpanList, usize) {
+        let (mut rope, mut spans) = (self.text_rope.clone(), self.spans.clone());
+        let mut cursor = self.cursor.char_idx;
+        if let Some(comp) = &self.composition {
+            let attrs = TextAttrs { underline: true, ..self.typing_attrs() };
+            rope.insert(cursor, &comp.text);
+            spans.insert(cursor, comp.text.chars().count(), attrs);
+            cursor += comp.cursor;
+        }
+        (rope, spans, cursor)
+    }
+    //text of the visible lines with their runs and placements, the last line is ellipsized when lines are clipped
+    fn shown_text(&self, (rope, spans): (&Rope, &SpanList), layout: &TextLayout, visible: &[(&VisualLine, f32)], ellipsize: bool,
+        draw_rect: &RotateRect, rt: &RenderText) -> (String, Vec<AttrRun>, Vec<LinePlacement>)
+    {
+        let lines = &layout.lines;
+        let mut texts: Vec<String> = visible.iter().map(|(line, _)| rope.slice(line.start..line.end).to_string()).collect();
+        let mut runs: Vec<Vec<AttrRun>> = visible.iter().map(|(line, _)| line_runs(spans, line)).collect();
+        let clipped = visible.last().is_some_and(|(line, _)| !std::ptr::eq(*line, lines.last().unwrap()));
+        if let (TextOverflow::Ellipsis, true, true) = (self.overflow, ellipsize, clipped) {
+            let (line, _) = visible[visible.len() - 1];
+            let (text, line_runs) = self.ellipsized(line, layout.scale, draw_rect, rt);
+            texts.pop();
+            texts.push(text);
+            runs.pop();
+            runs.push(line_runs);
+        }
+        let runs: Vec<AttrRun> = runs.into_iter().flatten().collect();
+        let placements: Vec<LinePlacement> = visible.iter().map(|(line, y)| line.layout.placement(*y)).collect();
+        (texts.join("\n"), runs, placements)
+    }
+    //the glyphs drawn in the rect, in its space. effects aren't included
+    pub fn place_glyphs(&self, draw_rect: &RotateRect, rt: &RenderText, vp: &Point) -> PlacedText {
+        let layout = self.wrapped(draw_rect, rt);
+        let visible = self.visible_lines(&layout.lines, draw_rect);
+        if self.text_rope.len_chars() == 0 || visible.is_empty() {
+            return PlacedText::default();
+        }
+        let trans = self.trans(draw_rect, vp);
+        let (text, runs, placements) = self.shown_text((&self.text_rope, &self.spans), &layout, &visible, true, draw_rect, rt);
+        rt.place_glyphs(&self.get_params(&text, &trans).runs(&runs).placements(&placements).scale(layout.scale))
     }
     pub fn draw(&self, draw_rect: &RotateRect, select_time: Option<SystemTime>, rt: &RenderText, draw_ctx: &DrawCtx) {
-        let cursor_line = self.text_rope.char_to_line(self.cursor.char_idx);
-        let line_height = rt.line_height(self.text_scale);
-        if self.text_rope.len_chars() > 0 {
-            let mut max_lines = (draw_rect.size.y / line_height) as usize;
-            max_lines = std::cmp::min(max_lines, self.text_rope.len_lines());
-            let start_idx = if self.text_rope.len_lines() == 0 { 0 } 
-                else { self.text_rope.line_to_char(self.top_line) };
-            let end_idx = self.text_rope.line_to_char(self.top_line + max_lines);
-            let color = (0,0,0);
-            let trans = self.trans(draw_rect, rt, &color, &draw_ctx.viewport);
-            let text = self.text_rope.slice(start_idx..end_idx).as_str().unwrap();
-            rt.draw(&self.get_params(text, &color, &trans), draw_ctx);
-        }
-        if let Some(select_time) = select_time {
+        //the composition is wrapped along with the text around it while it's shown
+        let composed = self.composition.as_ref().map(|_| self.composed_text());
+        let (rope, spans, cursor_idx, layout) = match &composed {
+            Some((rope, spans, cursor)) => (rope, spans, *cursor,
+                Rc::new(self.layout_text(rope, spans, &draw_rect.size, rt))),
+            None => (&self.text_rope, &self.spans, self.cursor.char_idx, self.wrapped(draw_rect, rt))
+        };
+        let lines = &layout.lines;
+        let visible = self.visible_lines(lines, draw_rect);
+        if select_time.is_some() && composed.is_none() {
+            self.draw_selection(&visible, draw_rect, draw_ctx);
+        }
+        if rope.len_chars() > 0 && !visible.is_empty() {
+            let trans = self.trans(draw_rect, &draw_ctx.viewport);
+            let (text, runs, placements) = self.shown_text((rope, spans), &layout, &visible, select_time.is_none(), draw_rect, rt);
+            rt.draw(&self.get_params(&text, &trans).runs(&runs).placements(&placements).scale(layout.scale), draw_ctx);
+        }
+        if self.overflow == TextOverflow::Scroll {
+            self.draw_scrollbar(lines, &visible, draw_rect, draw_ctx);
+        }
+        let cursor_line = &lines[TextBox::line_of(lines, cursor_idx)];
+        let cursor_layout = visible.iter().find(|(line, _)| std::ptr::eq(*line, cursor_line));
+        if let (Some(select_time), Some((line, line_y))) = (select_time, cursor_layout) {
             let millis = select_time.elapsed().unwrap().as_millis() % 1000;
-            if millis < 500 {
-                let before_str = self.text_rope.slice(self.text_rope.line_to_char(cursor_line)..self.cursor.char_idx).as_str().unwrap();
+            if millis < 500 || composed.is_some() {
+                let cursor_x = line.layout.cursor_x(cursor_idx - line.start);
                 let mut cursor_pt1 = Point::new(
-                    rt.measure(before_str, self.text_scale).x / draw_rect.size.x, 
-                    (cursor_line - self.top_line) as f32 * line_height / draw_rect.size.y);
-                let mut cursor_pt2 = Point::new(cursor_pt1.x, cursor_pt1.y + line_height / draw_rect.size.y);
+                    cursor_x / draw_rect.size.x, 
+                    line_y / draw_rect.size.y);
+                let mut cursor_pt2 = Point::new(cursor_pt1.x, cursor_pt1.y + line.layout.height / draw_rect.size.y);
                 cursor_pt1 = draw_rect.transform(&draw_ctx.viewport).model_to_pixel(&cursor_pt1.to_vec4());
                 cursor_pt2 = draw_rect.transform(&draw_ctx.viewport).model_to_pixel(&cursor_pt2.to_vec4());
                 let cursor_line = LineBuilder::new().points2(&cursor_pt1, &cursor_pt2).get();
@@ -175,70 +991,107 @@ impl TextBox {
             }
         }
     }
-    pub fn needs_format(&self, draw_rect: &RotateRect, start_line: usize, rt: &RenderText) -> bool {
-        let start_line_width = rt.measure(self.text_rope.line(start_line).as_str().unwrap(), self.text_scale).x;
-        if start_line_width > draw_rect.size.x {
-            return true;
-        }
-        if start_line < self.text_rope.len_lines() - 1 {
-            let next_line = self.text_rope.line(start_line + 1);
-            if next_line.len_chars() > 0 {
-                let next_line_char = next_line.char(0);
-                let next_line_char_width = rt.char_size(next_line_char, self.text_scale).x;
-                return start_line_width + next_line_char_width <= draw_rect.size.x
+}
+
+//attributes of a visual line's chars and of the break after it, which sets the height of an empty line
+fn line_runs(spans: &SpanList, line: &VisualLine) -> Vec<AttrRun> {
+    let mut runs = spans.slice(line.start..line.end);
+    let break_idx = if line.wrapped { line.end - 1 } else { line.end };
+    runs.push((1, spans.attrs_at(break_idx)));
+    runs
+}
+
+fn text_height(lines: &[VisualLine]) -> f32 {
+    lines.iter().map(|l| l.layout.height).sum()
+}
+
+//splits each paragraph into lines no wider than `width`, breaking after the spaces between words.
+//spaces at a break hang past the edge and a word too long for a line is broken between chars.
+//lines are then aligned in the width starting at `x`, the last line of a justified paragraph stays left aligned
+fn wrap_text(rope: &Rope, spans: &SpanList, width: f32, scale: f32, x: f32, align: TextAlign, rt: &RenderText) -> Vec<VisualLine> {
+    let mut lines = Vec::new();
+    for para in 0..rope.len_lines() {
+        let para_start = rope.line_to_char(para);
+        let chars: Vec<char> = rope.line(para).chars().filter(|c| *c != '\n').collect();
+        let text: String = chars.iter().collect();
+        let para_line = VisualLine::new(para_start, para_start + chars.len(), false);
+        let xs: Vec<f32> = {
+            let layout = rt.layout_line(&text, &line_runs(spans, &para_line), scale);
+            (0..=chars.len()).map(|i| layout.cursor_x(i)).collect()
+        };
+        let mut start = 0;
+        let end = loop {
+            let mut end = start;
+            while end < chars.len() && (end == start || xs[end + 1] - xs[start] <= width) {
+                end += 1;
             }
-        }
-        false
-    }
-    pub fn format_text(&mut self, draw_rect: &RotateRect, start_line: usize, rt: &RenderText) {
-        if !self.needs_format(draw_rect, start_line, rt) {
-            return;
-        }
-        let start_char = self.text_rope.line_to_char(start_line);
-        let mut line_x = 0.;
-        let mut line_breaks = Vec::new();
-        for (i, c) in self.text_rope.slice(start_char..).chars().enumerate() {
-            let add_break = line_x + rt.char_size(c, self.text_scale).x > draw_rect.size.x;
-            let was_break = c == '\n';
-            if add_break {
-                line_x = 0.;
-            }
-            if add_break != was_break {
-                line_breaks.push((i + start_char, add_break));
-            }
-            line_x += rt.char_size_w_advance(c, self.text_scale).x;
-        }
-        let mut offset: i32 = 0;
-        for (idx, is_add) in line_breaks {
-            let uidx = (idx as i32 + offset) as usize;
-            if is_add {
-                self.text_rope.insert_char(uidx, '\n');
-                if uidx < self.cursor.char_idx {
-                    self.cursor.char_idx += 1;
-                }
-                offset += 1;
+            while end < chars.len() && chars[end].is_whitespace() {
+                end += 1;
             }
-            else {
-                self.text_rope.remove(uidx..uidx+1);
-                if uidx < self.cursor.char_idx {
-                    self.cursor.char_idx -= 1;
-                }
-                offset -= 1;
+            if end == chars.len() {
+                break start;
             }
+            let brk = (start + 1..=end).rev().find(|i| chars[i - 1].is_whitespace()).unwrap_or(end);
+            lines.push(VisualLine::new(para_start + start, para_start + brk, true));
+            start = brk;
+        };
+        lines.push(VisualLine::new(para_start + end, para_line.end, false));
+    }
+    for line in lines.iter_mut() {
+        let text = rope.slice(line.start..line.end).to_string();
+        line.layout = rt.layout_line(&text, &line_runs(spans, line), scale);
+        let free = f32::max(width - line.layout.trimmed_width(), 0.);
+        let gaps = line.layout.word_gaps();
+        match align {
+            TextAlign::Left => line.layout.align(x, 0.),
+            TextAlign::Center => line.layout.align(x + free / 2., 0.),
+            TextAlign::Right => line.layout.align(x + free, 0.),
+            TextAlign::Justify if line.wrapped && gaps > 0 => line.layout.align(x, free / gaps as f32),
+            TextAlign::Justify => line.layout.align(x, 0.),
         }
     }
+    lines
 }
 
-pub fn get_char_from_keycode(keycode: Keycode) -> Option<char> {
-    let name = keycode.name();
-    if name.len() == 1 {
-        name.chars().nth(0)
+fn unescape(s: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('\\') => out.push('\\'),
+            other => return Err(format!("Invalid escape {:?}", other))
+        }
     }
-    else if keycode == Keycode::Space {
-        Some(' ')
+    Ok(out)
+}
+
+//text typed or being composed in an SDL text input event
+pub fn get_text_input(ev: &Event) -> Option<TextInput> {
+    match ev {
+        Event::TextInput { text, .. } => Some(TextInput::Commit(text.clone())),
+        Event::TextEditing { text, start, .. } => Some(TextInput::Compose(Composition {
+            text: text.clone(),
+            cursor: std::cmp::max(*start, 0) as usize
+        })),
+        _ => None
     }
-    else {
-        None
+}
+
+//ctrl moves by words with the arrows and to the ends of the text with home and end
+pub fn get_dir_from_chord(chord: &KeyChord) -> Option<TextCursorDirection> {
+    match (chord.keycode, chord.ctrl) {
+        (Keycode::Left, true) => Some(TextCursorDirection::WordLeft),
+        (Keycode::Right, true) => Some(TextCursorDirection::WordRight),
+        (Keycode::Home, false) => Some(TextCursorDirection::LineStart),
+        (Keycode::End, false) => Some(TextCursorDirection::LineEnd),
+        (Keycode::Home, true) => Some(TextCursorDirection::TextStart),
+        (Keycode::End, true) => Some(TextCursorDirection::TextEnd),
+        _ => get_dir_from_keycode(chord.keycode)
     }
 }
 
@@ -0,0 +1,193 @@
+use std::collections::BTreeSet;
+
+//just enough of a TrueType or OpenType font to embed it in a PDF. glyph IDs are the ones the shaper returns
+pub struct TrueType<'a> {
+    tables: Vec<([u8; 4], &'a [u8])>,
+    pub units_per_em: u16,
+    pub num_glyphs: u16,
+    pub bbox: [i16; 4],         // x min, y min, x max, y max
+    pub ascent: i16,
+    pub descent: i16,
+    pub italic_angle: f32,      // degrees counterclockwise from vertical, from the post table
+    long_loca: bool,
+    num_hmetrics: u16,
+}
+
+//tables a PDF viewer needs to draw glyphs from an embedded TrueType font, sorted by tag
+const SUBSET_TABLES: [&[u8; 4]; 9] = [b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp", b"prep"];
+
+//flags of a component of a composite glyph
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x1;
+const WE_HAVE_A_SCALE: u16 = 0x8;
+const MORE_COMPONENTS: u16 = 0x20;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x40;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x80;
+
+fn read_u16(d: &[u8], at: usize) -> Result<u16, String> {
+    d.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]])).ok_or("Truncated font".to_string())
+}
+
+fn read_i16(d: &[u8], at: usize) -> Result<i16, String> {
+    read_u16(d, at).map(|v| v as i16)
+}
+
+fn read_u32(d: &[u8], at: usize) -> Result<u32, String> {
+    d.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]])).ok_or("Truncated font".to_string())
+}
+
+//sum of the data as big endian words, the last one padded with zeros
+pub fn checksum(d: &[u8]) -> u32 {
+    d.chunks(4).fold(0u32, |sum, c| {
+        let mut word = [0; 4];
+        word[..c.len()].copy_from_slice(c);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+impl<'a> TrueType<'a> {
+    //collections are read from their first font, the one the shaper uses
+    pub fn parse(data: &'a [u8]) -> Result<Self, String> {
+        let start = if data.starts_with(b"ttcf") { read_u32(data, 12)? as usize } else { 0 };
+        let ntables = read_u16(data, start + 4)? as usize;
+        let mut tables = Vec::with_capacity(ntables);
+        for i in 0..ntables {
+            let rec = start + 12 + i * 16;
+            let tag = data.get(rec..rec + 4).ok_or("Truncated font".to_string())?;
+            let (offset, len) = (read_u32(data, rec + 8)? as usize, read_u32(data, rec + 12)? as usize);
+            let table = data.get(offset..offset + len).ok_or(format!("Font table {} is out of bounds", String::from_utf8_lossy(tag)))?;
+            tables.push(([tag[0], tag[1], tag[2], tag[3]], table));
+        }
+        let mut font = TrueType {
+            tables, units_per_em: 0, num_glyphs: 0, bbox: [0; 4], ascent: 0, descent: 0, italic_angle: 0., long_loca: false, num_hmetrics: 0
+        };
+        let head = font.required(b"head")?;
+        font.units_per_em = read_u16(head, 18)?;
+        font.bbox = [read_i16(head, 36)?, read_i16(head, 38)?, read_i16(head, 40)?, read_i16(head, 42)?];
+        font.long_loca = read_i16(head, 50)? == 1;
+        let hhea = font.required(b"hhea")?;
+        font.ascent = read_i16(hhea, 4)?;
+        font.descent = read_i16(hhea, 6)?;
+        font.num_hmetrics = read_u16(hhea, 34)?;
+        font.num_glyphs = read_u16(font.required(b"maxp")?, 4)?;
+        if let Some(post) = font.table(b"post") {
+            font.italic_angle = read_u32(post, 4)? as i32 as f32 / 0x10000 as f32;
+        }
+        if font.units_per_em == 0 || font.num_hmetrics == 0 {
+            return Err("Invalid font metrics".to_string());
+        }
+        Ok(font)
+    }
+    pub fn table(&self, tag: &[u8; 4]) -> Option<&'a [u8]> {
+        self.tables.iter().find(|(t, _)| t == tag).map(|(_, table)| *table)
+    }
+    fn required(&self, tag: &[u8; 4]) -> Result<&'a [u8], String> {
+        self.table(tag).ok_or(format!("Font has no {} table", String::from_utf8_lossy(tag)))
+    }
+    //fonts with CFF outlines can't be subset, they're embedded whole
+    pub fn has_glyf(&self) -> bool {
+        self.table(b"glyf").is_some() && self.table(b"loca").is_some()
+    }
+    //advance width in font units, glyphs past the last metric share its width
+    pub fn advance(&self, gid: u16) -> u16 {
+        let idx = std::cmp::min(gid, self.num_hmetrics - 1) as usize;
+        self.table(b"hmtx").and_then(|hmtx| read_u16(hmtx, idx * 4).ok()).unwrap_or(0)
+    }
+    pub fn glyph_data(&self, gid: u16) -> Result<&'a [u8], String> {
+        let (loca, glyf) = (self.required(b"loca")?, self.required(b"glyf")?);
+        let i = gid as usize;
+        let (start, end) = if self.long_loca {
+            (read_u32(loca, i * 4)? as usize, read_u32(loca, i * 4 + 4)? as usize)
+        } else {
+            (read_u16(loca, i * 2)? as usize * 2, read_u16(loca, i * 2 + 2)? as usize * 2)
+        };
+        glyf.get(start..end).ok_or(format!("Glyph {} is out of bounds", gid))
+    }
+    //glyphs a composite glyph is built from
+    fn components(&self, gid: u16) -> Result<Vec<u16>, String> {
+        let data = self.glyph_data(gid)?;
+        if data.is_empty() || read_i16(data, 0)? >= 0 {
+            return Ok(Vec::new());
+        }
+        let (mut components, mut at) = (Vec::new(), 10);
+        loop {
+            let flags = read_u16(data, at)?;
+            components.push(read_u16(data, at + 2)?);
+            at += 4 + if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+            at += if flags & WE_HAVE_A_SCALE != 0 { 2 }
+                else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 { 4 }
+                else if flags & WE_HAVE_A_TWO_BY_TWO != 0 { 8 }
+                else { 0 };
+            if flags & MORE_COMPONENTS == 0 {
+                return Ok(components);
+            }
+        }
+    }
+    //the font with every glyph but `gids`, the glyphs they're built from and .notdef emptied out.
+    //glyph IDs stay the same so text can refer to glyphs by the IDs the shaper gave them
+    pub fn subset(&self, gids: &BTreeSet<u16>) -> Result<Vec<u8>, String> {
+        if !self.has_glyf() {
+            return Err("Only fonts with TrueType outlines can be subset".to_string());
+        }
+        let mut keep: BTreeSet<u16> = gids.iter().cloned().filter(|g| *g < self.num_glyphs).collect();
+        keep.insert(0);
+        let mut todo: Vec<u16> = keep.iter().cloned().collect();
+        while let Some(gid) = todo.pop() {
+            for c in self.components(gid)? {
+                if c < self.num_glyphs && keep.insert(c) {
+                    todo.push(c);
+                }
+            }
+        }
+        let (mut glyf, mut loca) = (Vec::new(), Vec::new());
+        for gid in 0..self.num_glyphs {
+            loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+            if keep.contains(&gid) {
+                glyf.extend_from_slice(self.glyph_data(gid)?);
+                while glyf.len() % 4 != 0 {
+                    glyf.push(0);
+                }
+            }
+        }
+        loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+        //loca is always written with long offsets, and the checksum adjustment is filled in once the font is done
+        let mut head = self.required(b"head")?.to_vec();
+        head[8..12].copy_from_slice(&[0; 4]);
+        head[50..52].copy_from_slice(&1u16.to_be_bytes());
+
+        let tables: Vec<(&[u8; 4], Vec<u8>)> = SUBSET_TABLES.iter().filter_map(|tag| match *tag {
+            b"glyf" => Some((*tag, glyf.clone())),
+            b"loca" => Some((*tag, loca.clone())),
+            b"head" => Some((*tag, head.clone())),
+            _ => self.table(tag).map(|t| (*tag, t.to_vec()))
+        }).collect();
+        let n = tables.len() as u16;
+        let log2 = 15 - n.leading_zeros() as u16;
+        let search_range = 16 * (1 << log2);
+        let mut out = Vec::new();
+        out.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        for v in [n, search_range, log2, n * 16 - search_range].iter() {
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        let mut offset = 12 + 16 * tables.len();
+        let mut head_offset = 0;
+        for (tag, data) in tables.iter() {
+            if *tag == b"head" {
+                head_offset = offset;
+            }
+            out.extend_from_slice(*tag);
+            out.extend_from_slice(&checksum(data).to_be_bytes());
+            out.extend_from_slice(&(offset as u32).to_be_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            offset += data.len() + (4 - data.len() % 4) % 4;
+        }
+        for (_, data) in tables.iter() {
+            out.extend_from_slice(data);
+            while out.len() % 4 != 0 {
+                out.push(0);
+            }
+        }
+        let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&out));
+        out[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+        Ok(out)
+    }
+}
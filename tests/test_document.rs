@@ -0,0 +1,90 @@
+extern crate sem_graph;
+
+use sem_graph::document::*;
+use sem_graph::layers::Layer;
+use sem_graph::primitives::*;
+use sem_graph::textedit::TextBox;
+
+fn test_shapes() -> (Shape, Shape) {
+    let mut rect = ShapeBuilder::new().rect(120, 60).offset(40, 30).color(10, 20, 30).stroke(200, 100, 0).line_width(2.).get();
+    if let Shape::Polygon(ref mut poly) = rect {
+        poly.prim = PrimType::RoundRect(15);
+        poly.rect.set_radians(Radians(0.5));
+    }
+    let mut line = LineBuilder::new().points(0., 0., 100., 50.).color(0, 0, 255).line_width(3.).get();
+    if let Shape::Line(ref mut line) = line {
+        line.end_marker = LineMarker::Arrow;
+        line.route = LineRoute::Orthogonal;
+        line.waypoints = vec![Point::new(50., 0.), Point::new(50., 50.)];
+        line.label.t = 0.25;
+    }
+    (rect, line)
+}
+
+#[test]
+fn test_document_round_trip() {
+    let (rect, line) = test_shapes();
+    let mut hidden = Layer::new("Notes and sketches");
+    hidden.visible = false;
+    hidden.locked = true;
+    hidden.opacity = 0.4;
+    let tb = TextBox::parse("text = two\\nlines\n").unwrap();
+
+    let mut out = String::new();
+    write_layer(&mut out, &Layer::new("Layer 1"));
+    write_shape(&mut out, &rect, Some("default"), Some(&tb), None);
+    write_layer(&mut out, &hidden);
+    write_shape(&mut out, &line, None, None, None);
+
+    let doc = Document::parse(&out).unwrap();
+    assert_eq!(doc.pages.len(), 1);
+    let layers = &doc.pages[0].layers;
+    assert_eq!(layers.len(), 2);
+    assert_eq!(layers[0].layer, Layer::new("Layer 1"));
+    assert_eq!(layers[1].layer, hidden);
+    let shape = &layers[0].shapes[0];
+    assert!(shape.shape == rect);
+    assert_eq!(shape.style.as_deref(), Some("default"));
+    assert_eq!(shape.text.as_ref().map(|tb| tb.text()), Some("two\nlines".to_string()));
+    let shape = &layers[1].shapes[0];
+    assert!(shape.shape == line);
+    assert!(shape.style.is_none() && shape.text.is_none());
+}
+
+#[test]
+fn test_document_pages() {
+    let (rect, line) = test_shapes();
+    let mut out = String::new();
+    write_page(&mut out, "Overview", &Point::new(0., 0.));
+    write_layer(&mut out, &Layer::new("Layer 1"));
+    write_shape(&mut out, &rect, None, None, Some(1));
+    write_page(&mut out, "Detail view", &Point::new(-40., 120.5));
+    write_layer(&mut out, &Layer::new("Layer 1"));
+    write_shape(&mut out, &line, None, None, Some(0));
+
+    let doc = Document::parse(&out).unwrap();
+    assert_eq!(doc.pages.len(), 2);
+    assert_eq!(doc.pages[0].name, "Overview");
+    assert_eq!(doc.pages[1].name, "Detail view");
+    assert_eq!(doc.pages[1].camera, Point::new(-40., 120.5));
+    assert_eq!(doc.pages[0].layers[0].shapes[0].link, Some(1));
+    assert_eq!(doc.pages[1].layers[0].shapes[0].link, Some(0));
+    assert!(doc.pages[1].layers[0].shapes[0].shape == line);
+}
+
+#[test]
+fn test_document_errors() {
+    assert!(Document::parse("").is_err());
+    assert!(Document::parse("[shape]\nprim = rect\n").is_err());
+    assert!(Document::parse("[layer]\nopacity = 2\n").is_err());
+    assert!(Document::parse("[layer]\n[text]\ntext = orphan\n").is_err());
+    assert!(Document::parse("[layer]\n[shape]\nprim = blob\n").is_err());
+    assert!(Document::parse("[layer]\n[line]\np1 = 1\n").is_err());
+    assert!(Document::parse("[page]\nname = Empty\n").is_err());
+    assert!(Document::parse("[page]\ncamera = 1\n[layer]\n").is_err());
+    assert!(Document::parse("[layer]\n[shape]\nlink = 1\n").is_err());
+    let doc = Document::parse("; comment\n[layer]\nname = Background\n\n[shape]\nprim = circle\n").unwrap();
+    assert_eq!(doc.pages[0].name, "Page 1");
+    assert_eq!(doc.pages[0].layers[0].layer.name, "Background");
+    assert_eq!(doc.pages[0].layers[0].shapes.len(), 1);
+}
@@ -1,19 +1,18 @@
 extern crate sdl2;
 extern crate gl;
 extern crate rand;
-extern crate nalgebra_glm;
 
 extern crate sem_graph;
 
 use sem_graph::interface::*;
 use sem_graph::render_text::*;
+use sem_graph::fonts::FontConfig;
 use sem_graph::primitives::*;
 
 use sdl2::event::Event;
 use sdl2::keyboard::{Keycode};
 use std::time::{SystemTime, Duration};
 
-use nalgebra_glm as glm;
 use rand::{Rng};
 
 #[allow(dead_code)]
@@ -124,7 +123,9 @@ fn add_random_shapes(draw_list: &mut DrawList, vp: &Point, n: u8) {
     }
 }
 
+//opens a window and runs until it's closed, run it with --ignored
 #[test]
+#[ignore]
 fn test_draw() {
     let sdl = sdl2::init().unwrap();
     let video_subsystem = sdl.video().unwrap();
@@ -150,20 +151,21 @@ fn test_draw() {
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
     }
 
-    let draw_list = DrawList::new();
     //let mut draw_list = DrawList::new();
     //add_rotated_shapes(&mut draw_list);
     //add_test_lines(&mut draw_list);
     //add_random_shapes(&mut draw_list, &VIEWPORT, 10);
 
-    let mut app_state = AppState::new(&VIEWPORT);
+    let mut app_state = AppState::new(&VIEWPORT, &video_subsystem);
 
     let mut event_pump = sdl.event_pump().unwrap();
     let mut timer = SystemTime::now();
-    let render_text = RenderText::new().unwrap();
+    let render_text = RenderText::new(&FontConfig::default_config()).unwrap();
 
     let test_str = "This is a test sentence!\n let's test this sentence!\n haha! woo hoo!";
-    let text_params = TextParams::new(test_str).offset(&Point::new(300.,300.)).color(255, 0, 255).rot(Degrees(0.).into());
+    let text_rect = RotateRect::new(Point::new(300., 300.), Point::new(400., 200.), Degrees(0.).into());
+    let text_trans = TextUniforms::new(&text_rect, &VIEWPORT);
+    let text_params = TextParams::new(test_str, &text_trans).color(255, 0, 255);
     'main: loop {
         for event in event_pump.poll_iter() {
             let kmod = sdl.keyboard().mod_state();
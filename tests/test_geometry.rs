@@ -0,0 +1,188 @@
+extern crate proptest;
+
+extern crate sem_graph;
+
+use sem_graph::geometry::*;
+use sem_graph::primitives::*;
+
+use proptest::prelude::*;
+
+const EPS: f32 = 1e-3;
+
+fn point() -> impl Strategy<Value = Point> {
+    (-500f32..500., -500f32..500.).prop_map(|(x, y)| Point::new(x, y))
+}
+
+fn lerp(a: &Point, b: &Point, t: f32) -> Point {
+    Point::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+}
+
+//brute force distance to a polyline by sampling it densely
+fn sampled_dist(p: &Point, pts: &[Point]) -> f32 {
+    pts.windows(2).flat_map(|s| (0..=1000).map(move |i| lerp(&s[0], &s[1], i as f32 / 1000.)))
+        .map(|q| p.dist(&q)).fold(f32::INFINITY, f32::min)
+}
+
+proptest! {
+    #[test]
+    fn segment_dist_matches_sampling(p in point(), a in point(), b in point()) {
+        let d = dist_to_segment(&p, &a, &b);
+        let sampled = sampled_dist(&p, &[a, b]);
+        //sampling can only overestimate, by at most half a sample step
+        prop_assert!(d <= sampled + EPS);
+        prop_assert!(sampled - d <= a.dist(&b) / 2000. + 0.01);
+    }
+
+    #[test]
+    fn segment_dist_is_symmetric_and_bounded(p in point(), a in point(), b in point()) {
+        let d = dist_to_segment(&p, &a, &b);
+        prop_assert!((d - dist_to_segment(&p, &b, &a)).abs() <= EPS);
+        prop_assert!(d <= p.dist(&a) + EPS && d <= p.dist(&b) + EPS);
+        prop_assert!(d >= 0.);
+    }
+
+    #[test]
+    fn segment_dist_ignores_translation(p in point(), a in point(), b in point(), off in point()) {
+        let d = dist_to_segment(&p, &a, &b);
+        let moved = dist_to_segment(&(p + off), &(a + off), &(b + off));
+        prop_assert!((d - moved).abs() <= 0.01);
+    }
+
+    #[test]
+    fn points_past_the_ends_measure_to_the_end(a in point(), b in point(), t in 1.1f32..3.) {
+        prop_assume!(a.dist(&b) > 1.);
+        let p = lerp(&a, &b, t);
+        prop_assert!((dist_to_segment(&p, &a, &b) - p.dist(&b)).abs() <= 0.01);
+    }
+
+    #[test]
+    fn polyline_dist_is_closest_segment(p in point(), pts in prop::collection::vec(point(), 2..8)) {
+        let d = dist_to_polyline(&p, &pts);
+        let closest = pts.windows(2).map(|s| dist_to_segment(&p, &s[0], &s[1])).fold(f32::INFINITY, f32::min);
+        prop_assert_eq!(d, closest);
+    }
+
+    #[test]
+    fn bezier_dist_matches_sampling(p in point(), c in prop::array::uniform4(point())) {
+        let d = dist_to_bezier(&p, &c);
+        let sampled = (0..=4000).map(|i| p.dist(&bezier_point(&c, i as f32 / 4000.))).fold(f32::INFINITY, f32::min);
+        prop_assert!(d <= sampled + 0.01);
+        prop_assert!(sampled - d <= 0.5);
+    }
+
+    #[test]
+    fn straight_bezier_is_a_segment(p in point(), a in point(), b in point()) {
+        let c = [a, lerp(&a, &b, 1. / 3.), lerp(&a, &b, 2. / 3.), b];
+        prop_assert!((dist_to_bezier(&p, &c) - dist_to_segment(&p, &a, &b)).abs() <= 0.05);
+    }
+
+    #[test]
+    fn crossing_segments_meet_where_they_cross(m in point(), a in point(), c in point(), t in 0.1f32..0.9, u in 0.1f32..0.9) {
+        //segments through a shared point at `t` and `u` of the way along them
+        let b = lerp(&a, &m, 1. / t);
+        let d = lerp(&c, &m, 1. / u);
+        let (ab, cd) = (b - a, d - c);
+        prop_assume!((ab.x * cd.y - ab.y * cd.x).abs() > 1. * ab.mag() * cd.mag() / 100.);
+        let hit = segment_intersection(&a, &b, &c, &d);
+        prop_assert!(hit.is_some());
+        let hit = hit.unwrap();
+        prop_assert!(dist_to_segment(&hit, &a, &b) <= 0.05 && dist_to_segment(&hit, &c, &d) <= 0.05);
+    }
+
+    #[test]
+    fn intersections_lie_on_both_segments(a in point(), b in point(), c in point(), d in point()) {
+        if let Some(hit) = segment_intersection(&a, &b, &c, &d) {
+            prop_assert!(dist_to_segment(&hit, &a, &b) <= 0.05);
+            prop_assert!(dist_to_segment(&hit, &c, &d) <= 0.05);
+        }
+        prop_assert_eq!(segments_intersect(&a, &b, &c, &d), segments_intersect(&c, &d, &a, &b));
+    }
+
+    #[test]
+    fn rect_containment_matches_polygon(c1 in point(), c2 in point(), p in point()) {
+        let r = Rect::new(c1, c2);
+        //points right on an edge can go either way
+        prop_assume!(dist_to_polygon_edge(&p, &r.verts()) > EPS);
+        prop_assert_eq!(in_polygon(&r.verts(), &p), r.contains(&p));
+        let mut reversed = r.verts();
+        reversed.reverse();
+        prop_assert_eq!(in_polygon(&reversed, &p), r.contains(&p));
+    }
+
+    #[test]
+    fn lines_hit_near_the_segment_only(a in point(), b in point(), t in 0f32..1., off in -20f32..20., width in 1f32..10.) {
+        prop_assume!(a.dist(&b) > 1.);
+        let line = LineBuilder::new().points2(&a, &b).line_width(width).get();
+        let vp = Point::new(1000., 1000.);
+        //offset perpendicular to the line from a point along it
+        let n = Point::new(a.y - b.y, b.x - a.x) / a.dist(&b);
+        let p = lerp(&a, &b, t) + Point::new(n.x * off, n.y * off);
+        let reach = width / 2. + HIT_TOLERANCE;
+        if off.abs() < reach - EPS { prop_assert!(line.in_bounds(&p, &vp)); }
+        if off.abs() > reach + EPS { prop_assert!(!line.in_bounds(&p, &vp)); }
+    }
+
+    #[test]
+    fn lines_miss_past_their_ends(a in point(), b in point(), t in 1.01f32..2., width in 1f32..10.) {
+        let reach = width / 2. + HIT_TOLERANCE;
+        prop_assume!(a.dist(&b) * (t - 1.) > reach + EPS);
+        let line = LineBuilder::new().points2(&a, &b).line_width(width).get();
+        prop_assert!(!line.in_bounds(&lerp(&a, &b, t), &Point::new(1000., 1000.)));
+    }
+}
+
+//the old hit test widened every segment vertically, so points above and below a horizontal line's ends hit it
+#[test]
+fn test_axis_aligned_line_hits() {
+    let vp = Point::new(1000., 1000.);
+    let horizontal = LineBuilder::new().points(100., 100., 300., 100.).line_width(2.).get();
+    assert!(horizontal.in_bounds(&Point::new(200., 103.), &vp));
+    assert!(!horizontal.in_bounds(&Point::new(200., 110.), &vp));
+    assert!(!horizontal.in_bounds(&Point::new(310., 100.), &vp));
+    let vertical = LineBuilder::new().points(100., 100., 100., 300.).line_width(2.).get();
+    assert!(vertical.in_bounds(&Point::new(103., 200.), &vp));
+    assert!(!vertical.in_bounds(&Point::new(100., 310.), &vp));
+}
+
+//ui rects like panel cells and tabs sit edge to edge, a click only lands in the one it's inside
+#[test]
+fn test_ui_rect_hits() {
+    let vp = Point::new(1000., 1000.);
+    let top = Rect::new(Point::new(0., 0.), Point::new(100., 20.));
+    let bottom = Rect::new(Point::new(0., 20.), Point::new(100., 40.));
+    assert!(!top.in_bounds(&Point::new(50., 22.), &vp));
+    assert!(bottom.in_bounds(&Point::new(50., 22.), &vp));
+    assert!(top.in_bounds(&Point::new(50., 18.), &vp));
+    assert!(!bottom.in_bounds(&Point::new(50., 18.), &vp));
+    assert!(!top.in_bounds(&Point::new(50., -2.), &vp));
+    assert!(!top.in_bounds(&Point::new(102., 10.), &vp));
+    //a shape on the canvas the same size is still hit just outside it
+    let shape = RotateRect::new(Point::new(0., 0.), Point::new(100., 20.), Radians(0.));
+    assert!(shape.in_bounds(&Point::new(50., -2.), &vp));
+    assert!(shape.in_bounds(&Point::new(102., 10.), &vp));
+    assert!(!shape.in_bounds(&Point::new(50., -2. - HIT_TOLERANCE), &vp));
+}
+
+#[test]
+fn test_concave_polygon() {
+    //a U shape, the notch between its arms is outside
+    let u = [
+        Point::new(0., 0.), Point::new(30., 0.), Point::new(30., 100.), Point::new(70., 100.),
+        Point::new(70., 0.), Point::new(100., 0.), Point::new(100., 130.), Point::new(0., 130.)
+    ];
+    assert!(in_polygon(&u, &Point::new(15., 50.)));
+    assert!(in_polygon(&u, &Point::new(85., 50.)));
+    assert!(!in_polygon(&u, &Point::new(50., 50.)));
+    assert!(in_polygon(&u, &Point::new(50., 115.)));
+    assert!(polygon_hit(&u, &Point::new(50., 97.), HIT_TOLERANCE));
+}
+
+#[test]
+fn test_polylines_intersect() {
+    let zigzag = [Point::new(0., 0.), Point::new(50., 50.), Point::new(100., 0.)];
+    assert!(polylines_intersect(&zigzag, &[Point::new(0., 40.), Point::new(100., 40.)]));
+    assert!(!polylines_intersect(&zigzag, &[Point::new(0., 60.), Point::new(100., 60.)]));
+    //the inside of the V doesn't count, only its segments
+    assert!(!polylines_intersect(&zigzag, &[Point::new(45., 10.), Point::new(55., 10.)]));
+    assert!(!polylines_intersect(&zigzag, &[Point::new(50., 10.)]));
+}
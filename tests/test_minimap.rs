@@ -0,0 +1,59 @@
+extern crate sem_graph;
+
+use sem_graph::minimap::MinimapView;
+use sem_graph::primitives::*;
+
+fn close(a: &Point, b: &Point) -> bool {
+    a.dist(b) < 0.01
+}
+
+#[test]
+fn test_minimap_mapping() {
+    let panel = Rect::new(Point::new(600., 400.), Point::new(800., 500.));
+    let view = MinimapView::new(&Rect::new(Point::new(-100., 0.), Point::new(300., 100.)), &panel);
+    //the wider side fills the panel and the other is centered
+    assert_eq!(view.scale(), 0.5);
+    assert!(close(&view.to_panel(&Point::new(-100., 0.)), &Point::new(600., 425.)));
+    assert!(close(&view.to_panel(&Point::new(300., 100.)), &Point::new(800., 475.)));
+    let pt = Point::new(123., -45.);
+    assert!(close(&view.to_world(&view.to_panel(&pt)), &pt));
+}
+
+#[test]
+fn test_minimap_fit() {
+    let vp = Point::new(800., 600.);
+    let panel = Rect::new(Point::new(0., 0.), Point::new(200., 150.));
+    //an empty page maps the view at its origin
+    let view = MinimapView::fit(&[], &panel, &vp);
+    assert_eq!(view.bounds, Rect::new(Point::origin(), vp));
+    assert_eq!(view.scale(), 0.25);
+
+    let rect = ShapeBuilder::new().rect(100, 50).offset(900, 700).get();
+    let line = LineBuilder::new().points(-200., 10., 0., 10.).get();
+    let view = MinimapView::fit(&[(&rect, 1.), (&line, 1.)], &panel, &vp);
+    assert_eq!(view.bounds, Rect::new(Point::new(-200., 0.), Point::new(1000., 750.)));
+}
+
+#[test]
+fn test_minimap_verts() {
+    let vp = Point::new(800., 600.);
+    let panel = Rect::new(Point::new(0., 0.), Point::new(200., 150.));
+    let view = MinimapView::fit(&[], &panel, &vp);
+    let background = rgb_to_f32(255, 255, 255);
+    let filled = ShapeBuilder::new().rect(100, 100).color(0, 0, 0).get();
+    let outlined = ShapeBuilder::new().rect(100, 100).fill(false).get();
+    let line = LineBuilder::new().points(0., 0., 100., 0.).color(255, 0, 0).get();
+
+    //a filled rect is two triangles of x, y, r, g, b
+    let v = view.verts(&[(&filled, 1.)], &background, &vp);
+    assert_eq!(v.len(), 6 * 5);
+    assert_eq!(&v[..5], &[0., 0., 0., 0., 0.]);
+    //an outline is a quad for each side, a line one for each segment
+    assert_eq!(view.verts(&[(&outlined, 1.)], &background, &vp).len(), 4 * 6 * 5);
+    let v = view.verts(&[(&line, 1.)], &background, &vp);
+    assert_eq!(v.len(), 6 * 5);
+    assert_eq!(&v[2..5], &[1., 0., 0.]);
+    //shapes on faded layers are blended into the background
+    let v = view.verts(&[(&filled, 0.25)], &background, &vp);
+    assert_eq!(&v[2..5], &[0.75, 0.75, 0.75]);
+}
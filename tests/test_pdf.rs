@@ -0,0 +1,172 @@
+extern crate sem_graph;
+
+use std::collections::BTreeSet;
+use sem_graph::pdf::*;
+use sem_graph::primitives::*;
+use sem_graph::truetype::{self, TrueType};
+
+const FONT: &[u8] = include_bytes!("../src/fonts/DejaVuSans.ttf");
+
+fn find(data: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    data[from..].windows(needle.len()).position(|w| w == needle).map(|i| i + from)
+}
+
+fn number_after(data: &[u8], key: &[u8], from: usize) -> Option<(usize, usize)> {
+    let start = find(data, key, from)? + key.len();
+    let digits: String = data[start..].iter().skip_while(|b| **b == b' ').take_while(|b| b.is_ascii_digit()).map(|b| *b as char).collect();
+    digits.parse().ok().map(|n| (n, start))
+}
+
+fn test_doc() -> PdfDocument {
+    let vp = Point::new(800., 600.);
+    let mut doc = PdfDocument::new();
+    let font = doc.add_font(FONT, "DejaVu Sans", 0.).unwrap();
+    assert_eq!(doc.add_font(FONT, "DejaVu Sans", 0.), Ok(font));
+    let face = PdfFace { font, slant: 0., bold: false };
+
+    let mut page = PdfPage::new(&Rect::new(Point::new(0., 0.), Point::new(200., 100.)));
+    let rect = ShapeBuilder::new().rect(120, 60).offset(40, 30).color(10, 20, 30).stroke(200, 100, 0).line_width(2.).get();
+    draw_shape(&mut page, &rect, 0.5, &vp);
+    page.fill_color(&rgb_to_f32(0, 0, 0), 1.);
+    page.glyph(&face, 36, "A", &Point::new(10., 20.), 16.);
+    page.glyph(&face, 37, "B", &Point::new(20., 20.), 16.);
+    doc.add_page(page);
+
+    let mut page = PdfPage::new(&Rect::new(Point::new(-50., -50.), Point::new(50., 50.)));
+    let line = LineBuilder::new().points(0., 0., 40., 40.).color(0, 0, 255).get();
+    draw_shape(&mut page, &line, 1., &vp);
+    page.glyph(&PdfFace { bold: true, ..face }, 36, "A", &Point::origin(), 12.);
+    doc.add_page(page);
+    doc
+}
+
+#[test]
+fn test_pdf_structure() {
+    let data = test_doc().write().unwrap();
+    assert!(data.starts_with(b"%PDF-1.7\n"));
+    assert!(data.ends_with(b"%%EOF\n"));
+    let (xref, _) = number_after(&data, b"startxref\n", 0).unwrap();
+    assert!(data[xref..].starts_with(b"xref\n"));
+    //every entry of the cross-reference table points at its object
+    let (nobjs, _) = number_after(&data, b"xref\n0 ", xref).unwrap();
+    let (size, _) = number_after(&data, b"/Size", xref).unwrap();
+    assert_eq!(size, nobjs);
+    let table = std::str::from_utf8(&data[xref..]).unwrap();
+    let offsets: Vec<usize> = table.lines().skip(3).take(nobjs - 1).map(|l| l[..10].parse().unwrap()).collect();
+    assert_eq!(offsets.len(), 1 + 1 + 2 * 2 + 5);
+    for (i, offset) in offsets.iter().enumerate() {
+        assert!(data[*offset..].starts_with(format!("{} 0 obj\n", i + 1).as_bytes()), "object {}", i + 1);
+    }
+    //stream lengths match their data
+    let mut from = 0;
+    let mut nstreams = 0;
+    while let Some(start) = find(&data, b">>\nstream\n", from) {
+        let dict = data[..start].windows(10).rposition(|w| w == b"<< /Length").unwrap();
+        let (len, _) = number_after(&data, b"/Length", dict).unwrap();
+        let stream = start + b">>\nstream\n".len();
+        assert!(data[stream + len..].starts_with(b"\nendstream"));
+        from = stream + len;
+        nstreams += 1;
+    }
+    assert_eq!(nstreams, 2 + 2);
+    let text = String::from_utf8_lossy(&data);
+    assert!(text.contains("/Type /Pages /Kids [3 0 R 5 0 R] /Count 2"));
+    //pages fit their bounds and margin in points
+    assert!(text.contains("/MediaBox [0 0 180 105]"));
+    assert!(text.contains("/MediaBox [0 0 105 105]"));
+    assert!(text.contains("/ExtGState << /GS0 << /ca 0.5 >>"));
+}
+
+#[test]
+fn test_pdf_shapes() {
+    let vp = Point::new(800., 600.);
+    let mut rect = ShapeBuilder::new().rect(100, 50).offset(10, 20).get();
+    if let Shape::Polygon(ref mut poly) = rect {
+        poly.rect.set_radians(Radians(0.7));
+    }
+    let mut page = PdfPage::new(&Rect::new(Point::origin(), Point::new(200., 200.)));
+    draw_shape(&mut page, &rect, 1., &vp);
+    let content = page.content().to_string();
+    let mut lines = content.lines();
+    assert_eq!(lines.next(), Some("0.75 0 0 -0.75 15 165 cm"));
+    //the rect's corners are taken through its model matrix
+    let corner = match &rect {
+        Shape::Polygon(poly) => poly.rect.transform(&vp).model_to_pixel(&Point::origin().to_vec4()),
+        _ => unreachable!()
+    };
+    let m = lines.find(|l| l.ends_with(" m")).unwrap();
+    let xy: Vec<f32> = m.split_whitespace().take(2).map(|n| n.parse().unwrap()).collect();
+    assert!((xy[0] - corner.x).abs() < 0.01 && (xy[1] - corner.y).abs() < 0.01);
+    assert_eq!(content.matches(" l\n").count(), 3);
+    assert!(content.contains("\nf\n"));
+
+    let mut page = PdfPage::new(&Rect::new(Point::origin(), Point::new(200., 200.)));
+    let circle = ShapeBuilder::new().circle(40).fill(false).get();
+    draw_shape(&mut page, &circle, 1., &vp);
+    assert_eq!(page.content().matches(" c\n").count(), 4);
+    assert!(page.content().ends_with("h\nS\n"));
+}
+
+#[test]
+fn test_pdf_text() {
+    let data = test_doc().write().unwrap();
+    let text = String::from_utf8_lossy(&data);
+    assert!(text.contains("/Subtype /Type0"));
+    assert!(text.contains("/Encoding /Identity-H"));
+    assert!(text.contains("/Subtype /CIDFontType2"));
+    assert!(text.contains("/CIDToGIDMap /Identity"));
+    assert!(text.contains("/FontFile2 "));
+    assert!(text.contains("+DejaVuSans"));
+    //glyphs are shown by ID and map back to their text
+    assert!(text.contains("Tm <0024> Tj ET"));
+    assert!(text.contains("2 Tr 0.5 w\n"));
+    assert!(text.contains("2 beginbfchar\n<0024> <0041>\n<0025> <0042>\nendbfchar"));
+    assert!(text.contains("/Font << /F0 "));
+    assert!(text.contains("/Flags 4 "));
+    assert!(text.contains("/ItalicAngle 0 "));
+}
+
+#[test]
+fn test_pdf_slanted_font() {
+    let mut doc = PdfDocument::new();
+    let font = doc.add_font(FONT, "DejaVu Sans", 0.).unwrap();
+    //a synthesized italic gets a font of its own that leans right
+    let slanted = doc.add_font(FONT, "DejaVu Sans", 0.2).unwrap();
+    assert_ne!(font, slanted);
+    assert_eq!(doc.add_font(FONT, "DejaVu Sans", 0.2), Ok(slanted));
+    let mut page = PdfPage::new(&Rect::new(Point::origin(), Point::new(100., 100.)));
+    page.glyph(&PdfFace { font: slanted, slant: 0.2, bold: false }, 36, "A", &Point::origin(), 12.);
+    doc.add_page(page);
+    let data = doc.write().unwrap();
+    let text = String::from_utf8_lossy(&data);
+    assert!(text.contains("+DejaVuSans-Oblique"));
+    assert!(text.contains("/Flags 68 "));
+    assert!(text.contains("/ItalicAngle -11.3099 "));
+}
+
+#[test]
+fn test_font_subset() {
+    let font = TrueType::parse(FONT).unwrap();
+    let gids: BTreeSet<u16> = [36, 37].iter().cloned().collect();
+    let data = font.subset(&gids).unwrap();
+    assert_eq!(truetype::checksum(&data), 0xB1B0_AFBA);
+    let subset = TrueType::parse(&data).unwrap();
+    assert_eq!(subset.num_glyphs, font.num_glyphs);
+    assert_eq!(subset.units_per_em, font.units_per_em);
+    assert_eq!(subset.advance(36), font.advance(36));
+    for gid in [0, 36, 37].iter() {
+        assert_eq!(subset.glyph_data(*gid).unwrap(), font.glyph_data(*gid).unwrap());
+    }
+    assert!(subset.glyph_data(38).unwrap().is_empty());
+    assert!(data.len() < FONT.len() / 4);
+
+    //composite glyphs keep the glyphs they're built from
+    let composite = (1..font.num_glyphs).find(|g| {
+        let glyph = font.glyph_data(*g).unwrap();
+        glyph.len() > 2 && (glyph[0] & 0x80) != 0
+    }).unwrap();
+    let data = font.subset(&[composite].iter().cloned().collect()).unwrap();
+    let subset = TrueType::parse(&data).unwrap();
+    let kept = (1..subset.num_glyphs).filter(|g| !subset.glyph_data(*g).unwrap().is_empty()).count();
+    assert!(kept >= 2);
+}
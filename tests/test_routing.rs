@@ -0,0 +1,94 @@
+extern crate proptest;
+
+extern crate sem_graph;
+
+use sem_graph::geometry::*;
+use sem_graph::primitives::*;
+use sem_graph::routing::RouteQuery;
+
+use proptest::prelude::*;
+
+const EPS: f32 = 1e-3;
+
+//the whole route, ends included
+fn route(start: Point, end: Point, waypoints: &[Point], nodes: &[Rect]) -> Vec<Point> {
+    let bends = RouteQuery::new(start, end, waypoints, nodes, &[]).route(nodes);
+    std::iter::once(start).chain(bends).chain(std::iter::once(end)).collect()
+}
+
+fn axis_aligned(path: &[Point]) -> bool {
+    path.windows(2).all(|s| (s[0].x - s[1].x).abs() < EPS || (s[0].y - s[1].y).abs() < EPS)
+}
+
+//whether an axis aligned segment goes through the inside of the rect
+fn crosses(r: &Rect, a: &Point, b: &Point) -> bool {
+    let seg = Rect::new(*a, *b);
+    seg.c1.x < r.c2.x && seg.c2.x > r.c1.x && seg.c1.y < r.c2.y && seg.c2.y > r.c1.y
+}
+
+fn avoids(path: &[Point], nodes: &[Rect]) -> bool {
+    path.windows(2).all(|s| nodes.iter().all(|r| !crosses(r, &s[0], &s[1])))
+}
+
+#[test]
+fn test_route_around_obstacle() {
+    let nodes = [Rect::new(Point::new(150., 50.), Point::new(250., 150.))];
+    let path = route(Point::new(0., 100.), Point::new(400., 100.), &[], &nodes);
+    assert!(axis_aligned(&path));
+    assert!(avoids(&path, &nodes));
+    //around it, not through it
+    assert!(path.len() >= 4);
+
+    //nothing in the way, nothing to bend around
+    let path = route(Point::new(0., 100.), Point::new(400., 100.), &[], &[]);
+    assert_eq!(path, vec![Point::new(0., 100.), Point::new(400., 100.)]);
+}
+
+#[test]
+fn test_route_between_obstacles() {
+    //a wall with a gap in it the route has to go through
+    let nodes = vec![
+        Rect::new(Point::new(150., -300.), Point::new(250., 60.)),
+        Rect::new(Point::new(150., 140.), Point::new(250., 500.)),
+        Rect::new(Point::new(300., 80.), Point::new(340., 120.)),
+    ];
+    let path = route(Point::new(0., 0.), Point::new(400., 100.), &[], &nodes);
+    assert!(axis_aligned(&path));
+    assert!(avoids(&path, &nodes));
+}
+
+#[test]
+fn test_route_keeps_waypoints() {
+    let nodes = [Rect::new(Point::new(150., 50.), Point::new(250., 150.))];
+    //the second is off to the side of the path to the end, the route goes out to it and comes back
+    let waypoints = vec![Point::new(100., 300.), Point::new(300., -100.)];
+    let path = route(Point::new(0., 100.), Point::new(400., 100.), &waypoints, &nodes);
+    assert!(axis_aligned(&path));
+    assert!(avoids(&path, &nodes));
+    //each waypoint is on the route, in order
+    let mut from = 0;
+    for w in waypoints.iter() {
+        let seg = (from..path.len() - 1).find(|i| dist_to_segment(w, &path[*i], &path[*i + 1]) < EPS);
+        assert!(seg.is_some(), "{:?} isn't on {:?}", w, path);
+        from = seg.unwrap();
+    }
+}
+
+fn point() -> impl Strategy<Value = Point> {
+    (-300f32..300., -300f32..300.).prop_map(|(x, y)| Point::new(x, y))
+}
+
+fn node() -> impl Strategy<Value = Rect> {
+    (point(), 20f32..120., 20f32..120.).prop_map(|(c1, w, h)| Rect::new(c1, c1 + Point::new(w, h)))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+    #[test]
+    fn routes_are_orthogonal(start in point(), end in point(), waypoint in point(), nodes in prop::collection::vec(node(), 0..6)) {
+        let path = route(start, end, &[waypoint], &nodes);
+        prop_assert!(axis_aligned(&path), "{:?}", path);
+        prop_assert_eq!(path[0], start);
+        prop_assert_eq!(path[path.len() - 1], end);
+    }
+}
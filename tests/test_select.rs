@@ -0,0 +1,84 @@
+extern crate sem_graph;
+
+use sem_graph::interface::*;
+use sem_graph::primitives::*;
+
+const VIEWPORT: Point = Point { x: 800., y: 600. };
+
+fn region(x1: f32, y1: f32, x2: f32, y2: f32) -> Vec<Point> {
+    Rect::new(Point::new(x1, y1), Point::new(x2, y2)).verts()
+}
+
+//corners at (200, 100), (300, 200), (200, 300) and (100, 200)
+fn diamond() -> Shape {
+    ShapeBuilder::new().diamond(200, 200).offset(100, 100).get()
+}
+
+fn square(x: i32, y: i32, size: u32) -> Shape {
+    ShapeBuilder::new().rect(size, size).offset(x, y).get()
+}
+
+fn picks(shape: &Shape, region: &[Point]) -> (bool, bool) {
+    (shape.in_select_region(region, SelectMode::Intersect, &VIEWPORT), shape.in_select_region(region, SelectMode::Contain, &VIEWPORT))
+}
+
+#[test]
+fn test_select_box() {
+    let diamond = diamond();
+    //the top right edge crosses the box, none of the corners are in it
+    assert_eq!(picks(&diamond, &region(240., 130., 280., 170.)), (true, false));
+    //a box inside the shape touches it
+    assert_eq!(picks(&diamond, &region(190., 190., 210., 210.)), (true, false));
+    //inside the diamond's rect but outside the diamond
+    assert_eq!(picks(&diamond, &region(105., 105., 140., 140.)), (false, false));
+    assert_eq!(picks(&diamond, &region(90., 90., 310., 310.)), (true, true));
+    //one corner in, the rest out
+    assert_eq!(picks(&diamond, &region(250., 150., 350., 250.)), (true, false));
+
+    //a line only crossing the box
+    let line = LineBuilder::new().points(0., 150., 400., 150.).get();
+    assert_eq!(picks(&line, &region(240., 130., 280., 170.)), (true, false));
+    assert_eq!(picks(&line, &region(-10., 140., 410., 160.)), (true, true));
+    assert_eq!(picks(&line, &region(240., 160., 280., 200.)), (false, false));
+}
+
+#[test]
+fn test_select_lasso() {
+    //a U, with the notch between its arms open at the bottom
+    let lasso = vec![
+        Point::new(0., 0.), Point::new(400., 0.), Point::new(400., 400.), Point::new(300., 400.),
+        Point::new(300., 100.), Point::new(100., 100.), Point::new(100., 400.), Point::new(0., 400.),
+    ];
+    //in the notch, inside the lasso's bounds but not the lasso
+    assert_eq!(picks(&square(170, 250, 60), &lasso), (false, false));
+    //in an arm
+    assert_eq!(picks(&square(30, 200, 40), &lasso), (true, true));
+    //across the edge of an arm
+    assert_eq!(picks(&square(80, 200, 40), &lasso), (true, false));
+    //across both arms and the notch, with its corners out past them
+    let bar = ShapeBuilder::new().rect(500, 50).offset(-50, 250).get();
+    assert_eq!(picks(&bar, &lasso), (true, false));
+}
+
+#[test]
+fn test_region_selection() {
+    let mut draw_list = DrawList::new();
+    let diamond = draw_list.add(diamond());
+    let inside = draw_list.add(square(190, 190, 20));
+    let line = draw_list.add(LineBuilder::new().points(0., 150., 400., 150.).get());
+    let select = |region: &[Point], mode: SelectMode| {
+        let mut ids = draw_list.get_region_selection(region, mode, &VIEWPORT);
+        ids.sort();
+        ids
+    };
+    let edge = region(240., 130., 280., 170.);
+    assert_eq!(select(&edge, SelectMode::Intersect), vec![diamond, line]);
+    assert_eq!(select(&edge, SelectMode::Contain), vec![]);
+    let middle = region(160., 160., 240., 240.);
+    assert_eq!(select(&middle, SelectMode::Intersect), vec![diamond, inside]);
+    assert_eq!(select(&middle, SelectMode::Contain), vec![inside]);
+    //a lasso that's only been dragged a little doesn't select anything
+    assert_eq!(select(&[Point::new(0., 0.), Point::new(400., 400.)], SelectMode::Intersect), vec![]);
+    assert_eq!(SelectMode::Intersect.next(), SelectMode::Contain);
+    assert_eq!(SelectMode::Contain.next(), SelectMode::Intersect);
+}
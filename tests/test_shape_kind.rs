@@ -0,0 +1,78 @@
+extern crate sem_graph;
+
+use std::rc::Rc;
+use sem_graph::document::*;
+use sem_graph::layers::Layer;
+use sem_graph::primitives::*;
+use sem_graph::shape_kind::{self, ShapeKind};
+
+const VIEWPORT: Point = Point { x: 800., y: 600. };
+
+//flowchart document, a rect with a wavy bottom edge
+struct FlowDocument;
+
+impl ShapeKind for FlowDocument {
+    fn name(&self) -> &str {
+        "document"
+    }
+    fn outline(&self) -> Vec<Point> {
+        let mut pts = vec![Point::new(0., 0.), Point::new(1., 0.)];
+        pts.extend((0..=16).map(|i| {
+            let x = 1. - i as f32 / 16.;
+            Point::new(x, 0.9 - 0.08 * f32::sin(2. * std::f32::consts::PI * x))
+        }));
+        pts
+    }
+}
+
+fn document_shape(prim: PrimType) -> Shape {
+    let mut shape = ShapeBuilder::new().rect(400, 400).offset(100, 50).color(0, 255, 255).get();
+    if let Shape::Polygon(ref mut poly) = shape {
+        poly.prim = prim;
+    }
+    shape
+}
+
+fn save(shape: &Shape) -> String {
+    let mut out = String::new();
+    write_layer(&mut out, &Layer::new("Layer 1"));
+    write_shape(&mut out, shape, None, None, None);
+    out
+}
+
+#[test]
+fn test_shape_kind_round_trip() {
+    //the registry is per thread, this one doesn't know the kind yet
+    let saved = save(&document_shape(PrimType::Rect)).replace("prim = rect", "prim = document");
+    assert!(saved.contains("prim = document"));
+    assert!(Document::parse(&saved).is_err());
+
+    let prim = shape_kind::register(Rc::new(FlowDocument)).unwrap();
+    assert_eq!(prim.name(), "document");
+    assert_eq!(PrimType::from_name("document"), Some(prim));
+    let shape = document_shape(prim);
+    let saved = save(&shape);
+    assert!(saved.contains("prim = document"));
+    let doc = Document::parse(&saved).unwrap();
+    assert!(doc.pages[0].layers[0].shapes[0].shape == shape);
+
+    assert!(shape_kind::register(Rc::new(FlowDocument)).is_err());
+}
+
+#[test]
+fn test_shape_kind_in_bounds() {
+    let prim = shape_kind::register(Rc::new(FlowDocument)).unwrap();
+    let poly = match document_shape(prim) {
+        Shape::Polygon(poly) => poly,
+        Shape::Line(_) => unreachable!()
+    };
+    let to_pixel = |x: f32, y: f32| poly.rect.transform(&VIEWPORT).model_to_pixel(&Point::new(x, y).to_vec4());
+    assert!(poly.in_bounds(&to_pixel(0.5, 0.5), &VIEWPORT));
+    assert!(!poly.in_bounds(&to_pixel(1.1, 0.5), &VIEWPORT));
+    //the wavy bottom edge rises on the left and dips on the right
+    assert!(!poly.in_bounds(&to_pixel(0.25, 0.88), &VIEWPORT));
+    assert!(poly.in_bounds(&to_pixel(0.75, 0.88), &VIEWPORT));
+    //it is filled with the triangles the kind splits it into
+    let tris = prim.draw_range(false);
+    assert_eq!(tris.1.len(), FlowDocument.tessellate().len());
+}
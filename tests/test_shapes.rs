@@ -0,0 +1,95 @@
+extern crate gl;
+extern crate proptest;
+
+extern crate sem_graph;
+
+use sem_graph::geometry::*;
+use sem_graph::primitives::*;
+
+use proptest::prelude::*;
+
+const VIEWPORT: Point = Point { x: 800., y: 600. };
+const SHAPE_SIZE: u32 = 200;
+
+fn diagram_shapes() -> Vec<DrawPolygon> {
+    let s = SHAPE_SIZE;
+    vec![
+        ShapeBuilder::new().round_rect(s, s, 25),
+        ShapeBuilder::new().diamond(s, s),
+        ShapeBuilder::new().hexagon(s, s),
+        ShapeBuilder::new().parallelogram(s, s),
+        ShapeBuilder::new().cylinder(s, s),
+        ShapeBuilder::new().star(5, s, s),
+    ].into_iter().map(|b| match b.offset(100, 50).get() {
+        Shape::Polygon(poly) => poly,
+        Shape::Line(_) => unreachable!()
+    }).collect()
+}
+
+fn to_pixel(poly: &DrawPolygon, model: &Point) -> Point {
+    poly.rect.transform(&VIEWPORT).model_to_pixel(&model.to_vec4())
+}
+
+#[test]
+fn test_shape_verts() {
+    for poly in diagram_shapes() {
+        let verts = poly.verts(&VIEWPORT);
+        assert_eq!(verts.len(), poly.prim.size(), "{}", poly.prim.name());
+        //a fan around the center of the rect, its outline closed back on its first corner
+        assert!(verts[0].dist(&Point::new(200., 150.)) < 0.01, "{}", poly.prim.name());
+        let bounds = Rect::new(Point::new(100., 50.), Point::new(300., 250.));
+        assert!(verts.iter().all(|v| bounds.contains(v)), "{}", poly.prim.name());
+        if poly.prim != PrimType::Cylinder {
+            assert_eq!(verts[1], verts[verts.len() - 1], "{}", poly.prim.name());
+            assert_eq!(verts.len(), poly.prim.outline().len() + 2, "{}", poly.prim.name());
+        }
+    }
+}
+
+#[test]
+fn test_shape_draw_mode() {
+    for poly in diagram_shapes() {
+        let n = poly.prim.size();
+        assert_eq!(poly.prim.mode(), gl::TRIANGLE_FAN, "{}", poly.prim.name());
+        assert_eq!(poly.prim.draw_range(false), (gl::TRIANGLE_FAN, 0..n), "{}", poly.prim.name());
+        //the outline skips the center of the fan
+        assert_eq!(poly.prim.draw_range(true), (gl::LINE_STRIP, 1..n), "{}", poly.prim.name());
+    }
+    //the cylinder's outline carries on from the silhouette into the front of its top
+    assert_eq!(PrimType::Cylinder.detail().len(), PrimType::Cylinder.size() - PrimType::Cylinder.outline().len() - 2);
+}
+
+#[test]
+fn test_shape_in_bounds() {
+    for poly in diagram_shapes() {
+        let name = poly.prim.name();
+        assert!(poly.in_bounds(&to_pixel(&poly, &Point::new(0.5, 0.5)), &VIEWPORT), "{}", name);
+        assert!(!poly.in_bounds(&to_pixel(&poly, &Point::new(1.2, 0.5)), &VIEWPORT), "{}", name);
+        assert!(!poly.in_bounds(&to_pixel(&poly, &Point::new(0.5, -0.2)), &VIEWPORT), "{}", name);
+    }
+    //none of them reach into the corner of their rect
+    let corner = Point::new(0.02, 0.02);
+    for poly in diagram_shapes() {
+        assert!(!poly.in_bounds(&to_pixel(&poly, &corner), &VIEWPORT), "{}", poly.prim.name());
+    }
+    let star = &diagram_shapes()[5];
+    //in the notch between the star's two right points
+    assert!(!star.in_bounds(&to_pixel(star, &Point::new(0.8, 0.65)), &VIEWPORT));
+    assert!(star.in_bounds(&to_pixel(star, &Point::new(0.65, 0.6)), &VIEWPORT));
+}
+
+fn model_point() -> impl Strategy<Value = Point> {
+    (-0.2f32..1.2, -0.2f32..1.2).prop_map(|(x, y)| Point::new(x, y))
+}
+
+proptest! {
+    //away from the edges, a hit is being inside the outline that's drawn
+    #[test]
+    fn shape_hit_matches_outline(p in model_point(), i in 0usize..6) {
+        let poly = &diagram_shapes()[i];
+        let pixel = to_pixel(poly, &p);
+        let outline = poly.outline(&VIEWPORT);
+        prop_assume!(dist_to_polygon_edge(&pixel, &outline) > HIT_TOLERANCE + 2.);
+        prop_assert_eq!(poly.in_bounds(&pixel, &VIEWPORT), in_polygon(&outline, &pixel), "{}", poly.prim.name());
+    }
+}
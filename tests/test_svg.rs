@@ -0,0 +1,85 @@
+extern crate sem_graph;
+
+use sem_graph::primitives::*;
+use sem_graph::svg::*;
+
+const VIEWPORT: Point = Point { x: 800., y: 600. };
+
+fn attr(el: &str, name: &str) -> String {
+    let key = format!(" {}=\"", name);
+    let start = el.find(&key).unwrap() + key.len();
+    el[start..].split('"').next().unwrap().to_string()
+}
+
+#[test]
+fn test_svg_page() {
+    let page = SvgPage::new(&Rect::new(Point::new(-50., 0.), Point::new(150., 100.)));
+    let out = page.write();
+    assert!(out.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg "));
+    assert!(out.ends_with("</svg>\n"));
+    //the page fits its bounds and margin, in canvas pixels
+    let svg = out.lines().nth(1).unwrap();
+    assert_eq!(attr(svg, "width"), "240");
+    assert_eq!(attr(svg, "height"), "140");
+    assert_eq!(attr(svg, "viewBox"), "-70 -20 240 140");
+    //a file per page, in page order
+    assert_eq!(SvgPage::path(0), "diagram-1.svg");
+    assert_eq!(SvgPage::path(2), "diagram-3.svg");
+}
+
+#[test]
+fn test_svg_shapes() {
+    let mut rect = ShapeBuilder::new().rect(100, 50).offset(10, 20).color(255, 0, 0).stroke(0, 0, 255).line_width(2.).get();
+    if let Shape::Polygon(ref mut poly) = rect {
+        poly.rect.set_radians(Radians(0.7));
+    }
+    let mut page = SvgPage::new(&Rect::new(Point::origin(), Point::new(200., 200.)));
+    draw_shape(&mut page, &rect, 0.5, &VIEWPORT);
+    let out = page.write();
+    let paths: Vec<&str> = out.lines().filter(|l| l.starts_with("<path ")).collect();
+    assert_eq!(paths.len(), 2);
+    assert_eq!(attr(paths[0], "fill"), "#ff0000");
+    assert_eq!(attr(paths[0], "fill-opacity"), "0.5");
+    assert_eq!(attr(paths[1], "fill"), "none");
+    assert_eq!(attr(paths[1], "stroke"), "#0000ff");
+    assert_eq!(attr(paths[1], "stroke-width"), "2");
+    //the rect's corners are taken through its model matrix
+    let corner = match &rect {
+        Shape::Polygon(poly) => poly.rect.transform(&VIEWPORT).model_to_pixel(&Point::origin().to_vec4()),
+        _ => unreachable!()
+    };
+    let d = attr(paths[0], "d");
+    let xy: Vec<f32> = d[1..].split_whitespace().take(2).map(|n| n.parse().unwrap()).collect();
+    assert!((xy[0] - corner.x).abs() < 0.01 && (xy[1] - corner.y).abs() < 0.01);
+    assert_eq!(d.matches('L').count(), 3);
+    assert!(d.ends_with('Z'));
+
+    let mut page = SvgPage::new(&Rect::new(Point::origin(), Point::new(200., 200.)));
+    let circle = ShapeBuilder::new().circle(40).fill(false).get();
+    draw_shape(&mut page, &circle, 1., &VIEWPORT);
+    let line = LineBuilder::new().points(0., 0., 40., 40.).color(0, 0, 255).get();
+    draw_shape(&mut page, &line, 1., &VIEWPORT);
+    let out = page.write();
+    let paths: Vec<&str> = out.lines().filter(|l| l.starts_with("<path ")).collect();
+    assert_eq!(attr(paths[0], "d").matches('C').count(), 4);
+    assert_eq!(attr(paths[0], "fill"), "none");
+    assert_eq!(attr(paths[1], "d"), "M0 0 L40 40");
+}
+
+#[test]
+fn test_svg_text() {
+    let mut page = SvgPage::new(&Rect::new(Point::origin(), Point::new(200., 200.)));
+    let face = SvgFace { family: "DejaVu Sans".to_string(), bold: true, italic: false };
+    page.group(&RotateRect::new(Point::new(10., 10.), Point::new(100., 50.), Radians(0.)).transform(&VIEWPORT).model);
+    page.text(&face, "a < b & \"c\"", &Point::new(5., 20.), 16., &rgb_to_f32(0, 0, 0), 1.);
+    page.end_group();
+    let out = page.write();
+    let text = out.lines().find(|l| l.starts_with("<text ")).unwrap();
+    assert_eq!(attr(text, "font-family"), "DejaVu Sans");
+    assert_eq!(attr(text, "font-weight"), "bold");
+    assert!(!text.contains("font-style"));
+    assert_eq!(attr(text, "x"), "5");
+    assert!(text.ends_with(">a &lt; b &amp; &quot;c&quot;</text>"));
+    assert!(out.contains("<g transform=\"matrix("));
+    assert_eq!(out.matches("<g ").count(), out.matches("</g>").count());
+}
@@ -0,0 +1,165 @@
+extern crate sdl2;
+extern crate gl;
+
+extern crate sem_graph;
+
+use sem_graph::fonts::FontConfig;
+use sem_graph::primitives::*;
+use sem_graph::render_text::RenderText;
+use sem_graph::textedit::*;
+
+fn typed(text: &str) -> TextBox {
+    let mut tb = TextBox::new();
+    text.chars().for_each(|ch| tb.insert_char(ch));
+    tb
+}
+
+//a run of typing is undone in one step, a space or moving the cursor starts a new one
+#[test]
+fn test_undo_typing() {
+    let mut tb = typed("abc");
+    tb.undo();
+    assert_eq!(tb.text(), "");
+    tb.redo();
+    assert_eq!(tb.text(), "abc");
+
+    tb.set_cursor_pos(1, false);
+    "xy".chars().for_each(|ch| tb.insert_char(ch));
+    assert_eq!(tb.text(), "axybc");
+    tb.undo();
+    assert_eq!(tb.text(), "abc");
+    tb.undo();
+    assert_eq!(tb.text(), "");
+
+    //moving the cursor breaks the run even when it ends up where it was
+    let mut tb = typed("ab");
+    tb.set_cursor_pos(2, false);
+    tb.insert_char('c');
+    tb.undo();
+    assert_eq!(tb.text(), "ab");
+
+    let mut tb = typed("hello world");
+    tb.undo();
+    assert_eq!(tb.text(), "hello ");
+    tb.undo();
+    assert_eq!(tb.text(), "hello");
+    tb.undo();
+    assert_eq!(tb.text(), "");
+    tb.undo();
+    assert_eq!(tb.text(), "");
+}
+
+//enter breaks the line between two runs of typing, each undone on its own
+#[test]
+fn test_undo_newline() {
+    let mut tb = typed("ab");
+    tb.insert_char('\n');
+    "cd".chars().for_each(|ch| tb.insert_char(ch));
+    assert_eq!(tb.text(), "ab\ncd");
+    tb.undo();
+    assert_eq!(tb.text(), "ab\n");
+    tb.undo();
+    assert_eq!(tb.text(), "ab");
+    tb.undo();
+    assert_eq!(tb.text(), "");
+}
+
+#[test]
+fn test_undo_deleting() {
+    let mut tb = typed("abcd");
+    tb.delete_char();
+    tb.delete_char();
+    assert_eq!(tb.text(), "ab");
+    //typing after deleting is its own step
+    tb.insert_char('z');
+    tb.undo();
+    assert_eq!(tb.text(), "ab");
+    tb.undo();
+    assert_eq!(tb.text(), "abcd");
+    //a new edit drops what could be redone
+    tb.insert_char('e');
+    tb.redo();
+    assert_eq!(tb.text(), "abcde");
+}
+
+//with shift the anchor stays where the selection started, the cursor can go past it either way
+#[test]
+fn test_extend_selection() {
+    let mut tb = typed("hello world");
+    tb.set_cursor_pos(2, false);
+    assert_eq!(tb.selection(), None);
+    tb.set_cursor_pos(5, true);
+    assert_eq!(tb.selection(), Some(2..5));
+    tb.set_cursor_pos(8, true);
+    assert_eq!(tb.selection(), Some(2..8));
+    assert_eq!(tb.selected_text(), Some("llo wo".to_string()));
+    tb.set_cursor_pos(0, true);
+    assert_eq!(tb.selection(), Some(0..2));
+    tb.set_cursor_pos(4, false);
+    assert_eq!(tb.selection(), None);
+
+    //typing replaces the selection, undoing brings it back
+    tb.set_cursor_pos(0, false);
+    tb.set_cursor_pos(5, true);
+    tb.insert_char('H');
+    assert_eq!(tb.text(), "H world");
+    tb.undo();
+    assert_eq!(tb.text(), "hello world");
+}
+
+//text is measured with the glyphs it's drawn with, which are kept in a GL texture. the window is never shown
+fn with_render_text<F: FnOnce(&RenderText)>(f: F) {
+    let sdl = sdl2::init().unwrap();
+    let video_subsystem = sdl.video().unwrap();
+    let gl_attr = video_subsystem.gl_attr();
+    gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
+    gl_attr.set_context_version(3, 3);
+    let window = video_subsystem.window("Text", 100, 100).opengl().hidden().build().unwrap();
+    let _gl_context = window.gl_create_context().unwrap();
+    gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as *const std::os::raw::c_void);
+    f(&RenderText::new(&FontConfig::default_config()).unwrap());
+}
+
+//words are runs of letters, digits and underscores or of punctuation, moving right skips the spaces after one
+#[test]
+fn test_word_motion() {
+    with_render_text(|rt| {
+        let rect = RotateRect::new(Point::origin(), Point::new(1000., 100.), Radians(0.));
+        let text = "foo_bar, baz  qux.end";
+        let mut tb = typed(text);
+        //where the cursor goes from `idx`, the end of the selection made by moving it with shift
+        let mut step = |idx: usize, dir: TextCursorDirection| {
+            tb.set_cursor_pos(idx, false);
+            tb.move_cursor(dir, true, &rect, rt);
+            match tb.selection() {
+                Some(r) if r.start == idx => r.end,
+                Some(r) => r.start,
+                None => idx
+            }
+        };
+        let rights: Vec<usize> = (0..7).scan(0, |idx, _| { *idx = step(*idx, TextCursorDirection::WordRight); Some(*idx) }).collect();
+        assert_eq!(rights, vec![7, 9, 14, 17, 18, 21, 21]);
+        let lefts: Vec<usize> = (0..7).scan(text.len(), |idx, _| { *idx = step(*idx, TextCursorDirection::WordLeft); Some(*idx) }).collect();
+        assert_eq!(lefts, vec![18, 17, 14, 9, 7, 0, 0]);
+
+        //shift extends by words from where the selection started
+        tb.set_cursor_pos(9, false);
+        tb.move_cursor(TextCursorDirection::WordRight, true, &rect, rt);
+        assert_eq!(tb.selection(), Some(9..14));
+        tb.move_cursor(TextCursorDirection::WordRight, true, &rect, rt);
+        assert_eq!(tb.selection(), Some(9..17));
+        tb.move_cursor(TextCursorDirection::WordLeft, true, &rect, rt);
+        tb.move_cursor(TextCursorDirection::WordLeft, true, &rect, rt);
+        assert_eq!(tb.selection(), None);
+        tb.move_cursor(TextCursorDirection::WordLeft, true, &rect, rt);
+        assert_eq!(tb.selection(), Some(7..9));
+        //without shift left and right go to the ends of the selection
+        tb.move_cursor(TextCursorDirection::Right, false, &rect, rt);
+        assert_eq!(tb.selection(), None);
+        tb.move_cursor(TextCursorDirection::WordRight, true, &rect, rt);
+        assert_eq!(tb.selection(), Some(9..14));
+        tb.move_cursor(TextCursorDirection::Left, false, &rect, rt);
+        tb.move_cursor(TextCursorDirection::WordRight, true, &rect, rt);
+        assert_eq!(tb.selection(), Some(9..14));
+    });
+}
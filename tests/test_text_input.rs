@@ -0,0 +1,62 @@
+extern crate sdl2;
+
+extern crate sem_graph;
+
+use sem_graph::textedit::*;
+
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+
+fn text_input(text: &str) -> Event {
+    Event::TextInput { timestamp: 0, window_id: 0, text: text.to_string() }
+}
+
+fn text_editing(text: &str, start: i32) -> Event {
+    Event::TextEditing { timestamp: 0, window_id: 0, text: text.to_string(), start, length: 0 }
+}
+
+//drives a text box through the events SDL sends while an IME composes a word, no window needed
+#[test]
+fn test_text_input() {
+    let mut tb = TextBox::new();
+    let events = vec![
+        text_input("a"),
+        text_input("B"),
+        text_input("é"),
+        text_input("!"),
+        text_editing("に", 1),
+        text_editing("にほ", 2),
+        text_editing("日本", 2),
+        text_editing("", 0),
+        text_input("日本"),
+    ];
+    let mut compositions = Vec::new();
+    for ev in events.iter() {
+        match get_text_input(ev) {
+            Some(TextInput::Commit(text)) => tb.commit_text(&text),
+            Some(TextInput::Compose(comp)) => {
+                tb.set_composition(comp);
+                compositions.push(tb.composition().cloned());
+            }
+            None => panic!("{:?} is not a text input event", ev)
+        }
+    }
+    assert_eq!(tb.text(), "aBé!日本");
+    assert_eq!(tb.composition(), None);
+    assert_eq!(compositions, vec![
+        Some(Composition { text: "に".to_string(), cursor: 1 }),
+        Some(Composition { text: "にほ".to_string(), cursor: 2 }),
+        Some(Composition { text: "日本".to_string(), cursor: 2 }),
+        None,
+    ]);
+
+    //the IME cursor is kept inside the composed text
+    tb.set_composition(Composition { text: "ab".to_string(), cursor: 10 });
+    assert_eq!(tb.composition().map(|c| c.cursor), Some(2));
+
+    let key = Event::KeyDown {
+        timestamp: 0, window_id: 0, keycode: Some(Keycode::A), scancode: None,
+        keymod: Mod::NOMOD, repeat: false
+    };
+    assert_eq!(get_text_input(&key), None);
+}
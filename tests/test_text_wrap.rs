@@ -0,0 +1,121 @@
+extern crate sdl2;
+extern crate gl;
+
+extern crate sem_graph;
+
+use sem_graph::fonts::FontConfig;
+use sem_graph::primitives::*;
+use sem_graph::render_text::RenderText;
+use sem_graph::textedit::*;
+
+const VIEWPORT: Point = Point { x: 800., y: 600. };
+const TEXT: &str = "the quick brown fox jumps over the lazy dog";
+const OFFSET: Point = Point { x: 100., y: 50. };
+
+//text is measured with the glyphs it's drawn with, which are kept in a GL texture. the window is never shown
+fn with_render_text<F: FnOnce(&RenderText)>(f: F) {
+    let sdl = sdl2::init().unwrap();
+    let video_subsystem = sdl.video().unwrap();
+    let gl_attr = video_subsystem.gl_attr();
+    gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
+    gl_attr.set_context_version(3, 3);
+    let window = video_subsystem.window("Text", 100, 100).opengl().hidden().build().unwrap();
+    let _gl_context = window.gl_create_context().unwrap();
+    gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as *const std::os::raw::c_void);
+    f(&RenderText::new(&FontConfig::default_config()).unwrap());
+}
+
+fn text_box() -> TextBox {
+    let mut tb = TextBox::new();
+    tb.insert_text(TEXT);
+    tb.set_fit(TextFit::GrowShape);
+    tb
+}
+
+//a box a third as wide as the text, tall enough to show every line
+fn narrow_rect(tb: &TextBox, rt: &RenderText) -> RotateRect {
+    RotateRect::new(OFFSET, Point::new(tb.natural_size(rt).x / 3., 400.), Radians(0.))
+}
+
+fn wide_rect(tb: &TextBox, rt: &RenderText) -> RotateRect {
+    RotateRect::new(OFFSET, Point::new(tb.natural_size(rt).x + 50., 400.), Radians(0.))
+}
+
+//wrapping only changes how the text is drawn, no line breaks are put in it
+fn wrap_keeps_text(rt: &RenderText) {
+    let tb = text_box();
+    let saved = tb.write();
+    let narrow = narrow_rect(&tb, rt);
+    let placed = tb.place_glyphs(&narrow, rt, &VIEWPORT);
+    assert_eq!(tb.text(), TEXT);
+    assert_eq!(tb.write(), saved);
+    //every char is still drawn, on more than one line
+    let drawn: String = placed.glyphs.iter().flat_map(|g| g.text.chars()).filter(|c| !c.is_whitespace()).collect();
+    assert_eq!(drawn, TEXT.replace(' ', ""));
+    let mut baselines: Vec<f32> = placed.glyphs.iter().map(|g| g.pen.y).collect();
+    baselines.dedup();
+    assert!(baselines.len() >= 3, "{:?}", baselines);
+}
+
+//the lines are only cached for the size they were wrapped to
+fn rewrap_on_resize(rt: &RenderText) {
+    let mut tb = text_box();
+    let (wide, narrow) = (wide_rect(&tb, rt), narrow_rect(&tb, rt));
+    let line_height = tb.fit_height(&wide, rt).unwrap();
+    let height = tb.fit_height(&narrow, rt).unwrap();
+    let nlines = (height / line_height).round();
+    assert!(nlines >= 3., "{} lines", nlines);
+    assert!((height - nlines * line_height).abs() < 0.01);
+    assert_eq!(tb.fit_height(&wide, rt), Some(line_height));
+    assert_eq!(tb.fit_height(&narrow, rt), Some(height));
+    //and after an edit at the same size
+    tb.insert_text(" and back over the fox");
+    assert!(tb.fit_height(&narrow, rt).unwrap() > height);
+}
+
+//the caret moves through the lines as they're drawn, the space a line wraps at hangs past its end
+fn caret_across_lines(rt: &RenderText) {
+    let mut tb = text_box();
+    let narrow = narrow_rect(&tb, rt);
+    let line_height = tb.fit_height(&wide_rect(&tb, rt), rt).unwrap();
+    let sel_end = |tb: &TextBox| tb.selection().map_or(0, |r| r.end);
+
+    tb.set_cursor_pos(0, false);
+    tb.move_cursor(TextCursorDirection::LineEnd, true, &narrow, rt);
+    let end1 = sel_end(&tb);
+    assert_eq!(tb.selection(), Some(0..end1));
+    assert_eq!(TEXT.as_bytes()[end1], b' ');
+    //past the space is the start of the next line
+    tb.move_cursor(TextCursorDirection::Right, true, &narrow, rt);
+    let start2 = sel_end(&tb);
+    assert_eq!(start2, end1 + 1);
+    tb.move_cursor(TextCursorDirection::LineEnd, true, &narrow, rt);
+    let end2 = sel_end(&tb);
+    assert!(end2 > start2 && end2 < TEXT.len());
+    assert_eq!(TEXT.as_bytes()[end2], b' ');
+    tb.move_cursor(TextCursorDirection::LineStart, true, &narrow, rt);
+    assert_eq!(sel_end(&tb), start2);
+    tb.move_cursor(TextCursorDirection::Up, true, &narrow, rt);
+    assert_eq!(tb.selection(), None);
+    tb.move_cursor(TextCursorDirection::Down, true, &narrow, rt);
+    assert_eq!(tb.selection(), Some(0..start2));
+
+    //points over the box find the char on the line drawn there
+    let second_line = OFFSET + Point::new(1., 1.5 * line_height);
+    assert_eq!(tb.hover_text(&second_line, &narrow, rt, &VIEWPORT), Some(start2));
+    assert_eq!(tb.nearest_text_pos(&second_line, &narrow, rt, &VIEWPORT), start2);
+    let first_line_end = OFFSET + Point::new(narrow.size.x - 0.01, 0.5 * line_height);
+    assert_eq!(tb.hover_text(&first_line_end, &narrow, rt, &VIEWPORT), Some(end1));
+    let below = OFFSET + Point::new(1., narrow.size.y + 10.);
+    assert_eq!(tb.nearest_text_pos(&below, &narrow, rt, &VIEWPORT), TEXT.len());
+}
+
+//SDL is initialized once per test binary, so the cases share a window
+#[test]
+fn test_text_wrap() {
+    with_render_text(|rt| {
+        wrap_keeps_text(rt);
+        rewrap_on_resize(rt);
+        caret_across_lines(rt);
+    });
+}